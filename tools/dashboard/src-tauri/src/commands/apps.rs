@@ -9,6 +9,7 @@ pub struct AppInfo {
     pub path: String,
     pub has_config: bool,
     pub environments: Vec<pio_parser::DiscoveredEnvironment>,
+    pub ini_diagnostics: Vec<pio_parser::IniDiagnostic>,
     pub config_schema: config_schema::AppConfigSchema,
 }
 
@@ -55,28 +56,24 @@ pub fn discover_apps() -> Result<MonorepoInfo, String> {
             .to_string();
 
         // Parse platformio.ini
-        let environments = match pio_parser::parse_platformio_ini(&platformio_ini) {
-            Ok(config) => config.environments,
-            Err(e) => {
-                eprintln!("Warning: Failed to parse {}/platformio.ini: {}", name, e);
-                Vec::new()
-            }
-        };
+        let (environments, ini_diagnostics) =
+            match pio_parser::parse_platformio_ini(&platformio_ini) {
+                Ok(config) => (config.environments, config.diagnostics),
+                Err(e) => {
+                    eprintln!("Warning: Failed to parse {}/platformio.ini: {}", name, e);
+                    (Vec::new(), Vec::new())
+                }
+            };
 
         // Parse config.h schema
-        let config_schema = config_schema::parse_config_schema(&path).unwrap_or_else(|_| {
-            config_schema::AppConfigSchema {
-                has_config: false,
-                defines: Vec::new(),
-                platform_conditional: std::collections::HashMap::new(),
-            }
-        });
+        let config_schema = config_schema::parse_config_schema(&path).unwrap_or_default();
 
         apps.push(AppInfo {
             name,
             path: path.to_string_lossy().to_string(),
             has_config: config_schema.has_config,
             environments,
+            ini_diagnostics,
             config_schema,
         });
     }
@@ -99,23 +96,18 @@ pub fn get_app_info(app_name: String) -> Result<AppInfo, String> {
     let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
 
     let platformio_ini = app_path.join("platformio.ini");
-    let environments = pio_parser::parse_platformio_ini(&platformio_ini)
-        .map(|c| c.environments)
+    let (environments, ini_diagnostics) = pio_parser::parse_platformio_ini(&platformio_ini)
+        .map(|c| (c.environments, c.diagnostics))
         .unwrap_or_default();
 
-    let config_schema = config_schema::parse_config_schema(&app_path).unwrap_or_else(|_| {
-        config_schema::AppConfigSchema {
-            has_config: false,
-            defines: Vec::new(),
-            platform_conditional: std::collections::HashMap::new(),
-        }
-    });
+    let config_schema = config_schema::parse_config_schema(&app_path).unwrap_or_default();
 
     Ok(AppInfo {
         name: app_name,
         path: app_path.to_string_lossy().to_string(),
         has_config: config_schema.has_config,
         environments,
+        ini_diagnostics,
         config_schema,
     })
 }
@@ -1,7 +1,17 @@
+use crate::commands::pio::{validate_build_flags, validate_environment_name};
 use crate::utils::{
-    pin_validator::{self, Module, PinPurpose, PinValidation, Platform},
+    config_schema::{self, ConstraintViolation, ResolvedConfig},
+    ini_document::IniDocument,
+    monorepo, path_security,
+    pin_database::{self, PinProfile},
+    pin_validator::{self, Module, PinMapReport, PinPurpose, PinValidation, Platform},
+    pio_parser::{self, DefineComparison},
+    power::{self, PowerOptions, PowerReport},
     profile_paths,
+    resource_planner::{self, ResourcePlan},
 };
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -27,6 +37,42 @@ fn get_profiles_dir() -> Result<PathBuf, String> {
     Ok(profiles_dir)
 }
 
+/// Parses a platform name as used by the frontend or platformio.ini.
+fn parse_platform(platform: &str) -> Result<Platform, String> {
+    match platform.to_lowercase().as_str() {
+        "esp32" | "espressif32" => Ok(Platform::Esp32),
+        "esp8266" | "espressif8266" => Ok(Platform::Esp8266),
+        "avr" | "atmelavr" => Ok(Platform::Avr),
+        _ => Err(format!("Unknown platform: {}", platform)),
+    }
+}
+
+fn parse_module(module: Option<String>) -> Option<Module> {
+    module.and_then(|m| match m.to_lowercase().as_str() {
+        "wrover" => Some(Module::Wrover),
+        "pico" => Some(Module::Pico),
+        "standard" => Some(Module::Standard),
+        _ => None,
+    })
+}
+
+/// Resolves the pin profile for a PlatformIO board id, falling back to the legacy
+/// platform/module selection when no board is given.
+fn resolve_pin_profile(
+    platform: &str,
+    module: Option<String>,
+    board: Option<String>,
+) -> Result<PinProfile, String> {
+    match board {
+        Some(board) => pin_database::environment_profile(platform, Some(&board))
+            .ok_or_else(|| format!("Unknown board: {}", board)),
+        None => Ok(pin_validator::platform_profile(
+            parse_platform(platform)?,
+            parse_module(module),
+        )),
+    }
+}
+
 /// Validates a GPIO pin configuration.
 #[tauri::command]
 pub fn validate_pin(
@@ -34,6 +80,7 @@ pub fn validate_pin(
     purpose: String,
     platform: String,
     module: Option<String>,
+    board: Option<String>,
 ) -> Result<PinValidation, String> {
     let purpose = match purpose.to_lowercase().as_str() {
         "output" => PinPurpose::Output,
@@ -46,41 +93,355 @@ pub fn validate_pin(
         _ => return Err(format!("Unknown pin purpose: {}", purpose)),
     };
 
-    let platform = match platform.to_lowercase().as_str() {
-        "esp32" | "espressif32" => Platform::Esp32,
-        "esp8266" | "espressif8266" => Platform::Esp8266,
-        "avr" | "atmelavr" => Platform::Avr,
-        _ => return Err(format!("Unknown platform: {}", platform)),
+    let profile = resolve_pin_profile(&platform, module, board)?;
+
+    Ok(pin_validator::validate_board_pin(pin, purpose, &profile))
+}
+
+/// Gets the list of safe GPIO pins for a platform or board.
+#[tauri::command]
+pub fn get_safe_pins(
+    platform: String,
+    module: Option<String>,
+    board: Option<String>,
+) -> Result<Vec<u8>, String> {
+    let profile = resolve_pin_profile(&platform, module, board)?;
+
+    Ok(profile.safe_pins())
+}
+
+/// Lists the PlatformIO board ids the pin database knows about.
+#[tauri::command]
+pub fn list_pin_boards() -> Result<Vec<String>, String> {
+    Ok(pin_database::supported_boards())
+}
+
+/// Compares a profile's define overrides against the defines its environment inherits.
+#[tauri::command]
+pub fn compare_profile_defines(
+    app_name: String,
+    environment: String,
+    defines: HashMap<String, String>,
+) -> Result<Vec<DefineComparison>, String> {
+    info!(app = %app_name, env = %environment, "Comparing profile defines");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+
+    let env = config
+        .environments
+        .iter()
+        .find(|e| e.name == environment)
+        .ok_or_else(|| format!("Environment '{}' not found in {}", environment, app_name))?;
+
+    Ok(pio_parser::compare_profile_defines(env, &defines))
+}
+
+/// Validates all pin defines of an app together, using the config.h defaults overridden by
+/// `defines`. When an environment is given, its board selects the pin profile and only
+/// defines its platform selects are checked; otherwise `platform`/`module` are used.
+#[tauri::command]
+pub fn validate_pin_map(
+    app_name: String,
+    environment: Option<String>,
+    defines: HashMap<String, String>,
+    platform: Option<String>,
+    module: Option<String>,
+) -> Result<PinMapReport, String> {
+    info!(app = %app_name, env = ?environment, "Validating pin map");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let schema = config_schema::parse_config_schema(&app_path)?;
+
+    let profile = match (&environment, platform) {
+        (Some(environment), _) => {
+            let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+            let env = config
+                .environments
+                .iter()
+                .find(|e| e.name == *environment)
+                .ok_or_else(|| {
+                    format!("Environment '{}' not found in {}", environment, app_name)
+                })?;
+            pin_database::environment_profile(&env.platform, env.board.as_deref()).ok_or_else(
+                || {
+                    format!(
+                        "Environment '{}' has no pin data for platform {}",
+                        environment, env.platform
+                    )
+                },
+            )?
+        }
+        (None, Some(platform)) => {
+            pin_validator::platform_profile(parse_platform(&platform)?, parse_module(module))
+        }
+        (None, None) => return Err("Either an environment or a platform is required".to_string()),
     };
 
-    let module = module.and_then(|m| match m.to_lowercase().as_str() {
-        "wrover" => Some(Module::Wrover),
-        "pico" => Some(Module::Pico),
-        "standard" => Some(Module::Standard),
-        _ => None,
-    });
+    let pins = pin_validator::schema_pins(&schema, environment.as_deref(), &defines, &profile);
+    let wifi_enabled = pin_validator::app_uses_wifi(&app_path);
+    Ok(pin_validator::validate_pin_map(
+        &pins,
+        &profile,
+        wifi_enabled,
+    ))
+}
+
+/// Compares the LED strips, PWM outputs and audio inputs an environment would build with
+/// its chip's RMT, LEDC and I2S limits.
+#[tauri::command]
+pub fn plan_peripheral_resources(
+    app_name: String,
+    environment: String,
+    defines: HashMap<String, String>,
+) -> Result<ResourcePlan, String> {
+    info!(app = %app_name, env = %environment, "Planning peripheral resources");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
 
-    Ok(pin_validator::validate_pin(pin, purpose, platform, module))
+    let env = config
+        .environments
+        .iter()
+        .find(|e| e.name == environment)
+        .ok_or_else(|| format!("Environment '{}' not found in {}", environment, app_name))?;
+
+    resource_planner::plan_resources(&app_path, env, &defines)
 }
 
-/// Gets the list of safe GPIO pins for a platform.
+/// Calculates current draw, PSU and fuse sizing, injection points and voltage drop for an
+/// app's resolved config at a brightness cap.
 #[tauri::command]
-pub fn get_safe_pins(platform: String, module: Option<String>) -> Result<Vec<u8>, String> {
-    let platform = match platform.to_lowercase().as_str() {
-        "esp32" | "espressif32" => Platform::Esp32,
-        "esp8266" | "espressif8266" => Platform::Esp8266,
-        "avr" | "atmelavr" => Platform::Avr,
-        _ => return Err(format!("Unknown platform: {}", platform)),
+pub fn calculate_power_budget(
+    app_name: String,
+    environment: Option<String>,
+    defines: HashMap<String, String>,
+    options: PowerOptions,
+) -> Result<PowerReport, String> {
+    info!(app = %app_name, env = ?environment, "Calculating power budget");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+
+    let config;
+    let env = match &environment {
+        Some(environment) => {
+            config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+            let env = config
+                .environments
+                .iter()
+                .find(|e| &e.name == environment)
+                .ok_or_else(|| {
+                    format!("Environment '{}' not found in {}", environment, app_name)
+                })?;
+            Some(env)
+        }
+        None => None,
     };
 
-    let module = module.and_then(|m| match m.to_lowercase().as_str() {
-        "wrover" => Some(Module::Wrover),
-        "pico" => Some(Module::Pico),
-        "standard" => Some(Module::Standard),
-        _ => None,
+    power::app_power_budget(&app_path, env, &defines, &options)
+}
+
+/// Checks proposed define values against the `#error` guards in the app's config.h.
+#[tauri::command]
+pub fn validate_define_values(
+    app_name: String,
+    defines: HashMap<String, String>,
+) -> Result<Vec<ConstraintViolation>, String> {
+    info!(app = %app_name, count = defines.len(), "Validating define values");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let schema = config_schema::parse_config_schema(&app_path)?;
+
+    Ok(config_schema::validate_define_values(&schema, &defines))
+}
+
+/// Resolves every config.h define, including derived ones such as NUM_PIXELS and FRAME_MS,
+/// for a set of define overrides. When an environment is given, its platform macro and
+/// build flag defines are applied underneath the overrides.
+#[tauri::command]
+pub fn resolve_config_values(
+    app_name: String,
+    environment: Option<String>,
+    defines: HashMap<String, String>,
+) -> Result<ResolvedConfig, String> {
+    info!(app = %app_name, env = ?environment, "Resolving config values");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+
+    let mut flags: HashMap<String, String> = HashMap::new();
+    if let Some(environment) = environment {
+        let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+        let env = config
+            .environments
+            .iter()
+            .find(|e| e.name == environment)
+            .ok_or_else(|| format!("Environment '{}' not found in {}", environment, app_name))?;
+
+        flags = config_schema::environment_macros(env);
+    }
+    flags.extend(defines);
+
+    config_schema::resolve_config_values(&app_path, &flags)
+}
+
+/// Options that `update_environment_list` is allowed to edit.
+const EDITABLE_LIST_OPTIONS: &[&str] = &["build_flags", "lib_deps"];
+
+/// Checks `lib_deps` entries before they are written to platformio.ini.
+///
+/// Only registry specs (`owner/name@version`) and plain https URLs are accepted, so an
+/// entry can never smuggle in a `symlink://`/`file://` source or extra ini syntax.
+fn validate_lib_deps(lib_deps: &[String]) -> Result<(), String> {
+    static REGISTRY_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"^(?:[A-Za-z0-9][A-Za-z0-9_.-]*/)?[A-Za-z0-9][A-Za-z0-9_.-]*(?: [A-Za-z0-9_.-]+)*(?:@[~^]?[A-Za-z0-9.*+-]+)?$",
+        )
+        .expect("registry lib spec regex")
     });
+    static URL_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^https://[A-Za-z0-9.-]+(?::[0-9]+)?/[A-Za-z0-9._~/-]+(?:#[A-Za-z0-9._/-]+)?$")
+            .expect("url lib spec regex")
+    });
+
+    for spec in lib_deps {
+        let spec = spec.trim();
+        if !REGISTRY_RE.is_match(spec) && !URL_RE.is_match(spec) {
+            return Err(format!(
+                "Invalid library (expected owner/name@version or an https URL): {}",
+                spec
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Resolves an app's platformio.ini inside the monorepo.
+fn app_ini_path(app_name: &str) -> Result<PathBuf, String> {
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, app_name)?;
+    Ok(app_path.join("platformio.ini"))
+}
+
+/// Converts profile defines into sorted `-D` flags.
+fn profile_build_flags(defines: &HashMap<String, String>) -> Result<Vec<String>, String> {
+    let mut names: Vec<&String> = defines.keys().collect();
+    names.sort();
+
+    let flags: Vec<String> = names
+        .into_iter()
+        .map(|name| {
+            let value = defines[name].trim();
+            if value.is_empty() {
+                format!("-D{}", name)
+            } else {
+                format!("-D{}={}", name, value)
+            }
+        })
+        .collect();
+    validate_build_flags(&flags)?;
+    Ok(flags)
+}
+
+/// Adds an environment to an app's platformio.ini that extends the profile's environment
+/// and applies the profile's defines as build flags.
+#[tauri::command]
+pub fn create_environment_from_profile(
+    app_name: String,
+    profile_name: String,
+    environment: String,
+) -> Result<(), String> {
+    info!(
+        app = %app_name,
+        profile = %profile_name,
+        env = %environment,
+        "Creating environment from profile"
+    );
+    validate_environment_name(&environment)?;
+    let profile = load_profile(app_name.clone(), profile_name)?;
+    let ini_path = app_ini_path(&app_name)?;
+    let mut doc = IniDocument::load(&ini_path)?;
+
+    let base_section = format!("env:{}", profile.environment);
+    if !doc.has_section(&base_section) {
+        return Err(format!(
+            "Environment '{}' not found in {}",
+            profile.environment, app_name
+        ));
+    }
+
+    // A child's build_flags replace the parent's, so pull the parent's in explicitly
+    let mut flags = vec![format!("${{{}.build_flags}}", base_section)];
+    flags.extend(profile_build_flags(&profile.defines)?);
+
+    let section = format!("env:{}", environment);
+    doc.add_section(&section)?;
+    doc.set(&section, "extends", &base_section)?;
+    doc.set_list(&section, "build_flags", &flags)?;
+    doc.save(&ini_path)
+}
 
-    Ok(pin_validator::get_safe_pins(platform, module))
+/// Removes an environment from an app's platformio.ini.
+#[tauri::command]
+pub fn remove_environment(app_name: String, environment: String) -> Result<(), String> {
+    info!(app = %app_name, env = %environment, "Removing environment");
+    validate_environment_name(&environment)?;
+    let ini_path = app_ini_path(&app_name)?;
+    let mut doc = IniDocument::load(&ini_path)?;
+
+    if !doc.remove_section(&format!("env:{}", environment)) {
+        return Err(format!(
+            "Environment '{}' not found in {}",
+            environment, app_name
+        ));
+    }
+    doc.save(&ini_path)
+}
+
+/// Adds and removes `build_flags` or `lib_deps` entries of an environment.
+#[tauri::command]
+pub fn update_environment_list(
+    app_name: String,
+    environment: String,
+    option: String,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Vec<String>, String> {
+    info!(app = %app_name, env = %environment, option = %option, "Updating environment list");
+    validate_environment_name(&environment)?;
+    if !EDITABLE_LIST_OPTIONS.contains(&option.as_str()) {
+        return Err(format!("Option '{}' cannot be edited", option));
+    }
+    if add
+        .iter()
+        .any(|item| item.trim().is_empty() || item.contains('\n') || item.contains('\r'))
+    {
+        return Err(format!("Invalid {} entry", option));
+    }
+    match option.as_str() {
+        "build_flags" => validate_build_flags(&add)?,
+        "lib_deps" => validate_lib_deps(&add)?,
+        _ => {}
+    }
+
+    let ini_path = app_ini_path(&app_name)?;
+    let mut doc = IniDocument::load(&ini_path)?;
+    let section = format!("env:{}", environment);
+    if !doc.has_section(&section) {
+        return Err(format!(
+            "Environment '{}' not found in {}",
+            environment, app_name
+        ));
+    }
+
+    for item in &remove {
+        doc.remove_list_item(&section, &option, item)?;
+    }
+    for item in &add {
+        doc.add_list_item(&section, &option, item)?;
+    }
+    doc.save(&ini_path)?;
+
+    Ok(doc.get_list(&section, &option))
 }
 
 /// Saves a configuration profile.
@@ -100,8 +461,8 @@ pub fn save_profile(profile: SavedProfile) -> Result<(), String> {
     let file_name = format!("{}_{}.json", safe_app, safe_name);
     let file_path = profiles_dir.join(&file_name);
 
-    let json =
-        serde_json::to_string_pretty(&profile).map_err(|e| format!("Failed to serialize: {}", e))?;
+    let json = serde_json::to_string_pretty(&profile)
+        .map_err(|e| format!("Failed to serialize: {}", e))?;
 
     fs::write(&file_path, json).map_err(|e| format!("Failed to write profile: {}", e))?;
 
@@ -113,8 +474,9 @@ pub fn save_profile(profile: SavedProfile) -> Result<(), String> {
 pub fn load_profile(app_name: String, profile_name: String) -> Result<SavedProfile, String> {
     info!(app = %app_name, profile = %profile_name, "Loading profile");
     let profiles_dir = get_profiles_dir()?;
-    let profiles_dir_canon =
-        profiles_dir.canonicalize().map_err(|e| format!("Failed to resolve profiles dir: {}", e))?;
+    let profiles_dir_canon = profiles_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve profiles dir: {}", e))?;
     let safe_app = profile_paths::sanitize_profile_component("app name", &app_name)?;
     let safe_profile = profile_paths::sanitize_profile_component("profile name", &profile_name)?;
     let file_name = format!("{}_{}.json", safe_app, safe_profile);
@@ -127,8 +489,8 @@ pub fn load_profile(app_name: String, profile_name: String) -> Result<SavedProfi
         return Err("Profile path escapes profiles directory".to_string());
     }
 
-    let json = fs::read_to_string(&file_path)
-        .map_err(|e| format!("Failed to read profile: {}", e))?;
+    let json =
+        fs::read_to_string(&file_path).map_err(|e| format!("Failed to read profile: {}", e))?;
 
     let profile: SavedProfile =
         serde_json::from_str(&json).map_err(|e| format!("Failed to parse profile: {}", e))?;
@@ -172,8 +534,9 @@ pub fn list_profiles(app_name: String) -> Result<Vec<String>, String> {
 pub fn delete_profile(app_name: String, profile_name: String) -> Result<(), String> {
     info!(app = %app_name, profile = %profile_name, "Deleting profile");
     let profiles_dir = get_profiles_dir()?;
-    let profiles_dir_canon =
-        profiles_dir.canonicalize().map_err(|e| format!("Failed to resolve profiles dir: {}", e))?;
+    let profiles_dir_canon = profiles_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve profiles dir: {}", e))?;
     let safe_app = profile_paths::sanitize_profile_component("app name", &app_name)?;
     let safe_profile = profile_paths::sanitize_profile_component("profile name", &profile_name)?;
     let file_name = format!("{}_{}.json", safe_app, safe_profile);
@@ -191,3 +554,39 @@ pub fn delete_profile(app_name: String, profile_name: String) -> Result<(), Stri
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_lib_deps_accept_registry_and_https_specs() {
+        assert!(validate_lib_deps(&specs(&[
+            "makuna/NeoPixelBus@^2.8.0",
+            "throwtheswitch/Unity@^2.6.0",
+            "ArduinoJson",
+            "Adafruit GFX Library@~1.11.9",
+            "https://github.com/me-no-dev/ESPAsyncWebServer.git#v1.2.4",
+        ]))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_lib_deps_reject_other_sources() {
+        for spec in [
+            "symlink://../../shared/lib/LEDStrip",
+            "file:///etc",
+            "http://example.com/lib.zip",
+            "owner/name@$(touch pwned)",
+            "owner/name; rm -rf /",
+            "!echo hi",
+            "name = value",
+        ] {
+            assert!(validate_lib_deps(&specs(&[spec])).is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::commands::pio::validate_environment_name;
+use crate::utils::build_history::{self, BuildRecord, BuildRun, HistoryFilter, RunDiff};
+use crate::utils::firmware_size::{self, FirmwareSizeReport};
+use crate::utils::{monorepo, path_security};
+use std::fs;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Gets the build history directory, next to the profiles directory.
+pub(crate) fn get_history_dir() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or("Could not find config directory")?;
+    let history_dir = config_dir.join("rgbw-dashboard").join("history");
+    fs::create_dir_all(&history_dir)
+        .map_err(|e| format!("Failed to create history directory: {}", e))?;
+    Ok(history_dir)
+}
+
+/// Gets the directory of per app/environment firmware size reports.
+pub(crate) fn get_sizes_dir() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or("Could not find config directory")?;
+    let sizes_dir = config_dir.join("rgbw-dashboard").join("sizes");
+    fs::create_dir_all(&sizes_dir)
+        .map_err(|e| format!("Failed to create sizes directory: {}", e))?;
+    Ok(sizes_dir)
+}
+
+/// Gets the directory recording what `data/` held at each app/environment's last
+/// filesystem upload.
+pub(crate) fn get_filesystem_dir() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or("Could not find config directory")?;
+    let filesystem_dir = config_dir.join("rgbw-dashboard").join("filesystem");
+    fs::create_dir_all(&filesystem_dir)
+        .map_err(|e| format!("Failed to create filesystem directory: {}", e))?;
+    Ok(filesystem_dir)
+}
+
+/// Lists past builds, uploads, test runs and cleans, newest first.
+#[tauri::command]
+pub fn list_build_history(filter: Option<HistoryFilter>) -> Result<Vec<BuildRecord>, String> {
+    build_history::list_records(&get_history_dir()?, &filter.unwrap_or_default())
+}
+
+/// Reopens a past run with its full log.
+#[tauri::command]
+pub fn get_build_run(run_id: String) -> Result<BuildRun, String> {
+    info!(run = %run_id, "Opening build run");
+    build_history::load_run(&get_history_dir()?, &run_id)
+}
+
+/// Compares the build flags of two past runs.
+#[tauri::command]
+pub fn diff_build_runs(base_id: String, other_id: String) -> Result<RunDiff, String> {
+    let history_dir = get_history_dir()?;
+    let base = build_history::load_run(&history_dir, &base_id)?.record;
+    let other = build_history::load_run(&history_dir, &other_id)?.record;
+    let flags = build_history::diff_flags(&base.build_flags, &other.build_flags);
+    Ok(RunDiff { base, other, flags })
+}
+
+/// Returns the firmware size reports of an app/environment, oldest first.
+#[tauri::command]
+pub fn get_firmware_size_history(
+    app_name: String,
+    environment: String,
+) -> Result<Vec<FirmwareSizeReport>, String> {
+    validate_environment_name(&environment)?;
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    path_security::validate_app_path(&monorepo_path, &app_name)?;
+    firmware_size::load_reports(&get_sizes_dir()?, &app_name, &environment)
+}
@@ -0,0 +1,781 @@
+use crate::commands::history::get_history_dir;
+use crate::commands::pio::{emit_build_event, BuildEvent};
+use crate::commands::serial::lock_recover;
+use crate::utils::build_history::{self, BuildRecord, HistoryLog, MAX_HISTORY_RUNS};
+use crate::utils::diagnostics::DiagnosticCollector;
+use crate::utils::firmware_size::ReportedUsage;
+use crate::utils::test_report::{self, TestOutputParser, TestReport};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tauri::{AppHandle, Runtime, State};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::{watch, OwnedMutexGuard};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const PIO_COMMAND_TIMEOUT_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Build,
+    Upload,
+    Test,
+    Clean,
+    BuildFs,
+    UploadFs,
+}
+
+impl JobKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Build => "build",
+            JobKind::Upload => "upload",
+            JobKind::Test => "test",
+            JobKind::Clean => "clean",
+            JobKind::BuildFs => "buildfs",
+            JobKind::UploadFs => "uploadfs",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            JobKind::Build => "Build",
+            JobKind::Upload => "Upload",
+            JobKind::Test => "Tests",
+            JobKind::Clean => "Clean",
+            JobKind::BuildFs => "Filesystem build",
+            JobKind::UploadFs => "Filesystem upload",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for another job on the same app to finish.
+    Queued,
+    Running,
+    Cancelling,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub app_name: String,
+    pub environment: Option<String>,
+    pub status: JobStatus,
+    /// Unix timestamp in seconds.
+    pub created_at: u64,
+}
+
+/// What a job runs, as recorded in the build history.
+pub struct JobRequest<'a> {
+    pub kind: JobKind,
+    pub app_name: &'a str,
+    pub environment: Option<&'a str>,
+    /// Diagnostic paths are reported relative to this.
+    pub monorepo_path: &'a Path,
+    pub app_path: &'a Path,
+    pub build_flags: &'a [String],
+    /// Saved profile the build flags came from, if any.
+    pub profile: Option<&'a str>,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    pid: Option<u32>,
+    cancel_tx: watch::Sender<bool>,
+}
+
+type JobTable = Arc<Mutex<HashMap<String, JobEntry>>>;
+type AppLocks = Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>;
+
+/// Tracks running PlatformIO jobs. Jobs for the same app directory run one at a time so
+/// they never share `.pio/build`; jobs for different apps run in parallel.
+#[derive(Default)]
+pub struct JobManager {
+    jobs: JobTable,
+    app_locks: AppLocks,
+    /// Where build history is recorded. Defaults to the dashboard's config directory.
+    history_dir: Option<PathBuf>,
+}
+
+impl JobManager {
+    /// Registers a job. It stays listed until the returned handle is dropped.
+    pub fn create_job(&self, request: JobRequest) -> Result<Job, String> {
+        let JobRequest {
+            kind,
+            app_name,
+            environment,
+            monorepo_path,
+            app_path,
+            ..
+        } = request;
+        let id = Uuid::new_v4().to_string();
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let app_lock = lock_recover(&self.app_locks, "app_locks")?
+            .entry(app_path.to_path_buf())
+            .or_default()
+            .clone();
+
+        lock_recover(&self.jobs, "jobs")?.insert(
+            id.clone(),
+            JobEntry {
+                info: JobInfo {
+                    id: id.clone(),
+                    kind,
+                    app_name: app_name.to_string(),
+                    environment: environment.map(str::to_string),
+                    status: JobStatus::Queued,
+                    created_at,
+                },
+                pid: None,
+                cancel_tx,
+            },
+        );
+
+        info!(job = %id, app = %app_name, kind = ?kind, "Created job");
+        let history_dir = match &self.history_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => get_history_dir(),
+        };
+        let history = JobHistory::start(history_dir, &id, created_at, &request);
+        Ok(Job {
+            id,
+            kind,
+            app_name: app_name.to_string(),
+            environment: environment.map(str::to_string),
+            jobs: self.jobs.clone(),
+            cancel_rx,
+            app_locks: self.app_locks.clone(),
+            app_path: app_path.to_path_buf(),
+            app_lock,
+            started: OnceLock::new(),
+            history,
+            diagnostics: Arc::new(Mutex::new(DiagnosticCollector::new(
+                monorepo_path,
+                app_path,
+            ))),
+            usage: Arc::new(Mutex::new(ReportedUsage::default())),
+            tests: Arc::new(Mutex::new(TestOutputParser::default())),
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<JobInfo>, String> {
+        let jobs = lock_recover(&self.jobs, "jobs")?;
+        let mut infos: Vec<JobInfo> = jobs.values().map(|entry| entry.info.clone()).collect();
+        infos.sort_by_key(|info| info.created_at);
+        Ok(infos)
+    }
+
+    /// Cancels a queued job, or kills a running job's process tree.
+    pub fn cancel(&self, job_id: &str) -> Result<(), String> {
+        let mut jobs = lock_recover(&self.jobs, "jobs")?;
+        let entry = jobs
+            .get_mut(job_id)
+            .ok_or_else(|| format!("Job not found: {}", job_id))?;
+
+        entry.info.status = JobStatus::Cancelling;
+        entry.cancel_tx.send_replace(true);
+        if let Some(pid) = entry.pid {
+            kill_process_tree(pid);
+        }
+        Ok(())
+    }
+}
+
+/// A job's pending history record and the log its output is written to.
+struct JobHistory {
+    dir: PathBuf,
+    record: Mutex<Option<BuildRecord>>,
+    log: Arc<Mutex<HistoryLog>>,
+}
+
+impl JobHistory {
+    /// Starts recording a job. History is best-effort and never stops a job from running.
+    fn start(
+        history_dir: Result<PathBuf, String>,
+        id: &str,
+        started_at: u64,
+        request: &JobRequest,
+    ) -> Option<Self> {
+        let started = history_dir.and_then(|dir| {
+            let log = HistoryLog::create(&dir, id)?;
+            Ok((dir, log))
+        });
+        let (dir, log) = match started {
+            Ok(started) => started,
+            Err(e) => {
+                warn!(job = %id, "Not recording build history: {}", e);
+                return None;
+            }
+        };
+
+        let git = build_history::git_revision(request.app_path);
+        let record = BuildRecord {
+            id: id.to_string(),
+            kind: request.kind.as_str().to_string(),
+            app_name: request.app_name.to_string(),
+            environment: request.environment.map(str::to_string),
+            build_flags: request.build_flags.to_vec(),
+            profile: request.profile.map(str::to_string),
+            git_dirty: git.as_ref().is_some_and(|(_, dirty)| *dirty),
+            git_commit: git.map(|(commit, _)| commit),
+            started_at,
+            duration_ms: 0,
+            success: false,
+            exit_code: None,
+            error: None,
+        };
+
+        Some(Self {
+            dir,
+            record: Mutex::new(Some(record)),
+            log: Arc::new(Mutex::new(log)),
+        })
+    }
+
+    fn finish(
+        &self,
+        duration_ms: u64,
+        success: bool,
+        exit_code: Option<i32>,
+        error: Option<String>,
+    ) {
+        if let Ok(mut log) = lock_recover(&self.log, "history_log") {
+            log.flush();
+        }
+        let Some(mut record) = lock_recover(&self.record, "history_record")
+            .ok()
+            .and_then(|mut record| record.take())
+        else {
+            return;
+        };
+
+        record.duration_ms = duration_ms;
+        record.success = success;
+        record.exit_code = exit_code;
+        record.error = error;
+        if let Err(e) = build_history::save_record(&self.dir, &record, MAX_HISTORY_RUNS) {
+            warn!(job = %record.id, "Failed to save build history: {}", e);
+        }
+    }
+}
+
+/// A registered job. Dropping it removes the job from the manager.
+pub struct Job {
+    pub id: String,
+    kind: JobKind,
+    app_name: String,
+    environment: Option<String>,
+    jobs: JobTable,
+    cancel_rx: watch::Receiver<bool>,
+    app_locks: AppLocks,
+    app_path: PathBuf,
+    app_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Set once the job holds its app lock, so time spent queued isn't counted.
+    started: OnceLock<Instant>,
+    history: Option<JobHistory>,
+    diagnostics: Arc<Mutex<DiagnosticCollector>>,
+    usage: Arc<Mutex<ReportedUsage>>,
+    tests: Arc<Mutex<TestOutputParser>>,
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let Ok(mut jobs) = lock_recover(&self.jobs, "jobs") {
+            jobs.remove(&self.id);
+        }
+        // Once only the map and this job hold the lock, no other job is using the app
+        if let Ok(mut app_locks) = lock_recover(&self.app_locks, "app_locks") {
+            if Arc::strong_count(&self.app_lock) <= 2 {
+                app_locks.remove(&self.app_path);
+            }
+        }
+    }
+}
+
+impl Job {
+    pub fn emit<R: Runtime>(&self, app_handle: &AppHandle<R>, event: BuildEvent) {
+        match &event {
+            BuildEvent::Warning { message } => self.log_line(&format!("warning: {}", message)),
+            BuildEvent::Error { message } => self.log_line(&format!("error: {}", message)),
+            _ => {}
+        }
+        emit_build_event(app_handle, &self.id, event);
+    }
+
+    fn log_line(&self, line: &str) {
+        if let Some(history) = &self.history {
+            if let Ok(mut log) = lock_recover(&history.log, "history_log") {
+                log.write_line(line);
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        *self.cancel_rx.borrow()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut JobEntry)) {
+        if let Ok(mut jobs) = lock_recover(&self.jobs, "jobs") {
+            if let Some(entry) = jobs.get_mut(&self.id) {
+                f(entry);
+            }
+        }
+    }
+
+    /// Waits until no other job is using the app directory, then marks the job running.
+    /// Hold the returned guard for the rest of the job.
+    pub async fn acquire_app<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+    ) -> Result<OwnedMutexGuard<()>, String> {
+        let guard = match self.app_lock.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                self.emit(
+                    app_handle,
+                    BuildEvent::Queued {
+                        app_name: self.app_name.clone(),
+                        environment: self.environment.clone().unwrap_or_default(),
+                    },
+                );
+                let mut cancel_rx = self.cancel_rx.clone();
+                tokio::select! {
+                    guard = self.app_lock.clone().lock_owned() => guard,
+                    _ = cancel_rx.wait_for(|cancelled| *cancelled) => {
+                        return Err(self.fail(app_handle, format!("{} cancelled", self.kind.label())));
+                    }
+                }
+            }
+        };
+
+        if self.is_cancelled() {
+            return Err(self.fail(app_handle, format!("{} cancelled", self.kind.label())));
+        }
+        self.update(|entry| {
+            if entry.info.status == JobStatus::Queued {
+                entry.info.status = JobStatus::Running;
+            }
+        });
+        self.started.get_or_init(Instant::now);
+        Ok(guard)
+    }
+
+    /// Spawns a PlatformIO command and streams its output until it exits, times out or is
+    /// cancelled, then emits the completion event.
+    pub async fn run<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        cmd: Command,
+    ) -> Result<bool, String> {
+        let status = self.execute(app_handle, cmd).await?;
+        Ok(self.finish(app_handle, status))
+    }
+
+    /// Like `run`, but leaves the completion event to `finish` so callers can report on
+    /// the build products first. Failures to start, cancellation and timeouts still complete
+    /// the job.
+    pub async fn execute<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        mut cmd: Command,
+    ) -> Result<ExitStatus, String> {
+        cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .env("PYTHONUNBUFFERED", "1"); // Force unbuffered Python output
+
+        // Own process group so cancellation also reaches scons, the compilers and esptool
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return Err(self.fail(app_handle, format!("Failed to start PlatformIO: {}", e)))
+            }
+        };
+        let pid = child.id();
+        self.update(|entry| entry.pid = pid);
+        if self.is_cancelled() {
+            if let Some(pid) = pid {
+                kill_process_tree(pid);
+            }
+        }
+
+        let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            if let Some(pid) = pid {
+                kill_process_tree(pid);
+            }
+            let _ = child.kill().await;
+            self.update(|entry| entry.pid = None);
+            return Err(self.fail(
+                app_handle,
+                "Failed to capture PlatformIO output".to_string(),
+            ));
+        };
+        let stdout_task = self.stream_lines(app_handle, stdout);
+        let stderr_task = self.stream_lines(app_handle, stderr);
+
+        let timeout_duration = Duration::from_secs(PIO_COMMAND_TIMEOUT_SECS);
+        let status = timeout(timeout_duration, child.wait()).await;
+        if status.is_err() {
+            if let Some(pid) = pid {
+                kill_process_tree(pid);
+            }
+            let _ = child.kill().await;
+            let _ = child.wait().await;
+        }
+        // The process group is gone, so a later cancel must not signal a reused ID
+        self.update(|entry| entry.pid = None);
+        // Wait for readers to finish
+        let _ = tokio::join!(stdout_task, stderr_task);
+
+        let label = self.kind.label();
+        let status = match status {
+            _ if self.is_cancelled() => {
+                return Err(self.fail(app_handle, format!("{} cancelled", label)));
+            }
+            Ok(Ok(status)) => status,
+            Ok(Err(e)) => {
+                return Err(self.fail(app_handle, format!("Failed to wait for PlatformIO: {}", e)));
+            }
+            Err(_) => {
+                return Err(self.fail(
+                    app_handle,
+                    format!(
+                        "{} timed out after {} seconds",
+                        label, PIO_COMMAND_TIMEOUT_SECS
+                    ),
+                ));
+            }
+        };
+
+        Ok(status)
+    }
+
+    /// Emits the completion event for a process started with `execute`.
+    pub fn finish<R: Runtime>(&self, app_handle: &AppHandle<R>, status: ExitStatus) -> bool {
+        let success = status.success();
+        self.complete(app_handle, success, status.code(), None);
+        success
+    }
+
+    /// Per-test results from `pio test`, read from its JSON report when one was written.
+    pub fn test_report(&self, json_path: &Path, success: bool) -> TestReport {
+        match lock_recover(&self.tests, "tests") {
+            Ok(tests) => test_report::load_report(json_path, &tests, success),
+            Err(_) => test_report::load_report(json_path, &TestOutputParser::default(), success),
+        }
+    }
+
+    /// The RAM/Flash usage PlatformIO printed, if the command linked firmware.
+    pub fn reported_usage(&self) -> ReportedUsage {
+        lock_recover(&self.usage, "usage")
+            .map(|usage| usage.clone())
+            .unwrap_or_default()
+    }
+
+    fn stream_lines<R, T>(&self, app_handle: &AppHandle<R>, reader: T) -> JoinHandle<()>
+    where
+        R: Runtime,
+        T: AsyncRead + Unpin + Send + 'static,
+    {
+        let app_handle = app_handle.clone();
+        let job_id = self.id.clone();
+        let log = self.history.as_ref().map(|history| history.log.clone());
+        let diagnostics = self.diagnostics.clone();
+        let usage = self.usage.clone();
+        let tests = self.tests.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(log) = &log {
+                    if let Ok(mut log) = lock_recover(log, "history_log") {
+                        log.write_line(&line);
+                    }
+                }
+                if let Ok(mut usage) = lock_recover(&usage, "usage") {
+                    usage.push_line(&line);
+                }
+                if let Ok(mut tests) = lock_recover(&tests, "tests") {
+                    tests.push_line(&line);
+                }
+                let diagnostic = lock_recover(&diagnostics, "diagnostics")
+                    .ok()
+                    .and_then(|mut diagnostics| diagnostics.push_line(&line));
+                emit_build_event(&app_handle, &job_id, BuildEvent::Output { line });
+                if let Some(diagnostic) = diagnostic {
+                    emit_build_event(&app_handle, &job_id, BuildEvent::Diagnostic(diagnostic));
+                }
+            }
+        })
+    }
+
+    /// Emits the completion event and records the run in the build history.
+    fn complete<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        success: bool,
+        exit_code: Option<i32>,
+        error: Option<String>,
+    ) {
+        let duration_ms = self
+            .started
+            .get()
+            .map_or(0, |started| started.elapsed().as_millis() as u64);
+        let diagnostics = lock_recover(&self.diagnostics, "diagnostics")
+            .map(|diagnostics| diagnostics.summary())
+            .unwrap_or_default();
+        self.emit(
+            app_handle,
+            BuildEvent::Complete {
+                success,
+                duration_ms,
+                diagnostics,
+            },
+        );
+        if let Some(history) = &self.history {
+            history.finish(duration_ms, success, exit_code, error);
+        }
+    }
+
+    /// Reports a failed job and returns the message for the command's error.
+    pub fn fail<R: Runtime>(&self, app_handle: &AppHandle<R>, message: String) -> String {
+        self.emit(
+            app_handle,
+            BuildEvent::Error {
+                message: message.clone(),
+            },
+        );
+        self.complete(app_handle, false, None, Some(message.clone()));
+        message
+    }
+}
+
+/// Kills a job's process together with everything it started.
+fn kill_process_tree(pid: u32) {
+    // The job runs in its own process group, so signal the whole group
+    #[cfg(unix)]
+    let result = std::process::Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", pid)])
+        .status();
+
+    #[cfg(not(unix))]
+    let result = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status();
+
+    if let Err(e) = result {
+        warn!(pid, "Failed to kill process tree: {}", e);
+    }
+}
+
+/// Lists queued and running jobs, oldest first.
+#[tauri::command]
+pub fn list_jobs(jobs: State<'_, JobManager>) -> Result<Vec<JobInfo>, String> {
+    jobs.list()
+}
+
+/// Cancels a job by ID, killing its PlatformIO process tree if it is running.
+#[tauri::command]
+pub fn cancel_job(jobs: State<'_, JobManager>, job_id: String) -> Result<(), String> {
+    info!(job = %job_id, "Cancelling job");
+    jobs.cancel(&job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::Listener;
+    use tempfile::tempdir;
+
+    fn manager(history_dir: &Path) -> JobManager {
+        JobManager {
+            history_dir: Some(history_dir.to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    fn request<'a>(app_path: &'a Path) -> JobRequest<'a> {
+        JobRequest {
+            kind: JobKind::Build,
+            app_name: "led-panel",
+            environment: Some("esp32"),
+            monorepo_path: app_path,
+            app_path,
+            build_flags: &[],
+            profile: None,
+        }
+    }
+
+    /// Collects the build events emitted for one job.
+    fn record_events(
+        app_handle: &AppHandle<MockRuntime>,
+        job_id: &str,
+    ) -> Arc<Mutex<Vec<serde_json::Value>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let job_id = job_id.to_string();
+        app_handle.listen("build-event", move |event| {
+            let payload: serde_json::Value = serde_json::from_str(event.payload()).unwrap();
+            if payload["job_id"] == job_id.as_str() {
+                recorded.lock().unwrap().push(payload);
+            }
+        });
+        events
+    }
+
+    fn event_types(events: &Mutex<Vec<serde_json::Value>>) -> Vec<String> {
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| event["type"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_jobs_for_same_app_run_one_at_a_time() {
+        let temp = tempdir().unwrap();
+        let app = mock_app();
+        let jobs = manager(temp.path());
+        let first = jobs.create_job(request(temp.path())).unwrap();
+        let second = jobs.create_job(request(temp.path())).unwrap();
+        let events = record_events(app.handle(), &second.id);
+
+        let first_guard = first.acquire_app(app.handle()).await.unwrap();
+        let waiting = second.acquire_app(app.handle());
+        tokio::pin!(waiting);
+        assert!(timeout(Duration::from_millis(200), &mut waiting)
+            .await
+            .is_err());
+        assert_eq!(event_types(&events), vec!["queued"]);
+        let status = |id: &str| {
+            let infos = jobs.list().unwrap();
+            infos.iter().find(|info| info.id == id).unwrap().status
+        };
+        assert_eq!(status(&first.id), JobStatus::Running);
+        assert_eq!(status(&second.id), JobStatus::Queued);
+
+        drop(first_guard);
+        let _second_guard = waiting.await.unwrap();
+        assert!(second
+            .run(app.handle(), Command::new("true"))
+            .await
+            .unwrap());
+        // A cancel after the process exited must not signal its reused process group
+        assert!(jobs.jobs.lock().unwrap()[&second.id].pid.is_none());
+
+        // The time spent queued behind the first job is not part of the duration
+        let complete = events.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(complete["type"], "complete");
+        assert!(complete["duration_ms"].as_u64().unwrap() < 200);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_for_different_apps_run_in_parallel() {
+        let temp = tempdir().unwrap();
+        let (led_panel, dj_booth) = (temp.path().join("led-panel"), temp.path().join("dj-booth"));
+        let app = mock_app();
+        let jobs = manager(temp.path());
+        let first = jobs.create_job(request(&led_panel)).unwrap();
+        let second = jobs.create_job(request(&dj_booth)).unwrap();
+
+        let first_guard = first.acquire_app(app.handle()).await.unwrap();
+        let second_guard = timeout(Duration::from_millis(200), second.acquire_app(app.handle()))
+            .await
+            .expect("second app should not wait for the first")
+            .unwrap();
+        assert!(jobs
+            .list()
+            .unwrap()
+            .iter()
+            .all(|job| job.status == JobStatus::Running));
+
+        drop((first_guard, second_guard));
+        drop((first, second));
+        assert!(jobs.list().unwrap().is_empty());
+        assert!(jobs.app_locks.lock().unwrap().is_empty());
+    }
+
+    /// True while `pid` exists and has not exited.
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: u32) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .map(|stat| {
+                !stat
+                    .rsplit(')')
+                    .next()
+                    .unwrap_or("")
+                    .trim_start()
+                    .starts_with('Z')
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cancel_kills_process_group() {
+        let temp = tempdir().unwrap();
+        let app = mock_app();
+        let jobs = manager(temp.path());
+        let job = jobs.create_job(request(temp.path())).unwrap();
+        let events = record_events(app.handle(), &job.id);
+        let _guard = job.acquire_app(app.handle()).await.unwrap();
+
+        // The shell prints the PID of a background child that must die with it
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $!; wait"]);
+        let cancel = async {
+            let child = loop {
+                let line = events
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|event| event["type"] == "output")
+                    .map(|event| event["line"].as_str().unwrap().to_string());
+                if let Some(line) = line {
+                    break line.parse::<u32>().unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            };
+            assert!(is_running(child));
+            jobs.cancel(&job.id).unwrap();
+            child
+        };
+        let (result, child) = timeout(Duration::from_secs(10), async {
+            tokio::join!(job.run(app.handle(), cmd), cancel)
+        })
+        .await
+        .expect("cancelled job should finish promptly");
+
+        assert_eq!(result, Err("Build cancelled".to_string()));
+        assert_eq!(event_types(&events), vec!["output", "error", "complete"]);
+        let complete = events.lock().unwrap()[2].clone();
+        assert_eq!(complete["success"], false);
+        for _ in 0..100 {
+            if !is_running(child) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!is_running(child));
+    }
+}
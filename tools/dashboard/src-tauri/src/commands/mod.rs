@@ -1,4 +1,7 @@
 pub mod apps;
 pub mod config;
+pub mod history;
+pub mod jobs;
 pub mod pio;
+pub mod secrets;
 pub mod serial;
@@ -1,16 +1,28 @@
-use crate::utils::{monorepo, path_security, pio_path};
+use crate::commands::history::{get_filesystem_dir, get_sizes_dir};
+use crate::commands::jobs::{Job, JobKind, JobManager, JobRequest};
+use crate::commands::secrets::prepare_build_secrets;
+use crate::commands::serial::{upload_port_status, SerialState, UploadPortGuard};
+use crate::utils::{
+    diagnostics::{Diagnostic, DiagnosticSummary},
+    filesystem_image::{self, FileStamp, FilesystemReport, UploadedData},
+    firmware_size::{self, FirmwareSizeReport},
+    monorepo, path_security,
+    pin_validator::Severity,
+    pio_parser::{self, DiscoveredEnvironment},
+    pio_path,
+    power::{self, PowerOptions},
+    preflight::{self, PreflightCheck, PreflightContext, PreflightFinding, PreflightReport},
+    resource_planner,
+    test_report::{self, TestReport},
+};
 use serde::{Deserialize, Serialize};
 use serialport::available_ports;
-use std::collections::HashMap;
-use std::process::Stdio;
-use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Runtime, State};
 use tokio::process::Command;
-use tokio::time::{timeout, Duration};
 use tracing::{info, warn};
 
-const PIO_COMMAND_TIMEOUT_SECS: u64 = 600;
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum BuildEvent {
@@ -18,13 +30,64 @@ pub enum BuildEvent {
     Output { line: String },
     #[serde(rename = "error")]
     Error { message: String },
+    #[serde(rename = "warning")]
+    Warning { message: String },
+    /// A compiler, linker or PlatformIO problem recognized in the output.
+    #[serde(rename = "diagnostic")]
+    Diagnostic(Diagnostic),
+    /// RAM/Flash usage of a successful build, with the change since the previous one.
+    #[serde(rename = "size_report")]
+    SizeReport(FirmwareSizeReport),
+    /// `data/` and the filesystem image against their partition and the last upload.
+    #[serde(rename = "filesystem_report")]
+    FilesystemReport(FilesystemReport),
+    /// Per-suite and per-case results of a test run.
+    #[serde(rename = "test_report")]
+    TestReport(TestReport),
     #[serde(rename = "complete")]
-    Complete { success: bool, duration_ms: u64 },
+    Complete {
+        success: bool,
+        duration_ms: u64,
+        diagnostics: DiagnosticSummary,
+    },
     #[serde(rename = "started")]
-    Started { app_name: String, environment: String },
+    Started {
+        app_name: String,
+        environment: String,
+    },
+    #[serde(rename = "queued")]
+    Queued {
+        app_name: String,
+        environment: String,
+    },
+}
+
+/// A `BuildEvent` tagged with the job it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobEvent {
+    pub job_id: String,
+    #[serde(flatten)]
+    pub event: BuildEvent,
 }
 
-fn validate_environment_name(name: &str) -> Result<(), String> {
+/// Optional extras for `run_build`, `run_upload` and `run_uploadfs`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunOptions {
+    /// Unlocks the secrets store so secret headers are generated for the run.
+    #[serde(default)]
+    pub secrets_passphrase: Option<String>,
+    /// Wiring to check the power budget against before spawning PlatformIO.
+    #[serde(default)]
+    pub power_check: Option<PowerOptions>,
+    /// Run even when pre-flight checks report errors.
+    #[serde(default)]
+    pub ignore_preflight_errors: bool,
+    /// Saved profile the build flags came from, recorded in the build history.
+    #[serde(default)]
+    pub profile_name: Option<String>,
+}
+
+pub(crate) fn validate_environment_name(name: &str) -> Result<(), String> {
     if name.trim().is_empty() {
         return Err("Environment name cannot be empty".to_string());
     }
@@ -43,7 +106,7 @@ fn validate_environment_name(name: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn validate_build_flags(build_flags: &[String]) -> Result<(), String> {
+pub(crate) fn validate_build_flags(build_flags: &[String]) -> Result<(), String> {
     for flag in build_flags {
         if !flag.starts_with("-D") {
             return Err(format!("Invalid build flag (expected -D...): {}", flag));
@@ -70,7 +133,10 @@ fn validate_build_flags(build_flags: &[String]) -> Result<(), String> {
                 .chars()
                 .any(|c| matches!(c, '`' | '$' | ';' | '|' | '&' | '<' | '>'))
             {
-                return Err(format!("Build flag contains forbidden characters: {}", flag));
+                return Err(format!(
+                    "Build flag contains forbidden characters: {}",
+                    flag
+                ));
             }
         }
     }
@@ -95,147 +161,399 @@ fn validate_upload_port(port: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn emit_build_event(app_handle: &AppHandle, event: BuildEvent) {
-    if let Err(e) = app_handle.emit("build-event", event) {
+/// Emits a build event tagged with its job ID.
+pub(crate) fn emit_build_event<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    job_id: &str,
+    event: BuildEvent,
+) {
+    let payload = JobEvent {
+        job_id: job_id.to_string(),
+        event,
+    };
+    if let Err(e) = app_handle.emit("build-event", payload) {
         warn!("Failed to emit build event: {}", e);
     }
 }
 
-async fn wait_with_timeout(
-    child: &mut tokio::process::Child,
-    timeout_duration: Duration,
-) -> Result<Option<std::process::ExitStatus>, String> {
-    match timeout(timeout_duration, child.wait()).await {
-        Ok(status) => status
-            .map(Some)
-            .map_err(|e| format!("Failed to wait for PlatformIO: {}", e)),
-        Err(_) => {
-            let _ = child.kill().await;
-            let _ = child.wait().await;
-            Ok(None)
+/// Warns when the build would claim more RMT, I2S or LEDC channels than the target has.
+/// Planning problems are logged and never block the build.
+fn warn_resource_oversubscription(
+    app_handle: &AppHandle,
+    job: &Job,
+    app_path: &Path,
+    environment: &str,
+    build_flags: &[String],
+) {
+    let plan =
+        pio_parser::parse_platformio_ini(&app_path.join("platformio.ini")).and_then(|config| {
+            let env = config
+                .environments
+                .iter()
+                .find(|e| e.name == environment)
+                .ok_or_else(|| format!("Environment '{}' not found", environment))?;
+            resource_planner::plan_resources(app_path, env, &pio_parser::flag_defines(build_flags))
+        });
+
+    match plan {
+        Ok(plan) => {
+            for message in plan.warnings {
+                job.emit(app_handle, BuildEvent::Warning { message });
+            }
+        }
+        Err(e) => warn!(env = %environment, "Skipping peripheral resource check: {}", e),
+    }
+}
+
+/// Warns when the environment's partition table is invalid, or when the last recorded
+/// firmware size no longer fits its app partition. The build still runs.
+fn warn_partition_layout(
+    app_handle: &AppHandle,
+    job: &Job,
+    app_path: &Path,
+    app_name: &str,
+    environment: &str,
+) {
+    let layout =
+        pio_parser::parse_platformio_ini(&app_path.join("platformio.ini")).and_then(|config| {
+            config
+                .environments
+                .into_iter()
+                .find(|e| e.name == environment)
+                .map(|env| env.partitions)
+                .ok_or_else(|| format!("Environment '{}' not found", environment))
+        });
+    let layout = match layout {
+        Ok(Some(layout)) => layout,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(env = %environment, "Skipping partition table check: {}", e);
+            return;
+        }
+    };
+
+    let last_size = get_sizes_dir()
+        .and_then(|dir| firmware_size::load_reports(&dir, app_name, environment))
+        .ok()
+        .and_then(|reports| reports.last()?.flash)
+        .and_then(|flash| layout.check_app_fits(flash.used));
+    for issue in layout.issues.iter().chain(&last_size) {
+        if matches!(issue.severity, Severity::Warning | Severity::Error) {
+            job.emit(
+                app_handle,
+                BuildEvent::Warning {
+                    message: format!("Partition table: {}", issue.message),
+                },
+            );
+        }
+    }
+}
+
+/// Warns about power budget problems (voltage drop, wire rating, undersized PSU) for the
+/// wiring the user described. Like the resource check, this never blocks the build.
+fn warn_power_budget(
+    app_handle: &AppHandle,
+    job: &Job,
+    app_path: &Path,
+    environment: &str,
+    build_flags: &[String],
+    options: &PowerOptions,
+) {
+    let report =
+        pio_parser::parse_platformio_ini(&app_path.join("platformio.ini")).and_then(|config| {
+            let env = config
+                .environments
+                .iter()
+                .find(|e| e.name == environment)
+                .ok_or_else(|| format!("Environment '{}' not found", environment))?;
+            power::app_power_budget(
+                app_path,
+                Some(env),
+                &pio_parser::flag_defines(build_flags),
+                options,
+            )
+        });
+
+    match report {
+        Ok(report) => {
+            for message in report.warnings {
+                job.emit(app_handle, BuildEvent::Warning { message });
+            }
+        }
+        Err(e) => warn!(env = %environment, "Skipping power budget check: {}", e),
+    }
+}
+
+/// Reports the firmware size of a successful build, records it for the app/environment
+/// and warns when the firmware nearly fills its partition.
+/// The user's build flags, plus the linker map flag for hardware targets so the size report
+/// can break usage down per object file. Host builds keep their flags as-is since not every
+/// host linker accepts `-Map`.
+fn platformio_build_flags(
+    app_path: &Path,
+    environment: &str,
+    build_flags: &[String],
+) -> Vec<String> {
+    let mut flags = build_flags.to_vec();
+    let is_hardware_target = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))
+        .ok()
+        .and_then(|config| {
+            config
+                .environments
+                .into_iter()
+                .find(|e| e.name == environment)
+        })
+        .is_some_and(|env| env.is_hardware_target);
+    if is_hardware_target {
+        flags.push(firmware_size::linker_map_flag());
+    }
+    flags
+}
+
+async fn report_firmware_size(
+    app_handle: &AppHandle,
+    job: &Job,
+    app_path: &Path,
+    app_name: &str,
+    environment: &str,
+) {
+    let recorded_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let usage = job.reported_usage();
+    let (app_path, app_name, env_name) = (
+        app_path.to_path_buf(),
+        app_name.to_string(),
+        environment.to_string(),
+    );
+    // `size` and `nm` are run synchronously and can take a while on large firmware
+    let report = tokio::task::spawn_blocking(move || {
+        let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+        let env = config
+            .environments
+            .iter()
+            .find(|e| e.name == env_name)
+            .ok_or_else(|| format!("Environment '{}' not found", env_name))?;
+        if !env.is_hardware_target {
+            return Ok(None);
         }
+        let report = firmware_size::build_report(&app_path, &app_name, env, usage, recorded_at);
+        firmware_size::record_report(&get_sizes_dir()?, report).map(Some)
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Size report task failed: {}", e)));
+
+    match report {
+        Ok(Some(report)) => {
+            for message in firmware_size::size_warnings(&report) {
+                job.emit(app_handle, BuildEvent::Warning { message });
+            }
+            if report.top_objects.is_empty() {
+                job.emit(
+                    app_handle,
+                    BuildEvent::Warning {
+                        message: "The build wrote no usable linker map, so the size report has \
+                                  no per-object breakdown"
+                            .to_string(),
+                    },
+                );
+            }
+            job.emit(app_handle, BuildEvent::SizeReport(report));
+        }
+        Ok(None) => {}
+        Err(e) => warn!(env = %environment, "Skipping firmware size report: {}", e),
+    }
+}
+
+/// Runs the pre-flight checks for building (and, with a port, uploading) an environment.
+#[allow(clippy::too_many_arguments)]
+fn preflight_report(
+    state: &SerialState,
+    monorepo_path: &Path,
+    app_path: &Path,
+    environment: &str,
+    build_flags: &[String],
+    upload_port: Option<&str>,
+    secrets_unlocked: bool,
+    checks: &[PreflightCheck],
+) -> Result<PreflightReport, String> {
+    let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+    let env = config
+        .environments
+        .iter()
+        .find(|e| e.name == environment)
+        .ok_or_else(|| format!("Environment '{}' not found", environment))?;
+    let upload_port = upload_port
+        .map(|port| upload_port_status(state, port))
+        .transpose()?;
+
+    let ctx = PreflightContext {
+        monorepo_path,
+        app_path,
+        env,
+        defines: pio_parser::flag_defines(build_flags),
+        secrets_unlocked,
+        upload_port,
+    };
+    Ok(preflight::run_preflight(&ctx, checks))
+}
+
+/// Fails with the pre-flight errors unless the caller chose to ignore them, otherwise
+/// returns the findings to report once the run has started.
+fn enforce_preflight(
+    report: PreflightReport,
+    ignore_errors: bool,
+) -> Result<Vec<PreflightFinding>, String> {
+    if !report.passed && !ignore_errors {
+        let errors: Vec<&str> = report.errors().map(|f| f.message.as_str()).collect();
+        return Err(format!("Pre-flight checks failed:\n{}", errors.join("\n")));
+    }
+    Ok(report.findings)
+}
+
+fn emit_preflight_findings(app_handle: &AppHandle, job: &Job, findings: Vec<PreflightFinding>) {
+    for finding in findings {
+        let message = match finding.severity {
+            Severity::Error => format!("Ignored pre-flight error: {}", finding.message),
+            Severity::Warning => finding.message,
+            Severity::Ok | Severity::Info => continue,
+        };
+        job.emit(app_handle, BuildEvent::Warning { message });
+    }
+}
+
+/// Runs the pre-flight checks for a build or upload without starting it.
+#[tauri::command]
+pub fn run_preflight_checks(
+    state: State<'_, SerialState>,
+    app_name: String,
+    environment: String,
+    build_flags: Vec<String>,
+    upload_port: Option<String>,
+    secrets_unlocked: bool,
+) -> Result<PreflightReport, String> {
+    validate_environment_name(&environment)?;
+    validate_build_flags(&build_flags)?;
+    if let Some(ref port) = upload_port {
+        validate_upload_port(port)?;
     }
+
+    info!(app = %app_name, env = %environment, "Running pre-flight checks");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    preflight_report(
+        &state,
+        &monorepo_path,
+        &app_path,
+        &environment,
+        &build_flags,
+        upload_port.as_deref(),
+        secrets_unlocked,
+        preflight::DEFAULT_CHECKS,
+    )
 }
 
 /// Runs a PlatformIO build command with streaming output.
 #[tauri::command]
 pub async fn run_build(
     app_handle: AppHandle,
+    state: State<'_, SerialState>,
+    jobs: State<'_, JobManager>,
     app_name: String,
     environment: String,
     build_flags: Vec<String>,
+    options: Option<RunOptions>,
 ) -> Result<bool, String> {
     validate_environment_name(&environment)?;
     validate_build_flags(&build_flags)?;
 
+    let options = options.unwrap_or_default();
+
     info!(app = %app_name, env = %environment, "Starting build");
     let monorepo_path = monorepo::find_monorepo_root()?;
-    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
     let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let preflight = preflight_report(
+        &state,
+        &monorepo_path,
+        &app_path,
+        &environment,
+        &build_flags,
+        None,
+        options.secrets_passphrase.is_some(),
+        preflight::DEFAULT_CHECKS,
+    )?;
+    let preflight_findings = enforce_preflight(preflight, options.ignore_preflight_errors)?;
+    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
+
+    let job = jobs.create_job(JobRequest {
+        kind: JobKind::Build,
+        app_name: &app_name,
+        environment: Some(&environment),
+        monorepo_path: &monorepo_path,
+        app_path: &app_path,
+        build_flags: &build_flags,
+        profile: options.profile_name.as_deref(),
+    })?;
+    let _app_lock = job.acquire_app(&app_handle).await?;
+    // Generated secret headers are removed when this guard drops at the end of the run
+    let _secrets =
+        prepare_build_secrets(&app_name, &app_path, options.secrets_passphrase.as_deref())
+            .map_err(|e| job.fail(&app_handle, e))?;
 
     // Emit started event
-    emit_build_event(
+    job.emit(
         &app_handle,
         BuildEvent::Started {
             app_name: app_name.clone(),
             environment: environment.clone(),
         },
     );
-
-    let start_time = std::time::Instant::now();
+    emit_preflight_findings(&app_handle, &job, preflight_findings);
+    warn_resource_oversubscription(&app_handle, &job, &app_path, &environment, &build_flags);
+    warn_partition_layout(&app_handle, &job, &app_path, &app_name, &environment);
+    if let Some(power_check) = &options.power_check {
+        warn_power_budget(
+            &app_handle,
+            &job,
+            &app_path,
+            &environment,
+            &build_flags,
+            power_check,
+        );
+    }
 
     let mut cmd = Command::new(&pio_path);
     cmd.arg("run")
         .arg("-e")
         .arg(&environment)
-        .current_dir(&app_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .env("PYTHONUNBUFFERED", "1"); // Force unbuffered Python output
+        .current_dir(&app_path);
 
-    // Inject build flags via environment variable
-    if !build_flags.is_empty() {
-        cmd.env("PLATFORMIO_BUILD_FLAGS", build_flags.join(" "));
+    // Inject build flags, plus the linker map the size report reads, via environment variable
+    let pio_build_flags = platformio_build_flags(&app_path, &environment, &build_flags);
+    if !pio_build_flags.is_empty() {
+        cmd.env("PLATFORMIO_BUILD_FLAGS", pio_build_flags.join(" "));
     }
 
-    let mut child = cmd.spawn().map_err(|e| format!("Failed to start PlatformIO: {}", e))?;
-
-    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
-    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
-
-    let app_handle_out = app_handle.clone();
-    let app_handle_err = app_handle.clone();
-
-    // Spawn stdout reader
-    let stdout_task = tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_out, BuildEvent::Output { line });
-        }
-    });
-
-    // Spawn stderr reader
-    let stderr_task = tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_err, BuildEvent::Output { line });
-        }
-    });
-
-    let timeout_duration = Duration::from_secs(PIO_COMMAND_TIMEOUT_SECS);
-    let status = wait_with_timeout(&mut child, timeout_duration).await?;
-    // Wait for readers to finish
-    let _ = tokio::join!(stdout_task, stderr_task);
-
-    let duration_ms = start_time.elapsed().as_millis() as u64;
-    let success = match status {
-        Some(status) => status.success(),
-        None => {
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Error {
-                    message: format!(
-                        "Build timed out after {} seconds",
-                        PIO_COMMAND_TIMEOUT_SECS
-                    ),
-                },
-            );
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Complete {
-                    success: false,
-                    duration_ms,
-                },
-            );
-            return Err(format!(
-                "Build timed out after {} seconds",
-                PIO_COMMAND_TIMEOUT_SECS
-            ));
-        }
-    };
-
-    // Emit completion event
-    emit_build_event(
-        &app_handle,
-        BuildEvent::Complete {
-            success,
-            duration_ms,
-        },
-    );
-
-    Ok(success)
+    let status = job.execute(&app_handle, cmd).await?;
+    if status.success() {
+        report_firmware_size(&app_handle, &job, &app_path, &app_name, &environment).await;
+    }
+    Ok(job.finish(&app_handle, status))
 }
 
 /// Runs a PlatformIO upload command with streaming output.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn run_upload(
     app_handle: AppHandle,
+    state: State<'_, SerialState>,
+    jobs: State<'_, JobManager>,
     app_name: String,
     environment: String,
     build_flags: Vec<String>,
     upload_port: Option<String>,
+    options: Option<RunOptions>,
 ) -> Result<bool, String> {
     validate_environment_name(&environment)?;
     validate_build_flags(&build_flags)?;
@@ -243,21 +561,60 @@ pub async fn run_upload(
         validate_upload_port(port)?;
     }
 
+    let options = options.unwrap_or_default();
+
     info!(app = %app_name, env = %environment, "Starting upload");
     let monorepo_path = monorepo::find_monorepo_root()?;
-    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
     let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let preflight = preflight_report(
+        &state,
+        &monorepo_path,
+        &app_path,
+        &environment,
+        &build_flags,
+        upload_port.as_deref(),
+        options.secrets_passphrase.is_some(),
+        preflight::DEFAULT_CHECKS,
+    )?;
+    let preflight_findings = enforce_preflight(preflight, options.ignore_preflight_errors)?;
+    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
+
+    let job = jobs.create_job(JobRequest {
+        kind: JobKind::Upload,
+        app_name: &app_name,
+        environment: Some(&environment),
+        monorepo_path: &monorepo_path,
+        app_path: &app_path,
+        build_flags: &build_flags,
+        profile: options.profile_name.as_deref(),
+    })?;
+    let _app_lock = job.acquire_app(&app_handle).await?;
+    // Generated secret headers are removed when this guard drops at the end of the run
+    let _secrets =
+        prepare_build_secrets(&app_name, &app_path, options.secrets_passphrase.as_deref())
+            .map_err(|e| job.fail(&app_handle, e))?;
 
     // Emit started event
-    emit_build_event(
+    job.emit(
         &app_handle,
         BuildEvent::Started {
             app_name: app_name.clone(),
             environment: environment.clone(),
         },
     );
-
-    let start_time = std::time::Instant::now();
+    emit_preflight_findings(&app_handle, &job, preflight_findings);
+    warn_resource_oversubscription(&app_handle, &job, &app_path, &environment, &build_flags);
+    warn_partition_layout(&app_handle, &job, &app_path, &app_name, &environment);
+    if let Some(power_check) = &options.power_check {
+        warn_power_budget(
+            &app_handle,
+            &job,
+            &app_path,
+            &environment,
+            &build_flags,
+            power_check,
+        );
+    }
 
     let mut cmd = Command::new(&pio_path);
     cmd.arg("run")
@@ -265,14 +622,12 @@ pub async fn run_upload(
         .arg(&environment)
         .arg("-t")
         .arg("upload")
-        .current_dir(&app_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .env("PYTHONUNBUFFERED", "1");
+        .current_dir(&app_path);
 
-    // Inject build flags
-    if !build_flags.is_empty() {
-        cmd.env("PLATFORMIO_BUILD_FLAGS", build_flags.join(" "));
+    // Inject build flags, plus the linker map the size report reads, via environment variable
+    let pio_build_flags = platformio_build_flags(&app_path, &environment, &build_flags);
+    if !pio_build_flags.is_empty() {
+        cmd.env("PLATFORMIO_BUILD_FLAGS", pio_build_flags.join(" "));
     }
 
     // Set upload port if specified
@@ -280,177 +635,331 @@ pub async fn run_upload(
         cmd.env("PLATFORMIO_UPLOAD_PORT", port);
     }
 
-    let mut child = cmd.spawn().map_err(|e| format!("Failed to start PlatformIO: {}", e))?;
+    let status = job.execute(&app_handle, cmd).await?;
+    if status.success() {
+        report_firmware_size(&app_handle, &job, &app_path, &app_name, &environment).await;
+    }
+    Ok(job.finish(&app_handle, status))
+}
 
-    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
-    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+/// Finds an environment that builds a filesystem image, and the project's `data/` directory.
+fn filesystem_environment(
+    app_path: &Path,
+    environment: &str,
+) -> Result<(DiscoveredEnvironment, PathBuf), String> {
+    let config = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))?;
+    let env = config
+        .environments
+        .into_iter()
+        .find(|e| e.name == environment)
+        .ok_or_else(|| format!("Environment '{}' not found in platformio.ini", environment))?;
+    if !env.has_filesystem_image {
+        return Err(format!(
+            "Environment '{}' has no filesystem image. Set board_build.filesystem or add a data/ directory.",
+            environment
+        ));
+    }
+    Ok((env, PathBuf::from(config.data_dir)))
+}
 
-    let app_handle_out = app_handle.clone();
-    let app_handle_err = app_handle.clone();
+/// Describes `data/` and the built image, comparing against the last upload.
+fn filesystem_report(
+    app_path: &Path,
+    app_name: &str,
+    env: &DiscoveredEnvironment,
+    files: &BTreeMap<String, FileStamp>,
+) -> FilesystemReport {
+    let uploaded = get_filesystem_dir()
+        .and_then(|dir| filesystem_image::load_uploaded(&dir, app_name, &env.name))
+        .unwrap_or_else(|e| {
+            warn!(env = %env.name, "Ignoring last filesystem upload: {}", e);
+            None
+        });
+    filesystem_image::filesystem_report(app_path, env, files, uploaded.as_ref())
+}
 
-    let stdout_task = tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_out, BuildEvent::Output { line });
-        }
-    });
+fn emit_filesystem_report(app_handle: &AppHandle, job: &Job, report: &FilesystemReport) {
+    for message in filesystem_image::size_warnings(report) {
+        job.emit(app_handle, BuildEvent::Warning { message });
+    }
+    job.emit(app_handle, BuildEvent::FilesystemReport(report.clone()));
+}
 
-    let stderr_task = tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_err, BuildEvent::Output { line });
-        }
-    });
+/// Reports whether `data/` changed since the last filesystem upload and how it fits the
+/// filesystem partition.
+#[tauri::command]
+pub fn get_filesystem_status(
+    app_name: String,
+    environment: String,
+) -> Result<FilesystemReport, String> {
+    validate_environment_name(&environment)?;
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let (env, data_dir) = filesystem_environment(&app_path, &environment)?;
+    let files = filesystem_image::scan_data_dir(&data_dir)?;
+    Ok(filesystem_report(&app_path, &app_name, &env, &files))
+}
 
-    let timeout_duration = Duration::from_secs(PIO_COMMAND_TIMEOUT_SECS);
-    let status = wait_with_timeout(&mut child, timeout_duration).await?;
-
-    let _ = tokio::join!(stdout_task, stderr_task);
-
-    let duration_ms = start_time.elapsed().as_millis() as u64;
-    let success = match status {
-        Some(status) => status.success(),
-        None => {
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Error {
-                    message: format!(
-                        "Upload timed out after {} seconds",
-                        PIO_COMMAND_TIMEOUT_SECS
-                    ),
-                },
-            );
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Complete {
-                    success: false,
-                    duration_ms,
-                },
-            );
-            return Err(format!(
-                "Upload timed out after {} seconds",
-                PIO_COMMAND_TIMEOUT_SECS
-            ));
-        }
-    };
+/// Builds the filesystem image from `data/` (`pio run -t buildfs`).
+#[tauri::command]
+pub async fn run_buildfs(
+    app_handle: AppHandle,
+    jobs: State<'_, JobManager>,
+    app_name: String,
+    environment: String,
+) -> Result<bool, String> {
+    validate_environment_name(&environment)?;
 
-    emit_build_event(
+    info!(app = %app_name, env = %environment, "Starting filesystem build");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let (env, data_dir) = filesystem_environment(&app_path, &environment)?;
+
+    let job = jobs.create_job(JobRequest {
+        kind: JobKind::BuildFs,
+        app_name: &app_name,
+        environment: Some(&environment),
+        monorepo_path: &monorepo_path,
+        app_path: &app_path,
+        build_flags: &[],
+        profile: None,
+    })?;
+    let _app_lock = job.acquire_app(&app_handle).await?;
+
+    job.emit(
         &app_handle,
-        BuildEvent::Complete {
-            success,
-            duration_ms,
+        BuildEvent::Started {
+            app_name: app_name.clone(),
+            environment: environment.clone(),
         },
     );
 
-    Ok(success)
+    let mut cmd = Command::new(&pio_path);
+    cmd.arg("run")
+        .arg("-e")
+        .arg(&environment)
+        .arg("-t")
+        .arg("buildfs")
+        .current_dir(&app_path);
+
+    let status = job.execute(&app_handle, cmd).await?;
+    if status.success() {
+        match filesystem_image::scan_data_dir(&data_dir) {
+            Ok(files) => {
+                let report = filesystem_report(&app_path, &app_name, &env, &files);
+                emit_filesystem_report(&app_handle, &job, &report);
+            }
+            Err(e) => warn!(env = %environment, "Skipping filesystem report: {}", e),
+        }
+    }
+    Ok(job.finish(&app_handle, status))
 }
 
-/// Runs PlatformIO tests for an environment.
+/// Builds the filesystem image, checks it fits its partition, then flashes it
+/// (`pio run -t uploadfs`) while holding the upload port's lock.
 #[tauri::command]
-pub async fn run_tests(
+#[allow(clippy::too_many_arguments)]
+pub async fn run_uploadfs(
     app_handle: AppHandle,
+    state: State<'_, SerialState>,
+    jobs: State<'_, JobManager>,
     app_name: String,
     environment: String,
+    upload_port: Option<String>,
+    options: Option<RunOptions>,
 ) -> Result<bool, String> {
     validate_environment_name(&environment)?;
+    if let Some(ref port) = upload_port {
+        validate_upload_port(port)?;
+    }
 
-    info!(app = %app_name, env = %environment, "Starting tests");
+    let options = options.unwrap_or_default();
+
+    info!(app = %app_name, env = %environment, "Starting filesystem upload");
     let monorepo_path = monorepo::find_monorepo_root()?;
-    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
     let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    let (env, data_dir) = filesystem_environment(&app_path, &environment)?;
+    // Without a selected port, fall back to platformio.ini's upload_port when it passes the
+    // same validation as a selected one
+    let upload_port = upload_port.or_else(|| {
+        env.upload
+            .port
+            .clone()
+            .filter(|port| validate_upload_port(port).is_ok())
+    });
+    let preflight = preflight_report(
+        &state,
+        &monorepo_path,
+        &app_path,
+        &environment,
+        &[],
+        upload_port.as_deref(),
+        false,
+        preflight::FILESYSTEM_UPLOAD_CHECKS,
+    )?;
+    let preflight_findings = enforce_preflight(preflight, options.ignore_preflight_errors)?;
+    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
 
-    emit_build_event(
+    let job = jobs.create_job(JobRequest {
+        kind: JobKind::UploadFs,
+        app_name: &app_name,
+        environment: Some(&environment),
+        monorepo_path: &monorepo_path,
+        app_path: &app_path,
+        build_flags: &[],
+        profile: None,
+    })?;
+    let _app_lock = job.acquire_app(&app_handle).await?;
+    // Closes any monitor on the port and keeps it closed until the upload finishes
+    let _port_lock = match upload_port.as_deref() {
+        Some(port) => {
+            Some(UploadPortGuard::acquire(&state, port).map_err(|e| job.fail(&app_handle, e))?)
+        }
+        None => None,
+    };
+
+    job.emit(
         &app_handle,
         BuildEvent::Started {
             app_name: app_name.clone(),
             environment: environment.clone(),
         },
     );
+    emit_preflight_findings(&app_handle, &job, preflight_findings);
+    if upload_port.is_none() {
+        job.emit(
+            &app_handle,
+            BuildEvent::Warning {
+                message: "No upload port selected. PlatformIO will pick one, and serial monitors on it are not closed first.".to_string(),
+            },
+        );
+    }
 
-    let start_time = std::time::Instant::now();
+    let files = filesystem_image::scan_data_dir(&data_dir).map_err(|e| job.fail(&app_handle, e))?;
 
-    let mut cmd = Command::new(&pio_path);
-    cmd.arg("test")
+    let mut buildfs = Command::new(&pio_path);
+    buildfs
+        .arg("run")
         .arg("-e")
         .arg(&environment)
-        .current_dir(&app_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .env("PYTHONUNBUFFERED", "1");
-
-    let mut child = cmd.spawn().map_err(|e| format!("Failed to start PlatformIO: {}", e))?;
+        .arg("-t")
+        .arg("buildfs")
+        .current_dir(&app_path);
+    let status = job.execute(&app_handle, buildfs).await?;
+    if !status.success() {
+        return Ok(job.finish(&app_handle, status));
+    }
 
-    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
-    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+    let report = filesystem_report(&app_path, &app_name, &env, &files);
+    emit_filesystem_report(&app_handle, &job, &report);
+    if let Some(message) = filesystem_image::image_overflow(&report) {
+        return Err(job.fail(&app_handle, message));
+    }
 
-    let app_handle_out = app_handle.clone();
-    let app_handle_err = app_handle.clone();
+    let mut uploadfs = Command::new(&pio_path);
+    uploadfs
+        .arg("run")
+        .arg("-e")
+        .arg(&environment)
+        .arg("-t")
+        .arg("uploadfs")
+        .current_dir(&app_path);
+    if let Some(port) = &upload_port {
+        uploadfs.env("PLATFORMIO_UPLOAD_PORT", port);
+    }
 
-    let stdout_task = tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_out, BuildEvent::Output { line });
+    let status = job.execute(&app_handle, uploadfs).await?;
+    if status.success() {
+        let uploaded = UploadedData {
+            uploaded_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            files,
+        };
+        if let Err(e) = get_filesystem_dir().and_then(|dir| {
+            filesystem_image::record_upload(&dir, &app_name, &environment, &uploaded)
+        }) {
+            warn!(env = %environment, "Failed to record filesystem upload: {}", e);
         }
-    });
+    }
+    Ok(job.finish(&app_handle, status))
+}
 
-    let stderr_task = tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_err, BuildEvent::Output { line });
-        }
-    });
+/// Runs PlatformIO tests for an environment and returns per-test results.
+#[tauri::command]
+pub async fn run_tests(
+    app_handle: AppHandle,
+    jobs: State<'_, JobManager>,
+    app_name: String,
+    environment: String,
+) -> Result<TestReport, String> {
+    validate_environment_name(&environment)?;
 
-    let timeout_duration = Duration::from_secs(PIO_COMMAND_TIMEOUT_SECS);
-    let status = wait_with_timeout(&mut child, timeout_duration).await?;
-
-    let _ = tokio::join!(stdout_task, stderr_task);
-
-    let duration_ms = start_time.elapsed().as_millis() as u64;
-    let success = match status {
-        Some(status) => status.success(),
-        None => {
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Error {
-                    message: format!(
-                        "Tests timed out after {} seconds",
-                        PIO_COMMAND_TIMEOUT_SECS
-                    ),
-                },
-            );
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Complete {
-                    success: false,
-                    duration_ms,
-                },
-            );
-            return Err(format!(
-                "Tests timed out after {} seconds",
-                PIO_COMMAND_TIMEOUT_SECS
-            ));
-        }
-    };
+    info!(app = %app_name, env = %environment, "Starting tests");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let pio_path = pio_path::resolve_pio_path(&monorepo_path)?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
 
-    emit_build_event(
+    let job = jobs.create_job(JobRequest {
+        kind: JobKind::Test,
+        app_name: &app_name,
+        environment: Some(&environment),
+        monorepo_path: &monorepo_path,
+        app_path: &app_path,
+        build_flags: &[],
+        profile: None,
+    })?;
+    let _app_lock = job.acquire_app(&app_handle).await?;
+
+    job.emit(
         &app_handle,
-        BuildEvent::Complete {
-            success,
-            duration_ms,
+        BuildEvent::Started {
+            app_name: app_name.clone(),
+            environment: environment.clone(),
         },
     );
 
-    Ok(success)
+    // Written by PlatformIO and removed once read
+    let json_path = app_path
+        .join(".pio")
+        .join(format!("test-report-{}.json", job.id));
+    if let Some(parent) = json_path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            warn!(env = %environment, "Failed to create test report directory: {}", e);
+        }
+    }
+
+    let mut cmd = Command::new(&pio_path);
+    cmd.arg("test")
+        .arg("-e")
+        .arg(&environment)
+        .arg("--json-output-path")
+        .arg(&json_path)
+        .current_dir(&app_path);
+
+    let status = job.execute(&app_handle, cmd).await;
+    let report = job.test_report(&json_path, matches!(&status, Ok(s) if s.success()));
+    let _ = std::fs::remove_file(&json_path);
+    let status = status?;
+
+    job.emit(&app_handle, BuildEvent::TestReport(report.clone()));
+    job.finish(&app_handle, status);
+    Ok(report)
+}
+
+/// Renders a test report as JUnit XML for CI tools and test viewers.
+#[tauri::command]
+pub fn export_test_report_junit(report: TestReport) -> Result<String, String> {
+    Ok(test_report::to_junit_xml(&report))
 }
 
 /// Cleans build artifacts for an app.
 #[tauri::command]
 pub async fn clean_build(
     app_handle: AppHandle,
+    jobs: State<'_, JobManager>,
     app_name: String,
     environment: Option<String>,
 ) -> Result<bool, String> {
@@ -463,7 +972,18 @@ pub async fn clean_build(
         validate_environment_name(env)?;
     }
 
-    emit_build_event(
+    let job = jobs.create_job(JobRequest {
+        kind: JobKind::Clean,
+        app_name: &app_name,
+        environment: environment.as_deref(),
+        monorepo_path: &monorepo_path,
+        app_path: &app_path,
+        build_flags: &[],
+        profile: None,
+    })?;
+    let _app_lock = job.acquire_app(&app_handle).await?;
+
+    job.emit(
         &app_handle,
         BuildEvent::Started {
             app_name: app_name.clone(),
@@ -471,98 +991,14 @@ pub async fn clean_build(
         },
     );
 
-    let start_time = std::time::Instant::now();
-
     let mut cmd = Command::new(&pio_path);
-    cmd.arg("run")
-        .arg("-t")
-        .arg("clean")
-        .current_dir(&app_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .env("PYTHONUNBUFFERED", "1");
+    cmd.arg("run").arg("-t").arg("clean").current_dir(&app_path);
 
     if let Some(env) = environment {
         cmd.arg("-e").arg(&env);
     }
 
-    let mut child = match cmd.spawn() {
-        Ok(child) => child,
-        Err(e) => {
-            let duration_ms = start_time.elapsed().as_millis() as u64;
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Complete {
-                    success: false,
-                    duration_ms,
-                },
-            );
-            return Err(format!("Failed to start PlatformIO: {}", e));
-        }
-    };
-
-    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
-    let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
-
-    let app_handle_out = app_handle.clone();
-    let app_handle_err = app_handle.clone();
-
-    let stdout_task = tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_out, BuildEvent::Output { line });
-        }
-    });
-
-    let stderr_task = tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            emit_build_event(&app_handle_err, BuildEvent::Output { line });
-        }
-    });
-
-    let timeout_duration = Duration::from_secs(PIO_COMMAND_TIMEOUT_SECS);
-    let status = wait_with_timeout(&mut child, timeout_duration).await?;
-    let _ = tokio::join!(stdout_task, stderr_task);
-
-    let duration_ms = start_time.elapsed().as_millis() as u64;
-    let success = match status {
-        Some(status) => status.success(),
-        None => {
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Error {
-                    message: format!(
-                        "Clean timed out after {} seconds",
-                        PIO_COMMAND_TIMEOUT_SECS
-                    ),
-                },
-            );
-            emit_build_event(
-                &app_handle,
-                BuildEvent::Complete {
-                    success: false,
-                    duration_ms,
-                },
-            );
-            return Err(format!(
-                "Clean timed out after {} seconds",
-                PIO_COMMAND_TIMEOUT_SECS
-            ));
-        }
-    };
-
-    emit_build_event(
-        &app_handle,
-        BuildEvent::Complete {
-            success,
-            duration_ms,
-        },
-    );
-
-    Ok(success)
+    job.run(&app_handle, cmd).await
 }
 
 /// Gets PlatformIO version information.
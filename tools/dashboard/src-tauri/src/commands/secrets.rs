@@ -0,0 +1,128 @@
+use crate::utils::{
+    monorepo, path_security,
+    secrets::{self, AppSecrets, GeneratedSecrets, SecretTemplate},
+};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Gets the encrypted secrets store path, next to the profiles directory.
+fn get_secrets_path() -> Result<PathBuf, String> {
+    let config_dir = dirs::config_dir().ok_or("Could not find config directory")?;
+    let dashboard_dir = config_dir.join("rgbw-dashboard");
+    fs::create_dir_all(&dashboard_dir)
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    Ok(dashboard_dir.join("secrets.json"))
+}
+
+/// Lists the `*.h.example` secret templates an app ships with.
+#[tauri::command]
+pub fn list_secret_templates(app_name: String) -> Result<Vec<SecretTemplate>, String> {
+    info!(app = %app_name, "Listing secret templates");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+    secrets::find_secret_templates(&app_path)
+}
+
+/// Returns whether an encrypted secrets store has been created yet.
+#[tauri::command]
+pub fn has_secrets_store() -> Result<bool, String> {
+    Ok(get_secrets_path()?.exists())
+}
+
+/// Decrypts and returns the stored secrets for an app, keyed by header.
+#[tauri::command]
+pub fn load_app_secrets(app_name: String, passphrase: String) -> Result<AppSecrets, String> {
+    info!(app = %app_name, "Loading app secrets");
+    let store = secrets::load_store(&get_secrets_path()?, &passphrase)?;
+    Ok(store.get(&app_name).cloned().unwrap_or_default())
+}
+
+/// Stores the values for one of an app's secret headers.
+#[tauri::command]
+pub fn save_app_secrets(
+    app_name: String,
+    passphrase: String,
+    header: String,
+    values: BTreeMap<String, String>,
+) -> Result<(), String> {
+    info!(app = %app_name, header = %header, "Saving app secrets");
+    let monorepo_path = monorepo::find_monorepo_root()?;
+    let app_path = path_security::validate_app_path(&monorepo_path, &app_name)?;
+
+    let template = secrets::find_secret_templates(&app_path)?
+        .into_iter()
+        .find(|template| template.header == header)
+        .ok_or_else(|| format!("No secret template for {}", header))?;
+    if let Some(unknown) = values
+        .keys()
+        .find(|name| !template.fields.iter().any(|field| &field.name == *name))
+    {
+        return Err(format!(
+            "{} is not defined in {}",
+            unknown, template.template
+        ));
+    }
+
+    let path = get_secrets_path()?;
+    let mut store = secrets::load_store(&path, &passphrase)?;
+    store.entry(app_name).or_default().insert(header, values);
+    secrets::save_store(&path, &passphrase, &store)
+}
+
+/// Deletes generated secret headers left in every app by builds that did not finish
+/// cleaning up. Runs once at startup, before any job can generate new ones.
+pub(crate) fn remove_stale_secret_headers() {
+    let apps_dir = match monorepo::find_monorepo_root() {
+        Ok(monorepo_path) => monorepo_path.join("apps"),
+        Err(_) => return,
+    };
+    let Ok(entries) = fs::read_dir(&apps_dir) else {
+        return;
+    };
+    for app_path in entries.flatten().map(|entry| entry.path()) {
+        if !app_path.is_dir() {
+            continue;
+        }
+        match secrets::remove_stale_headers(&app_path) {
+            Ok(removed) => {
+                for path in removed {
+                    warn!("Removed stale generated secrets {}", path.display());
+                }
+            }
+            Err(e) => warn!(
+                "Failed to clean up secrets in {}: {}",
+                app_path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// Generates an app's secret headers for a build. Without a passphrase nothing is
+/// generated and hand-written headers on disk are used as-is.
+///
+/// Generated headers left behind by an earlier run are deleted first, so plaintext
+/// secrets never outlive the build that needed them.
+pub(crate) fn prepare_build_secrets(
+    app_name: &str,
+    app_path: &Path,
+    passphrase: Option<&str>,
+) -> Result<Option<GeneratedSecrets>, String> {
+    for path in secrets::remove_stale_headers(app_path)? {
+        warn!(app = %app_name, "Removed stale generated secrets {}", path.display());
+    }
+    let Some(passphrase) = passphrase else {
+        return Ok(None);
+    };
+    if secrets::find_secret_templates(app_path)?.is_empty() {
+        return Ok(None);
+    }
+
+    let store = secrets::load_store(&get_secrets_path()?, passphrase)?;
+    let app_secrets = store.get(app_name).cloned().unwrap_or_default();
+    let generated = secrets::write_secret_headers(app_path, &app_secrets)?;
+    info!(app = %app_name, count = generated.paths().len(), "Generated secret headers");
+    Ok(Some(generated))
+}
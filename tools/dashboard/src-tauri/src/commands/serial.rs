@@ -1,3 +1,4 @@
+use crate::utils::preflight::UploadPortStatus;
 use serde::{Deserialize, Serialize};
 use serialport::{SerialPort, SerialPortType};
 use std::collections::HashMap;
@@ -56,8 +57,8 @@ struct SerialConnection {
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum PortLock {
-    Monitor(String),   // connection_id
-    Upload(u64),       // timestamp
+    Monitor(String), // connection_id
+    Upload(u64),     // timestamp
 }
 
 pub struct SerialState {
@@ -82,8 +83,8 @@ fn validate_port_path(port_path: &str) -> Result<(), String> {
         return Err("Port path contains invalid characters".to_string());
     }
 
-    let ports = serialport::available_ports()
-        .map_err(|e| format!("Failed to list ports: {}", e))?;
+    let ports =
+        serialport::available_ports().map_err(|e| format!("Failed to list ports: {}", e))?;
     if !ports.iter().any(|p| p.port_name == port_path) {
         return Err(format!(
             "Invalid serial port path: {}. Please refresh ports and select a valid device.",
@@ -104,7 +105,10 @@ fn validate_baud_rate(baud_rate: u32) -> Result<(), String> {
     Ok(())
 }
 
-fn lock_recover<'a, T>(lock: &'a Mutex<T>, label: &str) -> Result<std::sync::MutexGuard<'a, T>, String> {
+pub(crate) fn lock_recover<'a, T>(
+    lock: &'a Mutex<T>,
+    label: &str,
+) -> Result<std::sync::MutexGuard<'a, T>, String> {
     match lock.lock() {
         Ok(guard) => Ok(guard),
         Err(poisoned) => {
@@ -123,7 +127,8 @@ fn emit_serial_event(app_handle: &AppHandle, event: SerialEvent) {
 /// Lists available serial ports.
 #[tauri::command]
 pub fn list_serial_ports() -> Result<Vec<PortInfo>, String> {
-    let ports = serialport::available_ports().map_err(|e| format!("Failed to list ports: {}", e))?;
+    let ports =
+        serialport::available_ports().map_err(|e| format!("Failed to list ports: {}", e))?;
 
     let port_infos: Vec<PortInfo> = ports
         .into_iter()
@@ -296,8 +301,8 @@ fn serial_reader_thread(
                 let text = String::from_utf8_lossy(&buf[..n]);
                 pending.push_str(&text);
 
-                let should_emit = pending.len() > SERIAL_BUFFER_MAX_BYTES
-                    || last_emit.elapsed() >= emit_interval;
+                let should_emit =
+                    pending.len() > SERIAL_BUFFER_MAX_BYTES || last_emit.elapsed() >= emit_interval;
                 if should_emit {
                     if alive.load(Ordering::Relaxed) {
                         emit_serial_event(
@@ -405,14 +410,18 @@ pub fn acquire_port_for_upload(
     state: State<'_, SerialState>,
     port_path: String,
 ) -> Result<(), String> {
-    validate_port_path(&port_path)?;
+    lock_port_for_upload(&state, &port_path)
+}
+
+pub(crate) fn lock_port_for_upload(state: &SerialState, port_path: &str) -> Result<(), String> {
+    validate_port_path(port_path)?;
 
     let (_conn_id, conn) = {
         // Hold both locks while checking and inserting to avoid TOCTOU races.
         let mut connections = lock_recover(&state.connections, "connections")?;
         let mut locks = lock_recover(&state.port_locks, "port_locks")?;
 
-        let conn_id = match locks.get(&port_path) {
+        let conn_id = match locks.get(port_path) {
             Some(PortLock::Monitor(conn_id)) => Some(conn_id.clone()),
             _ => None,
         };
@@ -421,7 +430,7 @@ pub fn acquire_port_for_upload(
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_secs())
             .unwrap_or(0);
-        locks.insert(port_path.clone(), PortLock::Upload(now));
+        locks.insert(port_path.to_string(), PortLock::Upload(now));
 
         let conn = match conn_id.as_ref() {
             Some(id) => {
@@ -452,15 +461,68 @@ pub fn acquire_port_for_upload(
 /// Releases a port lock after upload.
 #[tauri::command]
 pub fn release_upload_lock(state: State<'_, SerialState>, port_path: String) -> Result<(), String> {
+    unlock_upload_port(&state, &port_path)
+}
+
+fn unlock_upload_port(state: &SerialState, port_path: &str) -> Result<(), String> {
     let mut locks = lock_recover(&state.port_locks, "port_locks")?;
 
-    if matches!(locks.get(&port_path), Some(PortLock::Upload(_))) {
-        locks.remove(&port_path);
+    if matches!(locks.get(port_path), Some(PortLock::Upload(_))) {
+        locks.remove(port_path);
     }
 
     Ok(())
 }
 
+/// Holds a port's upload lock for a job and releases it when dropped.
+pub(crate) struct UploadPortGuard<'a> {
+    state: &'a SerialState,
+    port_path: String,
+}
+
+impl<'a> UploadPortGuard<'a> {
+    pub(crate) fn acquire(state: &'a SerialState, port_path: &str) -> Result<Self, String> {
+        lock_port_for_upload(state, port_path)?;
+        Ok(Self {
+            state,
+            port_path: port_path.to_string(),
+        })
+    }
+}
+
+impl Drop for UploadPortGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = unlock_upload_port(self.state, &self.port_path) {
+            warn!(port = %self.port_path, "Failed to release upload lock: {}", e);
+        }
+    }
+}
+
+/// Describes an upload port for the pre-flight checks: its USB ids and whether a
+/// monitor connection still holds it.
+pub(crate) fn upload_port_status(
+    state: &SerialState,
+    port_path: &str,
+) -> Result<UploadPortStatus, String> {
+    let port = list_serial_ports()?
+        .into_iter()
+        .find(|p| p.path == port_path);
+
+    let connections = lock_recover(&state.connections, "connections")?;
+    let locks = lock_recover(&state.port_locks, "port_locks")?;
+    let monitor_open = matches!(
+        locks.get(port_path),
+        Some(PortLock::Monitor(conn_id)) if connections.contains_key(conn_id)
+    );
+
+    Ok(UploadPortStatus {
+        path: port_path.to_string(),
+        vid: port.as_ref().and_then(|p| p.vid),
+        pid: port.as_ref().and_then(|p| p.pid),
+        monitor_open,
+    })
+}
+
 /// Gets the current lock status for a port.
 #[tauri::command]
 pub fn get_port_lock_status(
@@ -1,6 +1,7 @@
 mod commands;
 mod utils;
 
+use commands::jobs::JobManager;
 use commands::serial::SerialState;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -12,8 +13,12 @@ pub fn run() {
         .with_target(false)
         .try_init();
 
+    // Plaintext headers from a build the dashboard never finished cleaning up
+    commands::secrets::remove_stale_secret_headers();
+
     tauri::Builder::default()
         .manage(SerialState::default())
+        .manage(JobManager::default())
         .invoke_handler(tauri::generate_handler![
             // App commands
             commands::apps::discover_apps,
@@ -22,16 +27,44 @@ pub fn run() {
             // Config commands
             commands::config::validate_pin,
             commands::config::get_safe_pins,
+            commands::config::validate_pin_map,
+            commands::config::list_pin_boards,
             commands::config::save_profile,
             commands::config::load_profile,
             commands::config::list_profiles,
             commands::config::delete_profile,
+            commands::config::compare_profile_defines,
+            commands::config::validate_define_values,
+            commands::config::plan_peripheral_resources,
+            commands::config::calculate_power_budget,
+            commands::config::resolve_config_values,
+            commands::config::create_environment_from_profile,
+            commands::config::remove_environment,
+            commands::config::update_environment_list,
             // PIO commands
+            commands::pio::run_preflight_checks,
             commands::pio::run_build,
             commands::pio::run_upload,
+            commands::pio::run_buildfs,
+            commands::pio::run_uploadfs,
+            commands::pio::get_filesystem_status,
             commands::pio::run_tests,
+            commands::pio::export_test_report_junit,
             commands::pio::clean_build,
             commands::pio::get_pio_version,
+            // Job commands
+            commands::jobs::list_jobs,
+            commands::jobs::cancel_job,
+            // History commands
+            commands::history::list_build_history,
+            commands::history::get_build_run,
+            commands::history::diff_build_runs,
+            commands::history::get_firmware_size_history,
+            // Secrets commands
+            commands::secrets::list_secret_templates,
+            commands::secrets::has_secrets_store,
+            commands::secrets::load_app_secrets,
+            commands::secrets::save_app_secrets,
             // Serial commands
             commands::serial::list_serial_ports,
             commands::serial::open_serial,
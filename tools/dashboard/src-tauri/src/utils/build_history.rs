@@ -0,0 +1,386 @@
+use crate::utils::pio_parser;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Number of runs kept before the oldest are deleted.
+pub const MAX_HISTORY_RUNS: usize = 200;
+/// Logs stop growing past this size so a runaway build can't fill the disk.
+const MAX_LOG_BYTES: u64 = 8 * 1024 * 1024;
+
+/// One finished job, stored as `<id>.json` next to its `<id>.log`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BuildRecord {
+    pub id: String,
+    /// `build`, `upload`, `test`, `clean`, `buildfs` or `uploadfs`.
+    pub kind: String,
+    pub app_name: String,
+    pub environment: Option<String>,
+    pub build_flags: Vec<String>,
+    /// Saved profile the build flags came from, if any.
+    pub profile: Option<String>,
+    pub git_commit: Option<String>,
+    /// The working tree had uncommitted changes to tracked files.
+    pub git_dirty: bool,
+    /// Unix timestamp in seconds.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+    /// `None` when the process never exited on its own (not started, cancelled, timed out).
+    pub exit_code: Option<i32>,
+    /// Why the job failed without an exit code.
+    pub error: Option<String>,
+}
+
+/// A past run reopened with its log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildRun {
+    pub record: BuildRecord,
+    pub log: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+    pub app_name: Option<String>,
+    pub environment: Option<String>,
+    pub kind: Option<String>,
+    pub success: Option<bool>,
+    pub profile: Option<String>,
+    /// Only runs started at or after this Unix timestamp.
+    pub since: Option<u64>,
+    /// Only runs started before this Unix timestamp.
+    pub until: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, record: &BuildRecord) -> bool {
+        self.app_name
+            .as_ref()
+            .is_none_or(|app| *app == record.app_name)
+            && self
+                .environment
+                .as_ref()
+                .is_none_or(|env| record.environment.as_ref() == Some(env))
+            && self.kind.as_ref().is_none_or(|kind| *kind == record.kind)
+            && self.success.is_none_or(|success| success == record.success)
+            && self
+                .profile
+                .as_ref()
+                .is_none_or(|profile| record.profile.as_ref() == Some(profile))
+            && self.since.is_none_or(|since| record.started_at >= since)
+            && self.until.is_none_or(|until| record.started_at < until)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A define that differs between two runs' build flags. Bare `-DNAME` compares as `1`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlagChange {
+    pub name: String,
+    pub kind: FlagChangeKind,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunDiff {
+    pub base: BuildRecord,
+    pub other: BuildRecord,
+    pub flags: Vec<FlagChange>,
+}
+
+/// Writes a job's output to `<id>.log` as it streams.
+pub struct HistoryLog {
+    writer: BufWriter<File>,
+    written: u64,
+    truncated: bool,
+}
+
+impl HistoryLog {
+    pub fn create(history_dir: &Path, id: &str) -> Result<Self, String> {
+        let file = File::create(log_path(history_dir, id)?)
+            .map_err(|e| format!("Failed to create build log: {}", e))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            written: 0,
+            truncated: false,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) {
+        if self.truncated {
+            return;
+        }
+        if self.written + line.len() as u64 + 1 > MAX_LOG_BYTES {
+            self.truncated = true;
+            let _ = writeln!(self.writer, "[log truncated at {} bytes]", self.written);
+            return;
+        }
+        if writeln!(self.writer, "{}", line).is_ok() {
+            self.written += line.len() as u64 + 1;
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// Run ids are job UUIDs; anything else could escape the history directory.
+fn validate_run_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > 64 || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err(format!("Invalid run id: {}", id));
+    }
+    Ok(())
+}
+
+fn record_path(history_dir: &Path, id: &str) -> Result<PathBuf, String> {
+    validate_run_id(id)?;
+    Ok(history_dir.join(format!("{}.json", id)))
+}
+
+fn log_path(history_dir: &Path, id: &str) -> Result<PathBuf, String> {
+    validate_run_id(id)?;
+    Ok(history_dir.join(format!("{}.log", id)))
+}
+
+/// Saves a finished run, then deletes the oldest runs beyond `keep`.
+pub fn save_record(history_dir: &Path, record: &BuildRecord, keep: usize) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(record)
+        .map_err(|e| format!("Failed to serialize build record: {}", e))?;
+    fs::write(record_path(history_dir, &record.id)?, content)
+        .map_err(|e| format!("Failed to write build record: {}", e))?;
+    rotate(history_dir, keep)
+}
+
+fn read_records(history_dir: &Path) -> Result<Vec<BuildRecord>, String> {
+    let entries = match fs::read_dir(history_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read build history: {}", e)),
+    };
+
+    let mut records: Vec<BuildRecord> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    // Newest first
+    records.sort_by(|a, b| {
+        b.started_at
+            .cmp(&a.started_at)
+            .then_with(|| b.id.cmp(&a.id))
+    });
+    Ok(records)
+}
+
+fn rotate(history_dir: &Path, keep: usize) -> Result<(), String> {
+    let records = read_records(history_dir)?;
+    for record in records.iter().skip(keep) {
+        let _ = fs::remove_file(record_path(history_dir, &record.id)?);
+        let _ = fs::remove_file(log_path(history_dir, &record.id)?);
+    }
+
+    // Logs whose job never finished (e.g. the app was closed mid-build)
+    let kept: BTreeSet<&str> = records.iter().take(keep).map(|r| r.id.as_str()).collect();
+    if let Ok(entries) = fs::read_dir(history_dir) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let orphan = path.extension().is_some_and(|ext| ext == "log")
+                && path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| !kept.contains(stem));
+            let stale = fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age.as_secs() > 24 * 60 * 60);
+            if orphan && stale {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lists past runs matching `filter`, newest first.
+pub fn list_records(
+    history_dir: &Path,
+    filter: &HistoryFilter,
+) -> Result<Vec<BuildRecord>, String> {
+    let records = read_records(history_dir)?
+        .into_iter()
+        .filter(|record| filter.matches(record));
+    Ok(match filter.limit {
+        Some(limit) => records.take(limit).collect(),
+        None => records.collect(),
+    })
+}
+
+/// Reopens a past run with its log.
+pub fn load_run(history_dir: &Path, id: &str) -> Result<BuildRun, String> {
+    let content = fs::read_to_string(record_path(history_dir, id)?)
+        .map_err(|_| format!("Build run not found: {}", id))?;
+    let record: BuildRecord = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse build record: {}", e))?;
+    let log = fs::read_to_string(log_path(history_dir, id)?).unwrap_or_default();
+    Ok(BuildRun { record, log })
+}
+
+/// Compares the defines set by two runs' build flags, sorted by name.
+pub fn diff_flags(base: &[String], other: &[String]) -> Vec<FlagChange> {
+    let before = pio_parser::flag_defines(base);
+    let after = pio_parser::flag_defines(other);
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (before, after) = (before.get(name).cloned(), after.get(name).cloned());
+            let kind = match (&before, &after) {
+                (None, Some(_)) => FlagChangeKind::Added,
+                (Some(_), None) => FlagChangeKind::Removed,
+                (Some(a), Some(b)) if a != b => FlagChangeKind::Changed,
+                _ => return None,
+            };
+            Some(FlagChange {
+                name: name.clone(),
+                kind,
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+/// Current commit of the repository containing `path`, and whether tracked files have
+/// uncommitted changes. `None` outside a git checkout or without git installed.
+pub fn git_revision(path: &Path) -> Option<(String, bool)> {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .current_dir(path)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    let commit = git(&["rev-parse", "HEAD"])?;
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+        .is_some_and(|status| !status.is_empty());
+    Some((commit, dirty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record(id: &str, app: &str, started_at: u64, success: bool) -> BuildRecord {
+        BuildRecord {
+            id: id.to_string(),
+            kind: "build".to_string(),
+            app_name: app.to_string(),
+            environment: Some("esp32dev".to_string()),
+            build_flags: vec!["-DNUM_ROWS=8".to_string()],
+            profile: None,
+            git_commit: None,
+            git_dirty: false,
+            started_at,
+            duration_ms: 1000,
+            success,
+            exit_code: Some(if success { 0 } else { 1 }),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_save_list_and_rotate() {
+        let temp = tempdir().unwrap();
+        let dir = temp.path();
+        for (i, app) in ["dj-booth", "led-panel", "dj-booth", "dj-booth"]
+            .iter()
+            .enumerate()
+        {
+            let id = format!("0000000{}", i);
+            let mut log = HistoryLog::create(dir, &id).unwrap();
+            log.write_line("Compiling main.cpp");
+            log.flush();
+            save_record(dir, &record(&id, app, 100 + i as u64, i != 2), 3).unwrap();
+        }
+
+        // The oldest run and its log were rotated out
+        assert!(!dir.join("00000000.json").exists());
+        assert!(!dir.join("00000000.log").exists());
+
+        let all = list_records(dir, &HistoryFilter::default()).unwrap();
+        let ids: Vec<&str> = all.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["00000003", "00000002", "00000001"]);
+
+        let failed_booth = HistoryFilter {
+            app_name: Some("dj-booth".to_string()),
+            success: Some(false),
+            ..Default::default()
+        };
+        let failed = list_records(dir, &failed_booth).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, "00000002");
+
+        let since = HistoryFilter {
+            since: Some(103),
+            ..Default::default()
+        };
+        assert_eq!(list_records(dir, &since).unwrap().len(), 1);
+
+        let run = load_run(dir, "00000003").unwrap();
+        assert_eq!(run.log, "Compiling main.cpp\n");
+        assert!(load_run(dir, "../secrets").is_err());
+        assert!(load_run(dir, "00000000").is_err());
+    }
+
+    #[test]
+    fn test_diff_flags() {
+        let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let changes = diff_flags(
+            &flags(&["-DNUM_ROWS=8", "-DUSE_AUDIO", "-DSTRIP_TYPE=0"]),
+            &flags(&["-DNUM_ROWS=12", "-DUSE_AUDIO=1", "-DWIFI_ENABLED=1"]),
+        );
+        assert_eq!(
+            changes,
+            vec![
+                FlagChange {
+                    name: "NUM_ROWS".to_string(),
+                    kind: FlagChangeKind::Changed,
+                    before: Some("8".to_string()),
+                    after: Some("12".to_string()),
+                },
+                FlagChange {
+                    name: "STRIP_TYPE".to_string(),
+                    kind: FlagChangeKind::Removed,
+                    before: Some("0".to_string()),
+                    after: None,
+                },
+                FlagChange {
+                    name: "WIFI_ENABLED".to_string(),
+                    kind: FlagChangeKind::Added,
+                    before: None,
+                    after: Some("1".to_string()),
+                },
+            ]
+        );
+    }
+}
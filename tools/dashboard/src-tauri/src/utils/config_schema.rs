@@ -1,15 +1,22 @@
+use crate::utils::dashboard_manifest::{self, DashboardManifest, DisplayGroup};
+use crate::utils::monorepo;
+use crate::utils::pio_parser::{self, DiscoveredEnvironment};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppConfigSchema {
     pub has_config: bool,
     pub defines: Vec<ConfigDefine>,
     pub platform_conditional: HashMap<String, Vec<ConfigDefine>>,
+    /// Headers that were scanned, relative to the app directory, in include order.
+    pub headers: Vec<String>,
+    /// Display groups from the app's dashboard manifest.
+    pub groups: Vec<DisplayGroup>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +27,36 @@ pub struct ConfigDefine {
     pub enum_values: Option<Vec<EnumValue>>,
     pub platform: Option<String>,
     pub description: Option<String>,
+    /// Title of the enclosing `// ---- Section ----` heading, if any.
+    pub section: Option<String>,
+    /// Limits derived from `#if ... #error` guards.
+    pub constraints: Vec<ValueConstraint>,
+    /// platformio.ini environments whose platform conditionals select this define.
+    pub environments: Vec<String>,
+    /// Header the define was found in, relative to the app directory.
+    pub header: String,
+    /// Display group from the dashboard manifest.
+    pub group: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+    pub unit: Option<String>,
+}
+
+/// Integer range a define must stay within, taken from an `#if ... #error` guard.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ValueConstraint {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub message: String,
+}
+
+/// A proposed define value that would trip an `#error` guard.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConstraintViolation {
+    pub name: String,
+    pub value: String,
+    pub message: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,45 +84,323 @@ pub enum ValueType {
 ///
 /// And optional enum comments like:
 /// - `// 0=SK6812_RGBW, 1=WS2815B_RGB`
+/// - one `//   0 = Label - details` line per value in the comment block above the define
+///
+/// The comment block directly above the `#ifndef` becomes the define's description and
+/// the enclosing `// ----` heading becomes its section.
+///
+/// Platform conditionals such as `#if defined(ESP32) || defined(NATIVE_BUILD)` are
+/// evaluated against each of the app's platformio.ini environments to fill in
+/// `ConfigDefine::environments`.
+///
+/// Quoted `#include`s of project-local headers are followed, and an optional
+/// `dashboard.toml`/`dashboard.json` manifest can add headers, hide defines, group them
+/// and override their ranges.
 pub fn parse_config_schema(app_path: &Path) -> Result<AppConfigSchema, String> {
-    let config_path = app_path.join("src/config.h");
+    let manifest = dashboard_manifest::load_manifest(app_path)?.unwrap_or_default();
+    let sources = collect_header_sources(app_path, &manifest)?;
 
-    if !config_path.exists() {
-        return Ok(AppConfigSchema {
-            has_config: false,
-            defines: Vec::new(),
-            platform_conditional: HashMap::new(),
-        });
+    if sources.is_empty() {
+        return Ok(AppConfigSchema::default());
     }
 
-    let content =
-        fs::read_to_string(&config_path).map_err(|e| format!("Failed to read config.h: {}", e))?;
+    let content = combined_content(&sources);
+
+    let targets = pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))
+        .map(|config| {
+            config
+                .environments
+                .iter()
+                .map(|env| EnvironmentTarget {
+                    name: env.name.clone(),
+                    macros: environment_macros(env),
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let (mut defines, mut platform_conditional) = extract_config_defines(&content, &targets)?;
 
-    let (defines, platform_conditional) = extract_config_defines(&content)?;
+    defines.retain(|define| !manifest.is_hidden(&define.name));
+    for bucket in platform_conditional.values_mut() {
+        bucket.retain(|define| !manifest.is_hidden(&define.name));
+    }
+    for define in defines
+        .iter_mut()
+        .chain(platform_conditional.values_mut().flatten())
+    {
+        define.header = header_for(&sources, &define.name);
+        apply_manifest(define, &manifest);
+    }
 
     Ok(AppConfigSchema {
         has_config: true,
         defines,
         platform_conditional,
+        headers: sources.into_iter().map(|source| source.label).collect(),
+        groups: manifest.groups,
     })
 }
 
+/// One header read for the schema.
+#[derive(Debug, Clone)]
+struct HeaderSource {
+    label: String,
+    content: String,
+}
+
+/// Reads `src/config.h` and the manifest's extra headers, following quoted includes.
+///
+/// Includes must resolve inside the app directory. Manifest headers may also point into
+/// the rest of the monorepo that contains the app (e.g. a shared library).
+fn collect_header_sources(
+    app_path: &Path,
+    manifest: &DashboardManifest,
+) -> Result<Vec<HeaderSource>, String> {
+    let app_root = app_path
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve app path: {}", e))?;
+
+    let mut sources = Vec::new();
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+
+    let config_path = app_root.join("src/config.h");
+    if config_path.exists() {
+        read_header(&config_path, &app_root, &mut visited, &mut sources)?;
+    }
+
+    if manifest.headers.is_empty() {
+        return Ok(sources);
+    }
+    let monorepo_root = monorepo::find_monorepo_root_from(&app_root)?;
+    for header in &manifest.headers {
+        let path = app_root
+            .join(header)
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve header {}: {}", header, e))?;
+        if !path.starts_with(&monorepo_root) {
+            return Err(format!("Header {} escapes the monorepo", header));
+        }
+        read_header(&path, &app_root, &mut visited, &mut sources)?;
+    }
+
+    Ok(sources)
+}
+
+/// Reads one header, then the local headers it includes, depth-first in include order.
+fn read_header(
+    path: &Path,
+    app_root: &Path,
+    visited: &mut HashSet<PathBuf>,
+    sources: &mut Vec<HeaderSource>,
+) -> Result<(), String> {
+    static INCLUDE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"^#\s*include\s+"([^"]+)""#).expect("include regex"));
+
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+    if !visited.insert(canonical.clone()) {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&canonical)
+        .map_err(|e| format!("Failed to read {}: {}", canonical.display(), e))?;
+    let label = canonical
+        .strip_prefix(app_root)
+        .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|_| canonical.to_string_lossy().to_string());
+
+    let includes: Vec<String> = content
+        .lines()
+        .filter_map(|line| INCLUDE_RE.captures(line.trim()))
+        .map(|caps| caps[1].to_string())
+        .collect();
+    sources.push(HeaderSource { label, content });
+
+    let header_dir = canonical.parent().unwrap_or(app_root).to_path_buf();
+    for include in includes {
+        let resolved = [
+            header_dir.join(&include),
+            app_root.join("include").join(&include),
+            app_root.join("src").join(&include),
+        ]
+        .into_iter()
+        .filter_map(|candidate| candidate.canonicalize().ok())
+        .find(|candidate| candidate.starts_with(app_root) && candidate.is_file());
+
+        // Toolchain and library headers are not part of the app's configuration
+        if let Some(resolved) = resolved {
+            read_header(&resolved, app_root, visited, sources)?;
+        }
+    }
+    Ok(())
+}
+
+/// Joins headers into one translation unit for extraction. Blank lines between files keep
+/// one header's trailing comments from documenting the next header's first define.
+fn combined_content(sources: &[HeaderSource]) -> String {
+    sources
+        .iter()
+        .map(|source| source.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// First header that defines the name.
+fn header_for(sources: &[HeaderSource], name: &str) -> String {
+    sources
+        .iter()
+        .find(|source| {
+            source.content.lines().any(|line| {
+                line.trim()
+                    .strip_prefix("#define")
+                    .is_some_and(|rest| first_word(rest) == name)
+            })
+        })
+        .map(|source| source.label.clone())
+        .unwrap_or_default()
+}
+
+fn apply_manifest(define: &mut ConfigDefine, manifest: &DashboardManifest) {
+    define.group = manifest.group_for(&define.name).map(|g| g.to_string());
+    if let Some(field) = manifest.fields.get(&define.name) {
+        define.min = field.min;
+        define.max = field.max;
+        define.step = field.step;
+        define.unit = field.unit.clone();
+        if field.description.is_some() {
+            define.description = field.description.clone();
+        }
+    }
+}
+
+/// Platform buckets used for `AppConfigSchema::platform_conditional`, with the macros the
+/// toolchain predefines for each. Every ESP32 variant also defines `ESP32`.
+const PLATFORM_TARGETS: &[(&str, &[&str])] = &[
+    (
+        "ESP32",
+        &["ESP32", "ARDUINO_ARCH_ESP32", "CONFIG_IDF_TARGET_ESP32"],
+    ),
+    (
+        "ESP32S2",
+        &["ESP32", "ARDUINO_ARCH_ESP32", "CONFIG_IDF_TARGET_ESP32S2"],
+    ),
+    (
+        "ESP32S3",
+        &["ESP32", "ARDUINO_ARCH_ESP32", "CONFIG_IDF_TARGET_ESP32S3"],
+    ),
+    (
+        "ESP32C3",
+        &["ESP32", "ARDUINO_ARCH_ESP32", "CONFIG_IDF_TARGET_ESP32C3"],
+    ),
+    (
+        "ESP32C6",
+        &["ESP32", "ARDUINO_ARCH_ESP32", "CONFIG_IDF_TARGET_ESP32C6"],
+    ),
+    ("ESP8266", &["ESP8266", "ARDUINO_ARCH_ESP8266"]),
+    ("__AVR__", &["__AVR__", "ARDUINO_ARCH_AVR"]),
+    ("NATIVE_BUILD", &["NATIVE_BUILD"]),
+];
+
+/// Macros a platformio.ini environment defines: the platform's own plus its `-D` flags.
+pub fn environment_macros(env: &DiscoveredEnvironment) -> HashMap<String, String> {
+    let platform = env.platform.to_lowercase();
+    let board = env.board.as_deref().unwrap_or("").to_lowercase();
+
+    let target = if platform.contains("espressif32") {
+        ["s2", "s3", "c3", "c6"]
+            .iter()
+            .find(|variant| board.contains(*variant))
+            .map(|variant| format!("ESP32{}", variant.to_uppercase()))
+            .or_else(|| Some("ESP32".to_string()))
+    } else if platform.contains("espressif8266") {
+        Some("ESP8266".to_string())
+    } else if platform.contains("atmelavr") {
+        Some("__AVR__".to_string())
+    } else {
+        None
+    };
+
+    let mut macros: HashMap<String, String> = target
+        .and_then(|target| PLATFORM_TARGETS.iter().find(|(name, _)| *name == target))
+        .map(|(_, names)| {
+            names
+                .iter()
+                .map(|m| (m.to_string(), "1".to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    for define in &env.defines {
+        let value = define.value.clone().unwrap_or_else(|| "1".to_string());
+        macros.insert(define.name.clone(), value);
+    }
+    macros
+}
+
+/// An environment whose macros decide which conditional branches apply to it.
+#[derive(Debug, Clone)]
+struct EnvironmentTarget {
+    name: String,
+    macros: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone)]
 struct ConditionalFrame {
-    platform: Option<String>,
+    /// Condition of the branch currently being read; `1` for `#else`.
+    condition: String,
+    /// Conditions of earlier `#if`/`#elif` branches, all of which must be false.
+    previous: Vec<String>,
+    /// Whether any branch tests a platform macro.
     platform_conditional: bool,
     ifndef_name: Option<String>,
+    doc: Vec<String>,
+}
+
+impl ConditionalFrame {
+    fn new(condition: String, ifndef_name: Option<String>, doc: Vec<String>) -> Self {
+        Self {
+            platform_conditional: mentions_platform(&condition),
+            condition,
+            previous: Vec::new(),
+            ifndef_name,
+            doc,
+        }
+    }
+
+    fn is_selected(&self, macros: &HashMap<String, String>) -> bool {
+        !self
+            .previous
+            .iter()
+            .any(|expr| eval_condition(expr, macros))
+            && eval_condition(&self.condition, macros)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum PlatformContext {
-    None,
-    Specific,
-    Other,
+/// True when every platform conditional on the stack selects its current branch.
+fn platform_branches_selected(
+    stack: &[ConditionalFrame],
+    macros: &HashMap<String, String>,
+) -> bool {
+    stack
+        .iter()
+        .filter(|frame| frame.platform_conditional)
+        .all(|frame| frame.is_selected(macros))
+}
+
+fn mentions_platform(expr: &str) -> bool {
+    expr.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .any(|token| {
+            token.starts_with("ARDUINO_ARCH_")
+                || token.starts_with("CONFIG_IDF_TARGET_")
+                || PLATFORM_TARGETS
+                    .iter()
+                    .any(|(_, macros)| macros.contains(&token))
+        })
 }
 
 fn extract_config_defines(
     content: &str,
+    targets: &[EnvironmentTarget],
 ) -> Result<(Vec<ConfigDefine>, HashMap<String, Vec<ConfigDefine>>), String> {
     let mut defines: Vec<ConfigDefine> = Vec::new();
     let mut defines_seen: HashSet<String> = HashSet::new();
@@ -93,49 +408,60 @@ fn extract_config_defines(
     let mut platform_seen: HashMap<String, HashSet<String>> = HashMap::new();
     let mut stack: Vec<ConditionalFrame> = Vec::new();
     // Track nested preprocessor conditionals to map defines to platform-specific buckets.
+    let mut comments = CommentTracker::default();
+    let constants = NamedConstants::collect(content);
 
     static DEFINE_RE: Lazy<Regex> =
         Lazy::new(|| Regex::new(r"^#define\s+(\w+)(?:\s+(.*))?$").expect("define regex"));
     static IFNDEF_EXPR_RE: Lazy<Regex> = Lazy::new(|| {
-        Regex::new(r"!\s*defined\s*\(?\s*([A-Za-z_][A-Za-z0-9_]*)\s*\)?")
-            .expect("ifndef regex")
+        Regex::new(r"!\s*defined\s*\(?\s*([A-Za-z_][A-Za-z0-9_]*)\s*\)?").expect("ifndef regex")
     });
 
     for raw_line in content.lines() {
         let trimmed = raw_line.trim();
         if trimmed.is_empty() {
+            comments.end_block();
+            continue;
+        }
+
+        if let Some(text) = trimmed.strip_prefix("//") {
+            comments.push(text);
             continue;
         }
 
         let directive_line = strip_inline_comment(trimmed);
+        let doc = std::mem::take(&mut comments.pending);
+        comments.end_block();
 
         if let Some(rest) = directive_line.strip_prefix("#ifdef") {
             let name = rest.trim().split_whitespace().next().unwrap_or("");
-            let platform = extract_platform_from_tokens(name);
-            stack.push(ConditionalFrame {
-                platform: platform.clone(),
-                platform_conditional: platform.is_some(),
-                ifndef_name: None,
-            });
+            stack.push(ConditionalFrame::new(
+                format!("defined({})", name),
+                None,
+                Vec::new(),
+            ));
             continue;
         }
 
         if let Some(rest) = directive_line.strip_prefix("#ifndef") {
-            let name = rest.trim().split_whitespace().next().unwrap_or("").to_string();
-            stack.push(ConditionalFrame {
-                platform: None,
-                platform_conditional: false,
-                ifndef_name: if name.is_empty() { None } else { Some(name) },
-            });
+            let name = rest
+                .trim()
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .to_string();
+            let condition = format!("!defined({})", name);
+            let ifndef_name = if name.is_empty() { None } else { Some(name) };
+            stack.push(ConditionalFrame::new(condition, ifndef_name, doc));
             continue;
         }
 
         if let Some(rest) = directive_line.strip_prefix("#elif") {
             if let Some(frame) = stack.last_mut() {
                 let expr = rest.trim();
-                let (platform, platform_conditional) = extract_platform_from_expr(expr);
-                frame.platform = platform;
-                frame.platform_conditional = platform_conditional;
+                let previous = std::mem::replace(&mut frame.condition, expr.to_string());
+                frame.previous.push(previous);
+                frame.platform_conditional |= mentions_platform(expr);
                 frame.ifndef_name = extract_ifndef_from_expr(expr, &IFNDEF_EXPR_RE);
             }
             continue;
@@ -143,7 +469,8 @@ fn extract_config_defines(
 
         if directive_line.starts_with("#else") {
             if let Some(frame) = stack.last_mut() {
-                frame.platform = None;
+                let previous = std::mem::replace(&mut frame.condition, "1".to_string());
+                frame.previous.push(previous);
                 frame.ifndef_name = None;
             }
             continue;
@@ -156,13 +483,8 @@ fn extract_config_defines(
 
         if let Some(rest) = directive_line.strip_prefix("#if") {
             let expr = rest.trim();
-            let (platform, platform_conditional) = extract_platform_from_expr(expr);
             let ifndef_name = extract_ifndef_from_expr(expr, &IFNDEF_EXPR_RE);
-            stack.push(ConditionalFrame {
-                platform,
-                platform_conditional,
-                ifndef_name,
-            });
+            stack.push(ConditionalFrame::new(expr.to_string(), ifndef_name, doc));
             continue;
         }
 
@@ -177,220 +499,1117 @@ fn extract_config_defines(
             let raw_value = parts.next().unwrap_or("").trim().to_string();
             let comment = parts.next().map(|s| s.trim().to_string());
 
-            let (value_type, enum_values, default_value) = parse_value(&name, &raw_value, &comment);
-
-            match current_platform_context(&stack) {
-                PlatformContext::None => {
-                    if defines_seen.insert(name.clone()) {
-                        defines.push(ConfigDefine {
-                            name,
-                            default_value,
-                            value_type,
-                            enum_values,
-                            platform: None,
-                            description: comment,
-                        });
-                    }
+            let doc = stack
+                .iter()
+                .rev()
+                .find(|frame| frame.ifndef_name.as_deref() == Some(name.as_str()))
+                .map(|frame| frame.doc.as_slice())
+                .filter(|doc| !doc.is_empty())
+                .unwrap_or(&doc);
+            let resolved_value = constants.resolve(&raw_value).unwrap_or(raw_value);
+            let (value_type, enum_values, default_value) =
+                match constants.family_for(&name, value_with_comment) {
+                    Some(family) => (ValueType::Enum, Some(family), resolved_value),
+                    None => parse_value(&name, &resolved_value, &comment, doc),
+                };
+            let description = describe(doc, comment);
+            let section = comments.section.clone();
+
+            let define = ConfigDefine {
+                name: name.clone(),
+                default_value,
+                value_type,
+                enum_values,
+                platform: None,
+                description,
+                section,
+                constraints: Vec::new(),
+                environments: targets
+                    .iter()
+                    .filter(|target| platform_branches_selected(&stack, &target.macros))
+                    .map(|target| target.name.clone())
+                    .collect(),
+                header: String::new(),
+                group: None,
+                min: None,
+                max: None,
+                step: None,
+                unit: None,
+            };
+
+            if !stack.iter().any(|frame| frame.platform_conditional) {
+                if defines_seen.insert(name) {
+                    defines.push(define);
                 }
-                PlatformContext::Specific => {
-                    if let Some(platform) = current_platform_name(&stack) {
-                        let seen = platform_seen.entry(platform.clone()).or_default();
-                        if seen.insert(name.clone()) {
-                            platform_conditional
-                                .entry(platform.clone())
-                                .or_default()
-                                .push(ConfigDefine {
-                                    name,
-                                    default_value,
-                                    value_type,
-                                    enum_values,
-                                    platform: Some(platform),
-                                    description: comment,
-                                });
-                        }
-                    }
+                continue;
+            }
+
+            // Platform branches fan out to every bucket whose predefined macros select them
+            for (platform, platform_macros) in PLATFORM_TARGETS {
+                let macros: HashMap<String, String> = platform_macros
+                    .iter()
+                    .map(|m| (m.to_string(), "1".to_string()))
+                    .collect();
+                if !platform_branches_selected(&stack, &macros) {
+                    continue;
                 }
-                PlatformContext::Other => {
-                    // Ambiguous or negative platform branches are ignored to avoid duplicates.
+                let seen = platform_seen.entry(platform.to_string()).or_default();
+                if seen.insert(name.clone()) {
+                    platform_conditional
+                        .entry(platform.to_string())
+                        .or_default()
+                        .push(ConfigDefine {
+                            platform: Some(platform.to_string()),
+                            ..define.clone()
+                        });
                 }
             }
         }
     }
 
+    let guards = extract_error_guards(content);
+    for define in defines
+        .iter_mut()
+        .chain(platform_conditional.values_mut().flatten())
+    {
+        define.constraints = guards
+            .iter()
+            .filter(|(name, _)| *name == define.name)
+            .map(|(_, constraint)| constraint.clone())
+            .collect();
+    }
+
     Ok((defines, platform_conditional))
 }
 
-fn strip_inline_comment(line: &str) -> &str {
-    line.split("//").next().unwrap_or(line).trim()
+/// `#define NAME value` lines with a plain integer or identifier body, used to resolve
+/// aliases such as `#define STRIP_TYPE STRIP_TYPE_WS2812_RGB` and the constant families
+/// they point into.
+struct NamedConstants {
+    entries: Vec<NamedConstant>,
 }
 
-fn extract_ifndef_from_expr(expr: &str, re: &Regex) -> Option<String> {
-    re.captures(expr)
-        .and_then(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+struct NamedConstant {
+    name: String,
+    body: String,
+    /// Consecutive constant lines share a block; blank lines and code start a new one.
+    block: usize,
 }
 
-fn extract_platform_from_expr(expr: &str) -> (Option<String>, bool) {
-    let mut platforms: HashSet<String> = HashSet::new();
-    for token in expr
-        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
-        .filter(|t| !t.is_empty())
-    {
-        // Heuristic: extract single known platform token from complex preprocessor expressions.
-        if let Some(platform) = extract_platform_from_tokens(token) {
-            platforms.insert(platform);
-        }
-    }
+/// Alias hops followed before giving up, which also stops self-referential chains.
+const MAX_ALIAS_DEPTH: usize = 8;
 
-    let platform = if platforms.len() == 1 {
-        platforms.iter().next().cloned()
-    } else {
-        None
-    };
-    let platform_conditional = !platforms.is_empty();
-    (platform, platform_conditional)
-}
+impl NamedConstants {
+    fn collect(content: &str) -> Self {
+        static CONSTANT_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^#define\s+([A-Za-z_]\w*)\s+(\w+)$").expect("constant regex")
+        });
 
-fn extract_platform_from_tokens(token: &str) -> Option<String> {
-    match token {
-        "ESP32" | "ESP8266" | "__AVR__" => Some(token.to_string()),
-        _ => None,
+        let mut entries = Vec::new();
+        let mut block = 0;
+        for raw_line in content.lines() {
+            let trimmed = raw_line.trim();
+            if trimmed.starts_with("//") {
+                continue;
+            }
+            match CONSTANT_RE.captures(strip_inline_comment(trimmed)) {
+                Some(caps) => entries.push(NamedConstant {
+                    name: caps[1].to_string(),
+                    body: caps[2].to_string(),
+                    block,
+                }),
+                None => block += 1,
+            }
+        }
+        Self { entries }
     }
-}
 
-fn current_platform_context(stack: &[ConditionalFrame]) -> PlatformContext {
-    for frame in stack.iter().rev() {
-        if frame.platform_conditional {
-            return if frame.platform.is_some() {
-                PlatformContext::Specific
-            } else {
-                PlatformContext::Other
-            };
-        }
+    fn get(&self, name: &str) -> Option<&NamedConstant> {
+        self.entries.iter().find(|entry| entry.name == name)
     }
-    PlatformContext::None
-}
 
-fn current_platform_name(stack: &[ConditionalFrame]) -> Option<String> {
-    for frame in stack.iter().rev() {
-        if frame.platform_conditional {
-            if let Some(platform) = &frame.platform {
-                return Some(platform.clone());
+    /// Follows an identifier through `#define` aliases to an integer literal.
+    fn resolve(&self, value: &str) -> Option<String> {
+        let mut current = value;
+        for _ in 0..MAX_ALIAS_DEPTH {
+            if parse_integer(current).is_some() {
+                return (current != value).then(|| current.to_string());
             }
-            return None;
+            current = &self.get(current)?.body;
         }
+        None
     }
-    None
-}
 
-fn is_configurable_define(stack: &[ConditionalFrame], name: &str) -> bool {
-    stack
-        .iter()
-        .any(|frame| frame.ifndef_name.as_deref() == Some(name))
-}
+    /// Returns the enum options for a define whose default names a constant family
+    /// (`WIRING_SERPENTINE_H` among `WIRING_*`) or that has `NAME_*` constants of its own.
+    ///
+    /// Each option's value is the integer that `-D` must carry, because the family may be
+    /// defined after the define is first tested. The label is the constant's name.
+    fn family_for(&self, name: &str, default_value: &str) -> Option<Vec<EnumValue>> {
+        let default_value = strip_inline_comment(default_value);
+        let members: Vec<&NamedConstant> = match self.get(default_value) {
+            Some(target) => {
+                let prefix = match target.name.find('_') {
+                    Some(index) => &target.name[..=index],
+                    None => target.name.as_str(),
+                };
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.block == target.block && entry.name.starts_with(prefix))
+                    .collect()
+            }
+            None => {
+                let prefix = format!("{}_", name);
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.name.starts_with(&prefix))
+                    .collect()
+            }
+        };
 
-fn parse_value(
-    name: &str,
-    raw_value: &str,
-    comment: &Option<String>,
-) -> (ValueType, Option<Vec<EnumValue>>, String) {
-    // Check for enum pattern in comment: "0=Label1, 1=Label2"
-    if let Some(ref cmt) = comment {
-        static ENUM_RE: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"(\d+)\s*=\s*([^,]+)").expect("enum regex"));
-        let enum_values: Vec<EnumValue> = ENUM_RE
-            .captures_iter(cmt)
-            .filter_map(|caps| {
-                let value = caps.get(1)?.as_str().to_string();
-                let label = caps.get(2)?.as_str().trim().to_string();
-                Some(EnumValue { value, label })
+        let values: Vec<EnumValue> = members
+            .into_iter()
+            .filter(|entry| entry.name != name)
+            .filter_map(|entry| {
+                let value = if parse_integer(&entry.body).is_some() {
+                    entry.body.clone()
+                } else {
+                    self.resolve(&entry.body)?
+                };
+                Some(EnumValue {
+                    value,
+                    label: entry.name.clone(),
+                })
             })
             .collect();
 
-        if !enum_values.is_empty() {
-            return (ValueType::Enum, Some(enum_values), raw_value.to_string());
+        if values.len() >= 2 {
+            Some(values)
+        } else {
+            None
         }
     }
+}
 
-    // Check for pin-related names
-    if name.starts_with("PIN_") || name.ends_with("_PIN") {
-        return (ValueType::Pin, None, raw_value.to_string());
+/// Finds `#if` blocks whose first directive is `#error` and turns their conditions into
+/// per-define ranges.
+///
+/// Only `||`-joined comparisons between a define and an integer literal are understood,
+/// e.g. `#if PANEL_WIDTH < 1 || PANEL_HEIGHT < 1`. Anything else is skipped.
+fn extract_error_guards(content: &str) -> Vec<(String, ValueConstraint)> {
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+        .collect();
+
+    let mut guards = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let expr = match line.strip_prefix("#if") {
+            Some(rest) if rest.starts_with(char::is_whitespace) => strip_inline_comment(rest),
+            _ => continue,
+        };
+        let message = match lines.get(index + 1).and_then(|l| l.strip_prefix("#error")) {
+            Some(message) => message.trim().trim_matches('"').to_string(),
+            None => continue,
+        };
+
+        let comparisons: Option<Vec<(String, ValueConstraint)>> = expr
+            .split("||")
+            .map(|term| parse_guard_comparison(term, &message))
+            .collect();
+        guards.extend(comparisons.unwrap_or_default());
     }
+    guards
+}
 
-    // Check for boolean
-    if raw_value == "true" || raw_value == "false" || raw_value == "1" || raw_value == "0" {
-        if name.contains("ENABLED") || name.contains("ENABLE") || name.starts_with("USE_") {
-            return (ValueType::Boolean, None, raw_value.to_string());
+/// Converts one failing comparison into the range that keeps it false.
+fn parse_guard_comparison(term: &str, message: &str) -> Option<(String, ValueConstraint)> {
+    static COMPARISON_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^\(?\s*(\w+)\s*(<=|>=|<|>)\s*(\w+)\s*\)?$").expect("comparison regex")
+    });
+
+    let caps = COMPARISON_RE.captures(term.trim())?;
+    let (left, op, right) = (&caps[1], &caps[2], &caps[3]);
+
+    // Normalise `1 > PANEL_WIDTH` to `PANEL_WIDTH < 1`
+    let (name, op, limit) = match (parse_integer(left), parse_integer(right)) {
+        (None, Some(limit)) => (left, op, limit),
+        (Some(limit), None) => {
+            let flipped = match op {
+                "<" => ">",
+                ">" => "<",
+                "<=" => ">=",
+                _ => "<=",
+            };
+            (right, flipped, limit)
         }
+        _ => return None,
+    };
+
+    let (min, max) = match op {
+        "<" => (Some(limit), None),
+        "<=" => (Some(limit.checked_add(1)?), None),
+        ">" => (None, Some(limit)),
+        _ => (None, Some(limit.checked_sub(1)?)),
+    };
+
+    Some((
+        name.to_string(),
+        ValueConstraint {
+            min,
+            max,
+            message: message.to_string(),
+        },
+    ))
+}
+
+/// Parses a C integer literal, accepting hex and `U`/`L` suffixes.
+fn parse_integer(value: &str) -> Option<i64> {
+    let value = value.trim().trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return i64::from_str_radix(hex, 16).ok();
     }
+    value.parse::<i64>().ok()
+}
 
-    // Check for float
-    if raw_value.contains('.') {
-        if let Ok(_) = raw_value.parse::<f64>() {
-            return (ValueType::Float, None, raw_value.to_string());
+/// Checks proposed define values against the schema's `#error` guard constraints.
+pub fn validate_define_values(
+    schema: &AppConfigSchema,
+    values: &HashMap<String, String>,
+) -> Vec<ConstraintViolation> {
+    let mut names: Vec<&String> = values.keys().collect();
+    names.sort();
+
+    let mut violations = Vec::new();
+    for name in names {
+        let value = values[name].trim();
+        let constraints = schema
+            .defines
+            .iter()
+            .chain(schema.platform_conditional.values().flatten())
+            .filter(|define| &define.name == name)
+            .flat_map(|define| define.constraints.iter());
+
+        let mut seen: HashSet<&str> = HashSet::new();
+        for constraint in constraints {
+            if !seen.insert(constraint.message.as_str()) {
+                continue;
+            }
+            let message = match parse_integer(value) {
+                None => format!("{} must be an integer", name),
+                Some(n) if constraint.min.is_some_and(|min| n < min) => constraint.message.clone(),
+                Some(n) if constraint.max.is_some_and(|max| n > max) => constraint.message.clone(),
+                Some(_) => continue,
+            };
+            violations.push(ConstraintViolation {
+                name: name.clone(),
+                value: value.to_string(),
+                message,
+            });
         }
     }
+    violations
+}
 
-    // Check for integer
-    if let Ok(_) = raw_value.parse::<i64>() {
-        return (ValueType::Integer, None, raw_value.to_string());
-    }
+/// Checks that proposed define values parse as the types the schema infers for them.
+/// Defines the schema doesn't know about are skipped.
+pub fn validate_define_types(
+    schema: &AppConfigSchema,
+    values: &HashMap<String, String>,
+) -> Vec<ConstraintViolation> {
+    let mut names: Vec<&String> = values.keys().collect();
+    names.sort();
 
-    // Check for hex integer
-    if raw_value.starts_with("0x") || raw_value.starts_with("0X") {
-        if let Ok(_) = i64::from_str_radix(&raw_value[2..], 16) {
-            return (ValueType::Integer, None, raw_value.to_string());
-        }
+    let all_defines = || {
+        schema
+            .defines
+            .iter()
+            .chain(schema.platform_conditional.values().flatten())
+    };
+    let mut violations = Vec::new();
+    for name in names {
+        let value = values[name].trim();
+        let Some(define) = all_defines().find(|define| &define.name == name) else {
+            continue;
+        };
+
+        let message = match define.value_type {
+            ValueType::Integer if parse_integer(value).is_none() => {
+                format!("{} must be an integer", name)
+            }
+            ValueType::Float if value.trim_end_matches(['f', 'F']).parse::<f64>().is_err() => {
+                format!("{} must be a number", name)
+            }
+            ValueType::Boolean if !matches!(value, "0" | "1" | "true" | "false") => {
+                format!("{} must be 0, 1, true or false", name)
+            }
+            ValueType::Pin
+                if parse_integer(value).is_none()
+                    && !(value.starts_with('A') && value[1..].parse::<u8>().is_ok()) =>
+            {
+                format!("{} must be a pin number", name)
+            }
+            ValueType::Enum => {
+                let options = define.enum_values.as_deref().unwrap_or_default();
+                let known = options
+                    .iter()
+                    .any(|option| option.value == value || option.label == value)
+                    || all_defines().any(|other| other.name == value);
+                if known || options.is_empty() {
+                    continue;
+                }
+                let choices: Vec<&str> =
+                    options.iter().map(|option| option.value.as_str()).collect();
+                format!("{} must be one of {}", name, choices.join(", "))
+            }
+            _ => continue,
+        };
+        violations.push(ConstraintViolation {
+            name: name.clone(),
+            value: value.to_string(),
+            message,
+        });
     }
+    violations
+}
 
-    // Default to string
-    (ValueType::String, None, raw_value.to_string())
+/// Resolved value of one define for a proposed configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResolvedDefine {
+    pub name: String,
+    /// Macro body as written in config.h, or the override value.
+    pub expression: String,
+    /// Integer value after macro expansion, if the body is an integer expression.
+    pub value: Option<i64>,
+    /// True when config.h wraps the define in `#ifndef` so a build flag can replace it.
+    pub overridable: bool,
+    pub overridden: bool,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResolvedConfig {
+    pub values: Vec<ResolvedDefine>,
+    /// `#error` messages from branches the configuration would reach.
+    pub errors: Vec<String>,
+}
 
-    #[test]
-    fn test_no_config_file() {
-        let temp = tempdir().unwrap();
-        let result = parse_config_schema(temp.path()).unwrap();
-        assert!(!result.has_config);
-        assert!(result.defines.is_empty());
+/// Reads the app's config.h and resolves every define for the given `-D` values.
+pub fn resolve_config_values(
+    app_path: &Path,
+    defines: &HashMap<String, String>,
+) -> Result<ResolvedConfig, String> {
+    let manifest = dashboard_manifest::load_manifest(app_path)?.unwrap_or_default();
+    let sources = collect_header_sources(app_path, &manifest)?;
+    if sources.is_empty() {
+        return Err("App has no config.h".to_string());
     }
+    Ok(evaluate_config(&combined_content(&sources), defines))
+}
 
-    #[test]
-    fn test_basic_defines() {
-        let temp = tempdir().unwrap();
-        let src_dir = temp.path().join("src");
-        fs::create_dir_all(&src_dir).unwrap();
-        fs::write(
-            src_dir.join("config.h"),
-            r#"
-#ifndef PANEL_WIDTH
-#define PANEL_WIDTH 36
-#endif
-
-#ifndef PANEL_HEIGHT
-#define PANEL_HEIGHT 24
-#endif
+#[derive(Debug, Clone, Copy)]
+struct BranchFrame {
+    parent_active: bool,
+    active: bool,
+    taken: bool,
+}
 
-#ifndef BRIGHTNESS
-#define BRIGHTNESS 0.8
-#endif
-"#,
-        )
-        .unwrap();
+/// Runs config.h through a minimal preprocessor: `defines` act as `-D` flags, conditional
+/// branches are followed, and each object-like macro is expanded and evaluated as an
+/// integer expression where possible.
+pub fn evaluate_config(content: &str, defines: &HashMap<String, String>) -> ResolvedConfig {
+    static DIRECTIVE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^#\s*(\w+)\s*(.*)$").expect("directive regex"));
+    static MACRO_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^([A-Za-z_]\w*)(\()?\s*(.*)$").expect("macro regex"));
 
-        let result = parse_config_schema(temp.path()).unwrap();
-        assert!(result.has_config);
-        assert_eq!(result.defines.len(), 3);
+    let mut macros: HashMap<String, String> = defines
+        .iter()
+        .map(|(name, value)| {
+            let value = if value.trim().is_empty() {
+                "1"
+            } else {
+                value.trim()
+            };
+            (name.clone(), value.to_string())
+        })
+        .collect();
+    let mut order: Vec<String> = Vec::new();
+    let mut overridable: HashSet<String> = HashSet::new();
+    let mut errors = Vec::new();
+    let mut stack: Vec<BranchFrame> = Vec::new();
 
-        let width = result.defines.iter().find(|d| d.name == "PANEL_WIDTH").unwrap();
-        assert_eq!(width.default_value, "36");
-        assert_eq!(width.value_type, ValueType::Integer);
+    for raw_line in content.lines() {
+        let line = strip_c_comments(raw_line);
+        let caps = match DIRECTIVE_RE.captures(&line) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let directive = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+        let rest = caps.get(2).map(|m| m.as_str().trim()).unwrap_or("");
+        let active = stack.last().is_none_or(|frame| frame.active);
 
-        let brightness = result.defines.iter().find(|d| d.name == "BRIGHTNESS").unwrap();
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let condition = active
+                    && match directive {
+                        "ifdef" => macros.contains_key(first_word(rest)),
+                        "ifndef" => {
+                            overridable.insert(first_word(rest).to_string());
+                            !macros.contains_key(first_word(rest))
+                        }
+                        _ => eval_condition(rest, &macros),
+                    };
+                stack.push(BranchFrame {
+                    parent_active: active,
+                    active: condition,
+                    taken: condition,
+                });
+            }
+            "elif" => {
+                if let Some(frame) = stack.last_mut() {
+                    let condition =
+                        frame.parent_active && !frame.taken && eval_condition(rest, &macros);
+                    frame.active = condition;
+                    frame.taken |= condition;
+                }
+            }
+            "else" => {
+                if let Some(frame) = stack.last_mut() {
+                    frame.active = frame.parent_active && !frame.taken;
+                    frame.taken = true;
+                }
+            }
+            "endif" => {
+                stack.pop();
+            }
+            "define" if active => {
+                let Some(m) = MACRO_RE.captures(rest) else {
+                    continue;
+                };
+                // Function-like macros are not configuration values
+                if m.get(2).is_some() {
+                    continue;
+                }
+                let name = m[1].to_string();
+                let body = m.get(3).map(|b| b.as_str().trim()).unwrap_or("");
+                macros.insert(name.clone(), body.to_string());
+                if !order.contains(&name) {
+                    order.push(name);
+                }
+            }
+            "undef" if active => {
+                macros.remove(first_word(rest));
+            }
+            "error" if active => {
+                errors.push(rest.trim_matches('"').to_string());
+            }
+            _ => {}
+        }
+    }
+
+    // Defines that were supplied as flags and guarded by #ifndef never hit a #define
+    for name in defines.keys() {
+        if overridable.contains(name) && !order.contains(name) {
+            order.push(name.clone());
+        }
+    }
+    // Keep the header's order for guarded flags too
+    let position: HashMap<&str, usize> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let rest = line.trim().strip_prefix("#ifndef")?;
+            Some((first_word(rest.trim()), i))
+        })
+        .collect();
+    let define_line: HashMap<&str, usize> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let rest = line.trim().strip_prefix("#define")?;
+            Some((first_word(rest.trim()), i))
+        })
+        .collect();
+    order.sort_by_key(|name| {
+        position
+            .get(name.as_str())
+            .or_else(|| define_line.get(name.as_str()))
+            .copied()
+            .unwrap_or(usize::MAX)
+    });
+
+    let values = order
+        .into_iter()
+        .filter_map(|name| {
+            let expression = macros.get(&name)?.clone();
+            let value = tokenize_expression(&expression)
+                .and_then(|tokens| expand_tokens(&tokens, &macros, false, &mut Vec::new()))
+                .and_then(|tokens| ExprParser::new(&tokens).parse_all())
+                .ok();
+            Some(ResolvedDefine {
+                overridable: overridable.contains(&name),
+                overridden: defines.contains_key(&name),
+                name,
+                expression,
+                value,
+            })
+        })
+        .collect();
+
+    ResolvedConfig { values, errors }
+}
+
+/// Returns the comment-stripped code lines of a source file that survive preprocessing
+/// with the given macros. Directives are consumed; `#define`s inside the file apply to
+/// later conditions.
+pub fn active_source_lines(content: &str, macros: &HashMap<String, String>) -> Vec<String> {
+    static DIRECTIVE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^#\s*(\w+)\s*(.*)$").expect("directive regex"));
+
+    let mut macros = macros.clone();
+    let mut stack: Vec<BranchFrame> = Vec::new();
+    let mut lines = Vec::new();
+
+    for raw_line in content.lines() {
+        let line = strip_c_comments(raw_line);
+        let active = stack.last().is_none_or(|frame| frame.active);
+        let Some(caps) = DIRECTIVE_RE.captures(&line) else {
+            if active && !line.is_empty() {
+                lines.push(line);
+            }
+            continue;
+        };
+        let rest = caps.get(2).map(|m| m.as_str().trim()).unwrap_or("");
+
+        match &caps[1] {
+            directive @ ("if" | "ifdef" | "ifndef") => {
+                let condition = active
+                    && match directive {
+                        "ifdef" => macros.contains_key(first_word(rest)),
+                        "ifndef" => !macros.contains_key(first_word(rest)),
+                        _ => eval_condition(rest, &macros),
+                    };
+                stack.push(BranchFrame {
+                    parent_active: active,
+                    active: condition,
+                    taken: condition,
+                });
+            }
+            "elif" => {
+                if let Some(frame) = stack.last_mut() {
+                    let condition =
+                        frame.parent_active && !frame.taken && eval_condition(rest, &macros);
+                    frame.active = condition;
+                    frame.taken |= condition;
+                }
+            }
+            "else" => {
+                if let Some(frame) = stack.last_mut() {
+                    frame.active = frame.parent_active && !frame.taken;
+                    frame.taken = true;
+                }
+            }
+            "endif" => {
+                stack.pop();
+            }
+            "define" if active => {
+                let name = first_word(rest);
+                if !name.contains('(') {
+                    let body = rest[name.len()..].trim();
+                    macros.insert(name.to_string(), body.to_string());
+                }
+            }
+            "undef" if active => {
+                macros.remove(first_word(rest));
+            }
+            _ => {}
+        }
+    }
+
+    lines
+}
+
+fn first_word(text: &str) -> &str {
+    text.split_whitespace().next().unwrap_or("")
+}
+
+/// Removes `//` and single-line `/* */` comments.
+fn strip_c_comments(line: &str) -> String {
+    let mut text = line.split("//").next().unwrap_or(line).to_string();
+    while let Some(start) = text.find("/*") {
+        match text[start..].find("*/") {
+            Some(end) => text.replace_range(start..start + end + 2, " "),
+            None => text.truncate(start),
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Evaluates an `#if` condition; undefined identifiers count as 0 and errors as false.
+fn eval_condition(expr: &str, macros: &HashMap<String, String>) -> bool {
+    tokenize_expression(expr)
+        .and_then(|tokens| expand_tokens(&tokens, macros, true, &mut Vec::new()))
+        .and_then(|tokens| ExprParser::new(&tokens).parse_all())
+        .map(|value| value != 0)
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ExprToken {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &[
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "(", ")", "!", "~", "*", "/", "%", "+", "-",
+    "<", ">", "&", "^", "|", "?", ":",
+];
+
+fn tokenize_expression(expr: &str) -> Result<Vec<ExprToken>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+
+    while !rest.is_empty() {
+        let first = rest.chars().next().unwrap_or(' ');
+        if first.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                .unwrap_or(rest.len());
+            let literal = &rest[..end];
+            let digits = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+            // A leading zero without `x` is an octal literal such as 0755
+            let octal = digits
+                .strip_prefix('0')
+                .filter(|d| !d.is_empty() && d.chars().all(|c| c.is_ascii_digit()));
+            let value = match octal {
+                Some(d) => i64::from_str_radix(d, 8).ok(),
+                None => parse_integer(literal),
+            }
+            .ok_or_else(|| format!("Unsupported literal: {}", literal))?;
+            tokens.push(ExprToken::Number(value));
+            rest = &rest[end..];
+        } else if first.is_ascii_alphabetic() || first == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(ExprToken::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("Unexpected character '{}'", first))?;
+            tokens.push(ExprToken::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Replaces macro names with their bodies. In `#if` context, `defined` is resolved and
+/// unknown identifiers become 0; otherwise an unknown identifier is an error.
+fn expand_tokens(
+    tokens: &[ExprToken],
+    macros: &HashMap<String, String>,
+    in_condition: bool,
+    expanding: &mut Vec<String>,
+) -> Result<Vec<ExprToken>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            ExprToken::Ident(name) if in_condition && name == "defined" => {
+                let (target, consumed) = match (tokens.get(i + 1), tokens.get(i + 2)) {
+                    (Some(ExprToken::Op("(")), Some(ExprToken::Ident(target))) => {
+                        if tokens.get(i + 3) != Some(&ExprToken::Op(")")) {
+                            return Err("Malformed defined()".to_string());
+                        }
+                        (target, 4)
+                    }
+                    (Some(ExprToken::Ident(target)), _) => (target, 2),
+                    _ => return Err("Malformed defined".to_string()),
+                };
+                out.push(ExprToken::Number(macros.contains_key(target) as i64));
+                i += consumed;
+                continue;
+            }
+            ExprToken::Ident(name) => match macros.get(name) {
+                Some(body) if !expanding.contains(name) => {
+                    expanding.push(name.clone());
+                    let body_tokens = tokenize_expression(body)?;
+                    let expanded = expand_tokens(&body_tokens, macros, in_condition, expanding);
+                    expanding.pop();
+                    out.extend(expanded?);
+                }
+                _ if in_condition => out.push(ExprToken::Number(0)),
+                _ => return Err(format!("Unknown identifier: {}", name)),
+            },
+            token => out.push(token.clone()),
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+/// Precedence-climbing parser for C integer constant expressions.
+struct ExprParser<'a> {
+    tokens: &'a [ExprToken],
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn new(tokens: &'a [ExprToken]) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn parse_all(mut self) -> Result<i64, String> {
+        let value = self.parse_ternary()?;
+        if self.pos != self.tokens.len() {
+            return Err("Unexpected trailing tokens".to_string());
+        }
+        Ok(value)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(ExprToken::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}'", op))
+        }
+    }
+
+    fn parse_ternary(&mut self) -> Result<i64, String> {
+        let condition = self.parse_binary(0)?;
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let then_value = self.parse_ternary()?;
+        self.expect(":")?;
+        let else_value = self.parse_ternary()?;
+        Ok(if condition != 0 {
+            then_value
+        } else {
+            else_value
+        })
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<i64, String> {
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.peek_op() {
+            let precedence = match binary_precedence(op) {
+                Some(p) if p >= min_precedence => p,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.parse_binary(precedence + 1)?;
+            left = apply_binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<i64, String> {
+        match self.tokens.get(self.pos) {
+            Some(ExprToken::Number(value)) => {
+                self.pos += 1;
+                Ok(*value)
+            }
+            Some(ExprToken::Op("(")) => {
+                self.pos += 1;
+                let value = self.parse_ternary()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(ExprToken::Op(op @ ("!" | "~" | "-" | "+"))) => {
+                let op = *op;
+                self.pos += 1;
+                let value = self.parse_unary()?;
+                Ok(match op {
+                    "!" => (value == 0) as i64,
+                    "~" => !value,
+                    "-" => value.wrapping_neg(),
+                    _ => value,
+                })
+            }
+            Some(ExprToken::Ident(name)) => Err(format!("Unknown identifier: {}", name)),
+            _ => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn binary_precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | "<=" | ">" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        _ => return None,
+    })
+}
+
+fn apply_binary(op: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match op {
+        "||" => (left != 0 || right != 0) as i64,
+        "&&" => (left != 0 && right != 0) as i64,
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("Division by zero".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        _ => return Err(format!("Unsupported operator '{}'", op)),
+    })
+}
+
+/// Collects `//` comment lines into doc blocks and tracks the current section heading.
+///
+/// Headings are written as a `// ----` rule, one or more title lines, and a closing rule,
+/// or on a single line as `// ---- Title ----`.
+#[derive(Debug, Default)]
+struct CommentTracker {
+    pending: Vec<String>,
+    heading: Option<Vec<String>>,
+    section: Option<String>,
+}
+
+impl CommentTracker {
+    /// Ends the current comment block; a rule without a closing rule is not a heading.
+    fn end_block(&mut self) {
+        self.pending.clear();
+        self.heading = None;
+    }
+
+    fn push(&mut self, text: &str) {
+        let text = text.trim();
+        if is_comment_rule(text) {
+            if text.starts_with('-') {
+                match self.heading.take() {
+                    Some(title) => self.section = Some(title.join(" ")),
+                    None => self.heading = Some(Vec::new()),
+                }
+            }
+            self.pending.clear();
+            return;
+        }
+        if let Some(title) = inline_heading(text) {
+            self.section = Some(title.to_string());
+            self.heading = None;
+            self.pending.clear();
+            return;
+        }
+
+        match self.heading.as_mut() {
+            Some(title) => title.push(text.to_string()),
+            None => self.pending.push(text.to_string()),
+        }
+    }
+}
+
+fn is_comment_rule(text: &str) -> bool {
+    text.len() >= 3 && (text.chars().all(|c| c == '-') || text.chars().all(|c| c == '='))
+}
+
+/// Returns the title of a `---- Title ----` heading.
+fn inline_heading(text: &str) -> Option<&str> {
+    static INLINE_HEADING_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^-{3,}\s+(.*?[^\s-].*?)\s+-{3,}$").expect("inline heading regex")
+    });
+    INLINE_HEADING_RE
+        .captures(text)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str())
+}
+
+/// Joins a comment block and the define's trailing comment into help text.
+fn describe(doc: &[String], trailing: Option<String>) -> Option<String> {
+    let mut lines: Vec<String> = doc.iter().filter(|l| !l.is_empty()).cloned().collect();
+    lines.extend(trailing.filter(|c| !c.is_empty()));
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Parses enum lists written one value per comment line, e.g. `0 = SK6812 RGBW - 4-channel`.
+/// The label stops at the first ` - ` so the details stay in the description.
+fn parse_enum_lines(doc: &[String]) -> Vec<EnumValue> {
+    static ENUM_LINE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^(\d+)\s*=\s*(.+)$").expect("enum line regex"));
+    doc.iter()
+        .filter_map(|line| {
+            let caps = ENUM_LINE_RE.captures(line)?;
+            let value = caps.get(1)?.as_str().to_string();
+            let text = caps.get(2)?.as_str();
+            let label = text.split(" - ").next().unwrap_or(text).trim().to_string();
+            Some(EnumValue { value, label })
+        })
+        .collect()
+}
+
+fn strip_inline_comment(line: &str) -> &str {
+    line.split("//").next().unwrap_or(line).trim()
+}
+
+fn extract_ifndef_from_expr(expr: &str, re: &Regex) -> Option<String> {
+    re.captures(expr)
+        .and_then(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+}
+
+fn is_configurable_define(stack: &[ConditionalFrame], name: &str) -> bool {
+    stack
+        .iter()
+        .any(|frame| frame.ifndef_name.as_deref() == Some(name))
+}
+
+fn parse_value(
+    name: &str,
+    raw_value: &str,
+    comment: &Option<String>,
+    doc: &[String],
+) -> (ValueType, Option<Vec<EnumValue>>, String) {
+    // Check for enum pattern in comment: "0=Label1, 1=Label2"
+    if let Some(ref cmt) = comment {
+        static ENUM_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"(\d+)\s*=\s*([^,]+)").expect("enum regex"));
+        let enum_values: Vec<EnumValue> = ENUM_RE
+            .captures_iter(cmt)
+            .filter_map(|caps| {
+                let value = caps.get(1)?.as_str().to_string();
+                let label = caps.get(2)?.as_str().trim().to_string();
+                Some(EnumValue { value, label })
+            })
+            .collect();
+
+        if !enum_values.is_empty() {
+            return (ValueType::Enum, Some(enum_values), raw_value.to_string());
+        }
+    }
+
+    let enum_values = parse_enum_lines(doc);
+    if !enum_values.is_empty() {
+        return (ValueType::Enum, Some(enum_values), raw_value.to_string());
+    }
+
+    // Check for pin-related names
+    if name.starts_with("PIN_") || name.ends_with("_PIN") {
+        return (ValueType::Pin, None, raw_value.to_string());
+    }
+
+    // Check for boolean
+    if raw_value == "true" || raw_value == "false" || raw_value == "1" || raw_value == "0" {
+        if name.contains("ENABLED") || name.contains("ENABLE") || name.starts_with("USE_") {
+            return (ValueType::Boolean, None, raw_value.to_string());
+        }
+    }
+
+    // Check for float
+    if raw_value.contains('.') {
+        if let Ok(_) = raw_value.parse::<f64>() {
+            return (ValueType::Float, None, raw_value.to_string());
+        }
+    }
+
+    // Check for integer
+    if let Ok(_) = raw_value.parse::<i64>() {
+        return (ValueType::Integer, None, raw_value.to_string());
+    }
+
+    // Check for hex integer
+    if raw_value.starts_with("0x") || raw_value.starts_with("0X") {
+        if let Ok(_) = i64::from_str_radix(&raw_value[2..], 16) {
+            return (ValueType::Integer, None, raw_value.to_string());
+        }
+    }
+
+    // Default to string
+    (ValueType::String, None, raw_value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_no_config_file() {
+        let temp = tempdir().unwrap();
+        let result = parse_config_schema(temp.path()).unwrap();
+        assert!(!result.has_config);
+        assert!(result.defines.is_empty());
+    }
+
+    #[test]
+    fn test_basic_defines() {
+        let temp = tempdir().unwrap();
+        let src_dir = temp.path().join("src");
+        fs::create_dir_all(&src_dir).unwrap();
+        fs::write(
+            src_dir.join("config.h"),
+            r#"
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 36
+#endif
+
+#ifndef PANEL_HEIGHT
+#define PANEL_HEIGHT 24
+#endif
+
+#ifndef BRIGHTNESS
+#define BRIGHTNESS 0.8
+#endif
+"#,
+        )
+        .unwrap();
+
+        let result = parse_config_schema(temp.path()).unwrap();
+        assert!(result.has_config);
+        assert_eq!(result.defines.len(), 3);
+
+        let width = result
+            .defines
+            .iter()
+            .find(|d| d.name == "PANEL_WIDTH")
+            .unwrap();
+        assert_eq!(width.default_value, "36");
+        assert_eq!(width.value_type, ValueType::Integer);
+
+        let brightness = result
+            .defines
+            .iter()
+            .find(|d| d.name == "BRIGHTNESS")
+            .unwrap();
         assert_eq!(brightness.value_type, ValueType::Float);
     }
 
@@ -410,7 +1629,11 @@ mod tests {
         .unwrap();
 
         let result = parse_config_schema(temp.path()).unwrap();
-        let strip = result.defines.iter().find(|d| d.name == "STRIP_TYPE").unwrap();
+        let strip = result
+            .defines
+            .iter()
+            .find(|d| d.name == "STRIP_TYPE")
+            .unwrap();
 
         assert_eq!(strip.value_type, ValueType::Enum);
         let enums = strip.enum_values.as_ref().unwrap();
@@ -435,7 +1658,11 @@ mod tests {
         .unwrap();
 
         let result = parse_config_schema(temp.path()).unwrap();
-        let pin = result.defines.iter().find(|d| d.name == "PIN_LED_DATA").unwrap();
+        let pin = result
+            .defines
+            .iter()
+            .find(|d| d.name == "PIN_LED_DATA")
+            .unwrap();
         assert_eq!(pin.value_type, ValueType::Pin);
     }
 
@@ -470,14 +1697,8 @@ mod tests {
 
         let result = parse_config_schema(temp.path()).unwrap();
         assert_eq!(result.defines.len(), 1);
-        assert!(result
-            .defines
-            .iter()
-            .any(|d| d.name == "PANEL_WIDTH"));
-        assert!(result
-            .defines
-            .iter()
-            .all(|d| d.name != "PIN_LED_DATA"));
+        assert!(result.defines.iter().any(|d| d.name == "PANEL_WIDTH"));
+        assert!(result.defines.iter().all(|d| d.name != "PIN_LED_DATA"));
 
         let esp32 = result
             .platform_conditional
@@ -493,4 +1714,849 @@ mod tests {
         assert_eq!(esp8266.len(), 1);
         assert_eq!(esp8266[0].default_value, "2");
     }
+
+    #[test]
+    fn test_error_guard_constraints() {
+        let content = r#"
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 36
+#endif
+
+#ifndef PANEL_HEIGHT
+#define PANEL_HEIGHT 24
+#endif
+
+#ifndef TARGET_FPS
+#define TARGET_FPS 30
+#endif
+
+#if PANEL_WIDTH < 1 || PANEL_HEIGHT < 1
+#error "PANEL_WIDTH and PANEL_HEIGHT must be at least 1"
+#endif
+
+// Keep the frame time above 1 ms
+#if (TARGET_FPS > 1000)
+    #error "TARGET_FPS must be 1000 or less"
+#endif
+
+#if PANEL_WIDTH * PANEL_HEIGHT > 4096
+#error "Too many pixels"
+#endif
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        let width = defines.iter().find(|d| d.name == "PANEL_WIDTH").unwrap();
+        assert_eq!(
+            width.constraints,
+            vec![ValueConstraint {
+                min: Some(1),
+                max: None,
+                message: "PANEL_WIDTH and PANEL_HEIGHT must be at least 1".to_string(),
+            }]
+        );
+
+        let fps = defines.iter().find(|d| d.name == "TARGET_FPS").unwrap();
+        assert_eq!(fps.constraints.len(), 1);
+        assert_eq!(fps.constraints[0].min, None);
+        assert_eq!(fps.constraints[0].max, Some(1000));
+    }
+
+    #[test]
+    fn test_guard_comparison_forms() {
+        let parse = |term| parse_guard_comparison(term, "msg").map(|(n, c)| (n, c.min, c.max));
+        assert_eq!(parse("X <= 0"), Some(("X".to_string(), Some(1), None)));
+        assert_eq!(parse("X >= 0x10"), Some(("X".to_string(), None, Some(15))));
+        assert_eq!(parse("1 > X"), Some(("X".to_string(), Some(1), None)));
+        assert_eq!(parse("X == 3"), None);
+        assert_eq!(parse("X < Y"), None);
+    }
+
+    #[test]
+    fn test_validate_define_values() {
+        let content = r#"
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 36
+#endif
+
+#ifndef PANEL_HEIGHT
+#define PANEL_HEIGHT 24
+#endif
+
+#if PANEL_WIDTH < 1 || PANEL_HEIGHT < 1
+#error "PANEL_WIDTH and PANEL_HEIGHT must be at least 1"
+#endif
+"#;
+        let (defines, platform_conditional) = extract_config_defines(content, &[]).unwrap();
+        let schema = AppConfigSchema {
+            has_config: true,
+            defines,
+            platform_conditional,
+            ..Default::default()
+        };
+
+        let values: HashMap<String, String> = [
+            ("PANEL_WIDTH", "0"),
+            ("PANEL_HEIGHT", "wide"),
+            ("STRIP_TYPE", "1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let violations = validate_define_values(&schema, &values);
+        assert_eq!(
+            violations,
+            vec![
+                ConstraintViolation {
+                    name: "PANEL_HEIGHT".to_string(),
+                    value: "wide".to_string(),
+                    message: "PANEL_HEIGHT must be an integer".to_string(),
+                },
+                ConstraintViolation {
+                    name: "PANEL_WIDTH".to_string(),
+                    value: "0".to_string(),
+                    message: "PANEL_WIDTH and PANEL_HEIGHT must be at least 1".to_string(),
+                },
+            ]
+        );
+
+        let ok: HashMap<String, String> = [("PANEL_WIDTH".to_string(), "48".to_string())]
+            .into_iter()
+            .collect();
+        assert!(validate_define_values(&schema, &ok).is_empty());
+    }
+
+    const DERIVED_CONFIG: &str = r#"
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 36
+#endif
+
+#ifndef PANEL_HEIGHT
+#define PANEL_HEIGHT 24
+#endif
+
+#define NUM_PIXELS (PANEL_WIDTH * PANEL_HEIGHT)
+
+#if PANEL_WIDTH < 1 || PANEL_HEIGHT < 1
+#error "PANEL_WIDTH and PANEL_HEIGHT must be at least 1"
+#endif
+
+#ifndef STRIP_TYPE
+#define STRIP_TYPE 0
+#endif
+
+#ifdef ESP32
+    #ifndef PIN_LED_DATA
+    #define PIN_LED_DATA 4
+    #endif
+#elif defined(ESP8266)
+    #ifndef PIN_BRIGHTNESS
+    #define PIN_BRIGHTNESS A0
+    #endif
+#endif
+
+#define BEAT_THRESHOLD     1.5f
+
+#if STRIP_TYPE == 0
+    #define TARGET_FPS 25       // SK6812: 864 x 40us = 34.5ms data time
+#else
+    #define TARGET_FPS 30       /* WS2815B */
+#endif
+
+#define FRAME_MS (1000U / TARGET_FPS)
+"#;
+
+    fn resolved(config: &ResolvedConfig, name: &str) -> Option<i64> {
+        config
+            .values
+            .iter()
+            .find(|d| d.name == name)
+            .and_then(|d| d.value)
+    }
+
+    fn flags(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate_config_defaults() {
+        let config = evaluate_config(DERIVED_CONFIG, &HashMap::new());
+        assert_eq!(resolved(&config, "NUM_PIXELS"), Some(864));
+        assert_eq!(resolved(&config, "TARGET_FPS"), Some(25));
+        assert_eq!(resolved(&config, "FRAME_MS"), Some(40));
+        assert!(config.errors.is_empty());
+
+        let names: Vec<&str> = config.values.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "PANEL_WIDTH",
+                "PANEL_HEIGHT",
+                "NUM_PIXELS",
+                "STRIP_TYPE",
+                "BEAT_THRESHOLD",
+                "TARGET_FPS",
+                "FRAME_MS"
+            ]
+        );
+
+        let beat = config
+            .values
+            .iter()
+            .find(|d| d.name == "BEAT_THRESHOLD")
+            .unwrap();
+        assert_eq!(beat.expression, "1.5f");
+        assert_eq!(beat.value, None);
+        assert!(!beat.overridable);
+    }
+
+    #[test]
+    fn test_evaluate_config_overrides_follow_branches() {
+        let config = evaluate_config(
+            DERIVED_CONFIG,
+            &flags(&[("PANEL_WIDTH", "48"), ("STRIP_TYPE", "1"), ("ESP8266", "")]),
+        );
+        assert_eq!(resolved(&config, "NUM_PIXELS"), Some(1152));
+        assert_eq!(resolved(&config, "TARGET_FPS"), Some(30));
+        assert_eq!(resolved(&config, "FRAME_MS"), Some(33));
+
+        let width = config
+            .values
+            .iter()
+            .find(|d| d.name == "PANEL_WIDTH")
+            .unwrap();
+        assert!(width.overridable && width.overridden);
+        assert_eq!(width.expression, "48");
+
+        let brightness = config
+            .values
+            .iter()
+            .find(|d| d.name == "PIN_BRIGHTNESS")
+            .unwrap();
+        assert_eq!(brightness.expression, "A0");
+        assert_eq!(brightness.value, None);
+        assert!(config.values.iter().all(|d| d.name != "PIN_LED_DATA"));
+    }
+
+    #[test]
+    fn test_evaluate_config_reports_reached_errors() {
+        let config = evaluate_config(DERIVED_CONFIG, &flags(&[("PANEL_HEIGHT", "0")]));
+        assert_eq!(
+            config.errors,
+            vec!["PANEL_WIDTH and PANEL_HEIGHT must be at least 1".to_string()]
+        );
+        assert_eq!(resolved(&config, "NUM_PIXELS"), Some(0));
+    }
+
+    #[test]
+    fn test_expression_evaluator() {
+        let macros: HashMap<String, String> = flags(&[("A", "1 + 2"), ("B", "A * 3")]);
+        let eval = |expr: &str| {
+            tokenize_expression(expr)
+                .and_then(|t| expand_tokens(&t, &macros, true, &mut Vec::new()))
+                .and_then(|t| ExprParser::new(&t).parse_all())
+        };
+        assert_eq!(eval("B"), Ok(7));
+        assert_eq!(eval("(1 << 4) | 0x0F"), Ok(31));
+        assert_eq!(eval("defined(A) && !defined B"), Ok(0));
+        assert_eq!(eval("UNKNOWN == 0 ? 10 : 20"), Ok(10));
+        assert_eq!(eval("-3 + 010"), Ok(5));
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("(1").is_err());
+    }
+
+    #[test]
+    fn test_named_constant_enum_family() {
+        let content = r#"
+#define STRIP_TYPE_SK6812     0
+#define STRIP_TYPE_WS2812_RGB 1
+
+#ifndef STRIP_TYPE
+#define STRIP_TYPE STRIP_TYPE_WS2812_RGB
+#endif
+
+#define WIRING_SERPENTINE_H  0
+#define WIRING_PROGRESSIVE_H 1
+#define WIRING_SERPENTINE_V  2
+
+#ifndef WIRING_PATTERN
+#define WIRING_PATTERN WIRING_SERPENTINE_V
+#endif
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        let strip = defines.iter().find(|d| d.name == "STRIP_TYPE").unwrap();
+        assert_eq!(strip.value_type, ValueType::Enum);
+        assert_eq!(strip.default_value, "1");
+        let options: Vec<(&str, &str)> = strip
+            .enum_values
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|e| (e.value.as_str(), e.label.as_str()))
+            .collect();
+        assert_eq!(
+            options,
+            vec![("0", "STRIP_TYPE_SK6812"), ("1", "STRIP_TYPE_WS2812_RGB")]
+        );
+
+        let wiring = defines.iter().find(|d| d.name == "WIRING_PATTERN").unwrap();
+        assert_eq!(wiring.value_type, ValueType::Enum);
+        assert_eq!(wiring.default_value, "2");
+        assert_eq!(wiring.enum_values.as_ref().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_prefixed_constants_without_symbolic_default() {
+        let content = r#"
+#ifndef AUDIO_INPUT_MODE
+#define AUDIO_INPUT_MODE 0
+#endif
+
+// Input sources
+#define AUDIO_INPUT_MODE_MIC 0
+#define AUDIO_INPUT_MODE_ADC 1
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        let mode = &defines[0];
+        assert_eq!(mode.value_type, ValueType::Enum);
+        assert_eq!(mode.default_value, "0");
+        assert_eq!(
+            mode.enum_values.as_ref().unwrap()[1].label,
+            "AUDIO_INPUT_MODE_ADC"
+        );
+    }
+
+    #[test]
+    fn test_define_alias_resolves_to_integer() {
+        let content = r#"
+#define DEFAULT_LEVEL 128
+#define SAFE_LEVEL DEFAULT_LEVEL
+
+#ifndef DEFAULT_BRIGHTNESS
+#define DEFAULT_BRIGHTNESS SAFE_LEVEL
+#endif
+
+#ifndef LOOP_A
+#define LOOP_A LOOP_A
+#endif
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        let brightness = defines
+            .iter()
+            .find(|d| d.name == "DEFAULT_BRIGHTNESS")
+            .unwrap();
+        assert_eq!(brightness.value_type, ValueType::Integer);
+        assert_eq!(brightness.default_value, "128");
+
+        let looped = defines.iter().find(|d| d.name == "LOOP_A").unwrap();
+        assert_eq!(looped.value_type, ValueType::String);
+        assert_eq!(looped.default_value, "LOOP_A");
+    }
+
+    #[test]
+    fn test_compound_platform_conditionals() {
+        let content = r#"
+#if defined(ESP32) || defined(NATIVE_BUILD)
+    #ifndef PIN_LED_DATA
+    #define PIN_LED_DATA 4
+    #endif
+#endif
+
+#ifndef AUDIO_ENABLED
+    #ifdef ESP32
+        #define AUDIO_ENABLED 1
+    #else
+        #define AUDIO_ENABLED 0
+    #endif
+#endif
+
+#if CONFIG_IDF_TARGET_ESP32S3
+    #ifndef PIN_STATUS_LED
+    #define PIN_STATUS_LED 48
+    #endif
+#elif defined(ARDUINO_ARCH_ESP8266)
+    #ifndef PIN_STATUS_LED
+    #define PIN_STATUS_LED 2
+    #endif
+#endif
+"#;
+
+        let (defines, platform_conditional) = extract_config_defines(content, &[]).unwrap();
+        assert!(defines.is_empty());
+
+        let bucket = |platform: &str, name: &str| {
+            platform_conditional
+                .get(platform)
+                .and_then(|defines| defines.iter().find(|d| d.name == name))
+                .map(|d| d.default_value.clone())
+        };
+        assert_eq!(bucket("ESP32", "PIN_LED_DATA"), Some("4".to_string()));
+        assert_eq!(
+            bucket("NATIVE_BUILD", "PIN_LED_DATA"),
+            Some("4".to_string())
+        );
+        assert_eq!(bucket("ESP8266", "PIN_LED_DATA"), None);
+
+        assert_eq!(bucket("ESP32C3", "AUDIO_ENABLED"), Some("1".to_string()));
+        assert_eq!(bucket("ESP8266", "AUDIO_ENABLED"), Some("0".to_string()));
+        assert_eq!(
+            bucket("NATIVE_BUILD", "AUDIO_ENABLED"),
+            Some("0".to_string())
+        );
+
+        assert_eq!(bucket("ESP32S3", "PIN_STATUS_LED"), Some("48".to_string()));
+        assert_eq!(bucket("ESP32", "PIN_STATUS_LED"), None);
+        assert_eq!(bucket("ESP8266", "PIN_STATUS_LED"), Some("2".to_string()));
+    }
+
+    #[test]
+    fn test_define_environments_from_platformio_ini() {
+        let temp = tempdir().unwrap();
+        let src_dir = temp.path().join("src");
+        fs::create_dir_all(&src_dir).unwrap();
+        fs::write(
+            temp.path().join("platformio.ini"),
+            r#"
+[env:esp32]
+platform = espressif32
+board = esp32dev
+
+[env:s3]
+platform = espressif32
+board = esp32-s3-devkitc-1
+
+[env:esp8266]
+platform = espressif8266
+board = nodemcuv2
+
+[env:native]
+platform = native
+build_flags = -DNATIVE_BUILD
+"#,
+        )
+        .unwrap();
+        fs::write(
+            src_dir.join("config.h"),
+            r#"
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 26
+#endif
+
+#if defined(ESP32) || defined(NATIVE_BUILD)
+    #ifndef PIN_I2S_MCLK
+    #define PIN_I2S_MCLK 27
+    #endif
+#endif
+
+#ifndef AUDIO_ENABLED
+    #ifdef ESP32
+        #define AUDIO_ENABLED 1
+    #else
+        #define AUDIO_ENABLED 0
+    #endif
+#endif
+"#,
+        )
+        .unwrap();
+
+        let schema = parse_config_schema(temp.path()).unwrap();
+        assert_eq!(
+            schema.defines[0].environments,
+            vec!["esp32", "s3", "esp8266", "native"]
+        );
+
+        let mclk = &schema.platform_conditional["ESP32"][0];
+        assert_eq!(mclk.environments, vec!["esp32", "s3", "native"]);
+
+        let audio_off = schema.platform_conditional["ESP8266"]
+            .iter()
+            .find(|d| d.name == "AUDIO_ENABLED")
+            .unwrap();
+        assert_eq!(audio_off.default_value, "0");
+        assert_eq!(audio_off.environments, vec!["esp8266", "native"]);
+    }
+
+    #[test]
+    fn test_follows_local_includes() {
+        let temp = tempdir().unwrap();
+        let src_dir = temp.path().join("src");
+        let include_dir = temp.path().join("include");
+        fs::create_dir_all(&src_dir).unwrap();
+        fs::create_dir_all(&include_dir).unwrap();
+        fs::write(
+            src_dir.join("config.h"),
+            r#"
+#include <Arduino.h>
+#include "audio_config.h"
+#include "missing.h"
+
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 26
+#endif
+"#,
+        )
+        .unwrap();
+        fs::write(
+            include_dir.join("audio_config.h"),
+            r#"
+#include "config.h"
+
+// Samples per second
+#ifndef AUDIO_SAMPLE_RATE
+#define AUDIO_SAMPLE_RATE 44100
+#endif
+"#,
+        )
+        .unwrap();
+
+        let schema = parse_config_schema(temp.path()).unwrap();
+        assert_eq!(
+            schema.headers,
+            vec!["src/config.h", "include/audio_config.h"]
+        );
+
+        let rate = schema
+            .defines
+            .iter()
+            .find(|d| d.name == "AUDIO_SAMPLE_RATE")
+            .unwrap();
+        assert_eq!(rate.header, "include/audio_config.h");
+        assert_eq!(rate.description.as_deref(), Some("Samples per second"));
+
+        let width = schema
+            .defines
+            .iter()
+            .find(|d| d.name == "PANEL_WIDTH")
+            .unwrap();
+        assert_eq!(width.header, "src/config.h");
+    }
+
+    #[test]
+    fn test_manifest_headers_and_overrides() {
+        let temp = tempdir().unwrap();
+        let app_dir = temp.path().join("apps").join("dj-booth");
+        let shared_dir = temp.path().join("shared/lib/AudioInput/src");
+        fs::create_dir_all(app_dir.join("src")).unwrap();
+        fs::create_dir_all(&shared_dir).unwrap();
+        fs::write(temp.path().join("README.md"), "").unwrap();
+        fs::write(
+            app_dir.join("src/config.h"),
+            r#"
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 26
+#endif
+
+#ifndef CONFIG_ESP_MATTER_ENABLE_DATA_MODEL
+#define CONFIG_ESP_MATTER_ENABLE_DATA_MODEL 1
+#endif
+"#,
+        )
+        .unwrap();
+        fs::write(
+            shared_dir.join("audio_input_config.h"),
+            "#ifndef AUDIO_GAIN\n#define AUDIO_GAIN 1.0\n#endif\n",
+        )
+        .unwrap();
+        fs::write(
+            app_dir.join("dashboard.toml"),
+            r#"
+headers = ["../../shared/lib/AudioInput/src/audio_input_config.h"]
+hidden = ["CONFIG_ESP_MATTER_ENABLE_DATA_MODEL"]
+
+[[groups]]
+name = "Panel"
+defines = ["PANEL_WIDTH"]
+
+[fields.PANEL_WIDTH]
+min = 1
+max = 64
+step = 1
+unit = "px"
+"#,
+        )
+        .unwrap();
+
+        let schema = parse_config_schema(&app_dir).unwrap();
+        let names: Vec<&str> = schema.defines.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["PANEL_WIDTH", "AUDIO_GAIN"]);
+        assert_eq!(schema.groups[0].name, "Panel");
+
+        let width = &schema.defines[0];
+        assert_eq!(width.group.as_deref(), Some("Panel"));
+        assert_eq!(
+            (width.min, width.max, width.step),
+            (Some(1.0), Some(64.0), Some(1.0))
+        );
+        assert_eq!(width.unit.as_deref(), Some("px"));
+
+        let gain = &schema.defines[1];
+        assert_eq!(gain.value_type, ValueType::Float);
+        assert!(gain
+            .header
+            .ends_with("shared/lib/AudioInput/src/audio_input_config.h"));
+    }
+
+    #[test]
+    fn test_manifest_header_outside_monorepo_rejected() {
+        let temp = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let app_dir = temp.path().join("apps").join("demo");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::create_dir_all(temp.path().join("shared")).unwrap();
+        fs::write(temp.path().join("README.md"), "").unwrap();
+        let outside_header = outside.path().join("outside.h");
+        fs::write(&outside_header, "").unwrap();
+        fs::write(
+            app_dir.join("dashboard.json"),
+            serde_json::json!({ "headers": [outside_header] }).to_string(),
+        )
+        .unwrap();
+
+        let err = parse_config_schema(&app_dir).unwrap_err();
+        assert!(err.contains("escapes the monorepo"), "{}", err);
+    }
+
+    #[test]
+    fn test_comment_blocks_and_sections() {
+        let content = r#"
+// =============================================================================
+// LED Panel Configuration
+// =============================================================================
+// Values can be overridden via build flags.
+
+// -----------------------------------------------------------------------------
+// Panel Dimensions
+// -----------------------------------------------------------------------------
+
+// Panel width in LEDs (horizontal direction)
+// The number of LEDs in each row of your panel
+#ifndef PANEL_WIDTH
+#define PANEL_WIDTH 36
+#endif
+
+// -----------------------------------------------------------------------------
+// Pin Assignments (Platform-Specific)
+// -----------------------------------------------------------------------------
+
+#ifdef ESP8266
+    // GPIO pin for main control button/switch
+    #ifndef PIN_SWITCH
+    #define PIN_SWITCH 14       // D5
+    #endif
+#endif
+
+#ifndef TARGET_FPS
+#define TARGET_FPS 30
+#endif
+"#;
+
+        let (defines, platform_conditional) = extract_config_defines(content, &[]).unwrap();
+        let width = defines.iter().find(|d| d.name == "PANEL_WIDTH").unwrap();
+        assert_eq!(
+            width.description.as_deref(),
+            Some("Panel width in LEDs (horizontal direction)\nThe number of LEDs in each row of your panel")
+        );
+        assert_eq!(width.section.as_deref(), Some("Panel Dimensions"));
+
+        let switch = &platform_conditional.get("ESP8266").unwrap()[0];
+        assert_eq!(
+            switch.description.as_deref(),
+            Some("GPIO pin for main control button/switch\nD5")
+        );
+        assert_eq!(
+            switch.section.as_deref(),
+            Some("Pin Assignments (Platform-Specific)")
+        );
+
+        let fps = defines.iter().find(|d| d.name == "TARGET_FPS").unwrap();
+        assert_eq!(fps.description, None);
+        assert_eq!(
+            fps.section.as_deref(),
+            Some("Pin Assignments (Platform-Specific)")
+        );
+    }
+
+    #[test]
+    fn test_enum_list_in_comment_block() {
+        let content = r#"
+// -----------------------------------------------------------------------------
+// Wiring Pattern
+// -----------------------------------------------------------------------------
+// How the LED strip is physically wired on your panel:
+//   0 = Serpentine Horizontal (default) - Rows alternate direction (zigzag)
+//       First row left-to-right, second row right-to-left, etc.
+//   1 = Progressive Horizontal - All rows run the same direction
+//   2 = Serpentine Vertical - Columns alternate direction
+#ifndef WIRING_PATTERN
+#define WIRING_PATTERN 0
+#endif
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        let wiring = &defines[0];
+        assert_eq!(wiring.value_type, ValueType::Enum);
+        assert_eq!(wiring.section.as_deref(), Some("Wiring Pattern"));
+
+        let enums = wiring.enum_values.as_ref().unwrap();
+        let labels: Vec<(&str, &str)> = enums
+            .iter()
+            .map(|e| (e.value.as_str(), e.label.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("0", "Serpentine Horizontal (default)"),
+                ("1", "Progressive Horizontal"),
+                ("2", "Serpentine Vertical"),
+            ]
+        );
+        assert!(wiring
+            .description
+            .as_deref()
+            .unwrap()
+            .starts_with("How the LED strip is physically wired on your panel:\n0 = Serpentine"));
+    }
+
+    #[test]
+    fn test_unclosed_rule_is_not_a_section() {
+        let content = r#"
+// ----------
+// Not a heading
+
+// Brightness cap
+#ifndef MAX_BRIGHTNESS
+#define MAX_BRIGHTNESS 200
+#endif
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        assert_eq!(defines[0].section, None);
+        assert_eq!(defines[0].description.as_deref(), Some("Brightness cap"));
+    }
+    #[test]
+    fn test_single_line_section_heading() {
+        let content = r#"
+// ---- Audio Input ----
+
+// I2S sample rate in Hz
+#ifndef AUDIO_SAMPLE_RATE
+#define AUDIO_SAMPLE_RATE 44100
+#endif
+
+// ---------- Effects ----------
+#ifndef EFFECT_SPEED
+#define EFFECT_SPEED 5
+#endif
+"#;
+
+        let (defines, _) = extract_config_defines(content, &[]).unwrap();
+        assert_eq!(defines[0].section.as_deref(), Some("Audio Input"));
+        assert_eq!(
+            defines[0].description.as_deref(),
+            Some("I2S sample rate in Hz")
+        );
+        assert_eq!(defines[1].section.as_deref(), Some("Effects"));
+        assert_eq!(defines[1].description, None);
+    }
+
+    #[test]
+    fn test_active_source_lines() {
+        let content = r#"
+#include "config.h"
+#if AUDIO_ENABLED
+AudioInput s_audioInput; // mic
+#endif
+#if STRIP_TYPE == STRIP_TYPE_SK6812
+LEDStrip<StripType::SK6812_RGBW> strip(PIN_LED_DATA, NUM_PIXELS);
+#else
+LEDStrip<StripType::WS2815B_RGB> strip(PIN_LED_DATA, NUM_PIXELS);
+#endif
+"#;
+        let macros: HashMap<String, String> = [
+            ("AUDIO_ENABLED", "0"),
+            ("STRIP_TYPE_SK6812", "0"),
+            ("STRIP_TYPE", "STRIP_TYPE_SK6812"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let lines = active_source_lines(content, &macros);
+        assert_eq!(
+            lines,
+            vec!["LEDStrip<StripType::SK6812_RGBW> strip(PIN_LED_DATA, NUM_PIXELS);"]
+        );
+    }
+
+    #[test]
+    fn test_validate_define_types() {
+        let content = r#"
+#ifndef NUM_ROWS
+#define NUM_ROWS 8
+#endif
+
+#ifndef AUDIO_GAIN
+#define AUDIO_GAIN 1.5
+#endif
+
+#ifndef USE_AUDIO
+#define USE_AUDIO 1
+#endif
+
+#ifndef PIN_LED_DATA
+#define PIN_LED_DATA 16
+#endif
+
+#ifndef STRIP_TYPE
+#define STRIP_TYPE 0 // 0 = SK6812 RGBW (default), 1 = WS2815B RGB
+#endif
+"#;
+        let (defines, platform_conditional) = extract_config_defines(content, &[]).unwrap();
+        let schema = AppConfigSchema {
+            has_config: true,
+            defines,
+            platform_conditional,
+            ..Default::default()
+        };
+
+        let check = |name: &str, value: &str| {
+            let values = HashMap::from([(name.to_string(), value.to_string())]);
+            validate_define_types(&schema, &values)
+                .into_iter()
+                .map(|v| v.message)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(check("NUM_ROWS", "0x10").is_empty());
+        assert_eq!(
+            check("NUM_ROWS", "eight"),
+            vec!["NUM_ROWS must be an integer"]
+        );
+        assert!(check("AUDIO_GAIN", "2.0f").is_empty());
+        assert_eq!(
+            check("AUDIO_GAIN", "loud"),
+            vec!["AUDIO_GAIN must be a number"]
+        );
+        assert_eq!(
+            check("USE_AUDIO", "yes"),
+            vec!["USE_AUDIO must be 0, 1, true or false"]
+        );
+        assert!(check("PIN_LED_DATA", "A3").is_empty());
+        assert_eq!(
+            check("PIN_LED_DATA", "D4"),
+            vec!["PIN_LED_DATA must be a pin number"]
+        );
+        assert!(check("STRIP_TYPE", "1").is_empty());
+        assert_eq!(
+            check("STRIP_TYPE", "7"),
+            vec!["STRIP_TYPE must be one of 0, 1"]
+        );
+        assert!(check("UNKNOWN", "anything").is_empty());
+    }
 }
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Optional per-app `dashboard.toml` / `dashboard.json` that tunes how the config schema
+/// is built and displayed.
+///
+/// ```toml
+/// headers = ["../../shared/lib/AudioInput/src/audio_input.h"]
+/// hidden = ["CONFIG_ESP_MATTER_ENABLE_DATA_MODEL"]
+///
+/// [[groups]]
+/// name = "Panel"
+/// defines = ["PANEL_WIDTH", "PANEL_HEIGHT"]
+///
+/// [fields.PANEL_WIDTH]
+/// min = 1
+/// max = 128
+/// unit = "px"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DashboardManifest {
+    /// Extra headers to scan after `src/config.h`, relative to the app directory.
+    pub headers: Vec<String>,
+    /// Defines that should not be shown or edited in the dashboard.
+    pub hidden: Vec<String>,
+    /// Display groups in the order the UI should render them.
+    pub groups: Vec<DisplayGroup>,
+    /// Per-define display and range overrides.
+    pub fields: HashMap<String, FieldOverride>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayGroup {
+    pub name: String,
+    pub defines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FieldOverride {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+    pub unit: Option<String>,
+    pub description: Option<String>,
+}
+
+const TOML_MANIFEST: &str = "dashboard.toml";
+const JSON_MANIFEST: &str = "dashboard.json";
+
+/// Loads the app's dashboard manifest, if it has one.
+pub fn load_manifest(app_path: &Path) -> Result<Option<DashboardManifest>, String> {
+    let toml_path = app_path.join(TOML_MANIFEST);
+    let json_path = app_path.join(JSON_MANIFEST);
+
+    match (toml_path.exists(), json_path.exists()) {
+        (true, true) => Err(format!(
+            "Both {} and {} exist; keep only one",
+            TOML_MANIFEST, JSON_MANIFEST
+        )),
+        (true, false) => {
+            let content = fs::read_to_string(&toml_path)
+                .map_err(|e| format!("Failed to read {}: {}", TOML_MANIFEST, e))?;
+            toml::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("Failed to parse {}: {}", TOML_MANIFEST, e))
+        }
+        (false, true) => {
+            let content = fs::read_to_string(&json_path)
+                .map_err(|e| format!("Failed to read {}: {}", JSON_MANIFEST, e))?;
+            serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("Failed to parse {}: {}", JSON_MANIFEST, e))
+        }
+        (false, false) => Ok(None),
+    }
+}
+
+impl DashboardManifest {
+    /// Name of the first group that lists the define.
+    pub fn group_for(&self, name: &str) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|group| group.defines.iter().any(|d| d == name))
+            .map(|group| group.name.as_str())
+    }
+
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.hidden.iter().any(|hidden| hidden == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_no_manifest() {
+        let temp = tempdir().unwrap();
+        assert_eq!(load_manifest(temp.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_toml_manifest() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("dashboard.toml"),
+            r#"
+hidden = ["CONFIG_ESP_MATTER_ENABLE_DATA_MODEL"]
+
+[[groups]]
+name = "Panel"
+defines = ["PANEL_WIDTH", "PANEL_HEIGHT"]
+
+[fields.PANEL_WIDTH]
+min = 1
+max = 128
+step = 1
+unit = "px"
+"#,
+        )
+        .unwrap();
+
+        let manifest = load_manifest(temp.path()).unwrap().unwrap();
+        assert!(manifest.headers.is_empty());
+        assert!(manifest.is_hidden("CONFIG_ESP_MATTER_ENABLE_DATA_MODEL"));
+        assert_eq!(manifest.group_for("PANEL_HEIGHT"), Some("Panel"));
+        assert_eq!(manifest.group_for("STRIP_TYPE"), None);
+
+        let width = &manifest.fields["PANEL_WIDTH"];
+        assert_eq!(width.min, Some(1.0));
+        assert_eq!(width.max, Some(128.0));
+        assert_eq!(width.unit.as_deref(), Some("px"));
+    }
+
+    #[test]
+    fn test_json_manifest() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("dashboard.json"),
+            r#"{ "headers": ["src/effects_config.h"], "fields": { "TARGET_FPS": { "unit": "fps" } } }"#,
+        )
+        .unwrap();
+
+        let manifest = load_manifest(temp.path()).unwrap().unwrap();
+        assert_eq!(manifest.headers, vec!["src/effects_config.h"]);
+        assert_eq!(manifest.fields["TARGET_FPS"].unit.as_deref(), Some("fps"));
+    }
+
+    #[test]
+    fn test_both_manifests_rejected() {
+        let temp = tempdir().unwrap();
+        fs::write(temp.path().join("dashboard.toml"), "").unwrap();
+        fs::write(temp.path().join("dashboard.json"), "{}").unwrap();
+        assert!(load_manifest(temp.path()).is_err());
+    }
+}
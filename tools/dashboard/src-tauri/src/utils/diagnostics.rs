@@ -0,0 +1,365 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Diagnostics kept for the completion summary; later ones are only counted.
+const MAX_SUMMARY_DIAGNOSTICS: usize = 200;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticSource {
+    Compiler,
+    Linker,
+    PlatformIO,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    pub source: DiagnosticSource,
+    /// Relative to the monorepo root when the file lives inside it (e.g.
+    /// `apps/dj-booth/src/main.cpp`, `shared/lib/Audio/Audio.cpp`), otherwise as printed.
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosticSummary {
+    pub errors: usize,
+    pub warnings: usize,
+    /// Unique diagnostics in the order they were printed, capped at 200.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Recognizes diagnostics in a job's output. Headers included from several translation
+/// units repeat their warnings, so each diagnostic is only reported once.
+pub struct DiagnosticCollector {
+    monorepo_path: PathBuf,
+    app_path: PathBuf,
+    seen: HashSet<Diagnostic>,
+    summary: DiagnosticSummary,
+}
+
+impl DiagnosticCollector {
+    pub fn new(monorepo_path: &Path, app_path: &Path) -> Self {
+        Self {
+            monorepo_path: normalize(monorepo_path),
+            app_path: app_path.to_path_buf(),
+            seen: HashSet::new(),
+            summary: DiagnosticSummary::default(),
+        }
+    }
+
+    /// Parses one output line, returning the diagnostic if it hasn't been seen before.
+    pub fn push_line(&mut self, line: &str) -> Option<Diagnostic> {
+        let mut diagnostic = parse_line(line)?;
+        diagnostic.file = diagnostic.file.map(|file| self.resolve_path(&file));
+        if !self.seen.insert(diagnostic.clone()) {
+            return None;
+        }
+
+        match diagnostic.severity {
+            DiagnosticSeverity::Error => self.summary.errors += 1,
+            DiagnosticSeverity::Warning => self.summary.warnings += 1,
+            DiagnosticSeverity::Note => {}
+        }
+        if self.summary.diagnostics.len() < MAX_SUMMARY_DIAGNOSTICS {
+            self.summary.diagnostics.push(diagnostic.clone());
+        }
+        Some(diagnostic)
+    }
+
+    pub fn summary(&self) -> DiagnosticSummary {
+        self.summary.clone()
+    }
+
+    /// PlatformIO compiles from the app directory, so relative paths are resolved against it.
+    fn resolve_path(&self, file: &str) -> String {
+        let path = Path::new(file);
+        let absolute = if let Some(source) = self.object_source(file) {
+            source
+        } else if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.app_path.join(path)
+        };
+        match normalize(&absolute).strip_prefix(&self.monorepo_path) {
+            Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+            Err(_) => file.to_string(),
+        }
+    }
+
+    /// Maps object files named in linker errors back to their sources, from
+    /// `.pio/build/<env>/src/main.cpp.o` to `src/main.cpp` and from library objects such as
+    /// `.pio/build/<env>/lib<hash>/libFoo.a(foo.cpp.o)` to `shared/lib/Foo/src/foo.cpp`.
+    fn object_source(&self, file: &str) -> Option<PathBuf> {
+        // `lib<hash>/libFoo.a(foo.cpp.o)`, or `lib<hash>/Foo/foo.cpp.o` with `lib_archive = no`
+        static LIBRARY_OBJECT_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^lib[0-9a-f]+/(?:lib(?P<archive>[^/]+)\.a\((?P<member>[^/()]+)\.o\)|(?P<library>[^/]+)/(?P<object>.+)\.o)$",
+            )
+            .expect("library object regex")
+        });
+
+        let rest = file.strip_prefix(".pio/build/")?;
+        let (_env, object) = rest.split_once('/')?;
+        if let Some(source) = object.strip_suffix(".o").filter(|o| o.starts_with("src/")) {
+            return Some(self.app_path.join(source));
+        }
+
+        let caps = LIBRARY_OBJECT_RE.captures(object)?;
+        let (library, source) = match (caps.name("archive"), caps.name("member")) {
+            (Some(archive), Some(member)) => (archive.as_str(), member.as_str()),
+            _ => (&caps["library"], &caps["object"]),
+        };
+        Some(self.library_dir(library).join("src").join(source))
+    }
+
+    /// App-local libraries shadow the shared ones, as in PlatformIO's search order.
+    fn library_dir(&self, name: &str) -> PathBuf {
+        let local = self.app_path.join("lib").join(name);
+        if local.is_dir() {
+            local
+        } else {
+            self.monorepo_path.join("shared/lib").join(name)
+        }
+    }
+}
+
+/// Resolves `.` and `..` without touching the filesystem; the file may be in a
+/// temporary build directory that is already gone.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn parse_line(line: &str) -> Option<Diagnostic> {
+    let line = line.trim_end();
+    parse_compiler(line)
+        .or_else(|| parse_linker(line))
+        .or_else(|| parse_platformio(line))
+}
+
+fn parse_compiler(line: &str) -> Option<Diagnostic> {
+    static COMPILER_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"^(?P<file>(?:[A-Za-z]:)?[^:]+):(?P<line>\d+):(?:(?P<col>\d+):)?\s+(?P<sev>fatal error|error|warning|note):\s+(?P<msg>.+)$",
+        )
+        .expect("compiler diagnostic regex")
+    });
+
+    let caps = COMPILER_RE.captures(line)?;
+    let severity = match &caps["sev"] {
+        "warning" => DiagnosticSeverity::Warning,
+        "note" => DiagnosticSeverity::Note,
+        _ => DiagnosticSeverity::Error,
+    };
+    Some(Diagnostic {
+        severity,
+        source: DiagnosticSource::Compiler,
+        file: Some(caps["file"].trim().to_string()),
+        line: caps["line"].parse().ok(),
+        column: caps.name("col").and_then(|col| col.as_str().parse().ok()),
+        message: caps["msg"].trim().to_string(),
+    })
+}
+
+fn parse_linker(line: &str) -> Option<Diagnostic> {
+    // `<ld>: <object or source>:(<section>+0x0): undefined reference to `foo'`
+    static SYMBOL_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"^(?:.*?\bld(?:\.exe)?: )?(?P<file>(?:[A-Za-z]:)?[^:]+?)(?::(?P<line>\d+)|:\([^)]*\))?: (?P<msg>(?:undefined reference to|multiple definition of) .+)$",
+        )
+        .expect("linker symbol regex")
+    });
+    // `<ld>: region `iram0_0_seg' overflowed by 123 bytes`
+    static OVERFLOW_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^(?:.*?\bld(?:\.exe)?: )?(?P<msg>.*(?:will not fit in region|overflowed by \d+ bytes).*)$")
+            .expect("linker overflow regex")
+    });
+
+    if let Some(caps) = SYMBOL_RE.captures(line) {
+        return Some(Diagnostic {
+            severity: DiagnosticSeverity::Error,
+            source: DiagnosticSource::Linker,
+            file: Some(caps["file"].trim().to_string()),
+            line: caps
+                .name("line")
+                .and_then(|line| line.as_str().parse().ok()),
+            column: None,
+            message: caps["msg"].to_string(),
+        });
+    }
+    let caps = OVERFLOW_RE.captures(line)?;
+    Some(Diagnostic {
+        severity: DiagnosticSeverity::Error,
+        source: DiagnosticSource::Linker,
+        file: None,
+        line: None,
+        column: None,
+        message: caps["msg"].to_string(),
+    })
+}
+
+fn parse_platformio(line: &str) -> Option<Diagnostic> {
+    // `Error: Unknown environment names 'x'` or `UnknownPackageError: Could not find ...`
+    static PIO_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^(?:Error: (?P<msg>.+)|(?P<exception>[A-Z]\w*(?:Error|Exception): .+))$")
+            .expect("platformio error regex")
+    });
+
+    let caps = PIO_RE.captures(line)?;
+    let message = caps
+        .name("msg")
+        .or_else(|| caps.name("exception"))?
+        .as_str()
+        .trim()
+        .to_string();
+    Some(Diagnostic {
+        severity: DiagnosticSeverity::Error,
+        source: DiagnosticSource::PlatformIO,
+        file: None,
+        line: None,
+        column: None,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collector() -> DiagnosticCollector {
+        DiagnosticCollector::new(Path::new("/repo"), Path::new("/repo/apps/dj-booth"))
+    }
+
+    #[test]
+    fn test_compiler_diagnostics_resolve_paths() {
+        let mut collector = collector();
+
+        let error = collector
+            .push_line("src/main.cpp:42:7: error: 'strip' was not declared in this scope")
+            .unwrap();
+        assert_eq!(error.severity, DiagnosticSeverity::Error);
+        assert_eq!(error.file.as_deref(), Some("apps/dj-booth/src/main.cpp"));
+        assert_eq!((error.line, error.column), (Some(42), Some(7)));
+        assert_eq!(error.message, "'strip' was not declared in this scope");
+
+        let warning = collector
+            .push_line("/repo/apps/dj-booth/../../shared/lib/Audio/Audio.cpp:10:3: warning: unused variable 'x' [-Wunused-variable]")
+            .unwrap();
+        assert_eq!(warning.severity, DiagnosticSeverity::Warning);
+        assert_eq!(warning.file.as_deref(), Some("shared/lib/Audio/Audio.cpp"));
+
+        let fatal = collector
+            .push_line("src/main.cpp:3:10: fatal error: FastLED.h: No such file or directory")
+            .unwrap();
+        assert_eq!(fatal.severity, DiagnosticSeverity::Error);
+
+        // Outside the monorepo, the printed path is kept
+        let toolchain = collector
+            .push_line("/home/me/.platformio/packages/framework/cores/esp32/Arduino.h:5:1: note: declared here")
+            .unwrap();
+        assert_eq!(toolchain.severity, DiagnosticSeverity::Note);
+        assert_eq!(
+            toolchain.file.as_deref(),
+            Some("/home/me/.platformio/packages/framework/cores/esp32/Arduino.h")
+        );
+
+        assert!(collector
+            .push_line("In file included from src/main.cpp:3:")
+            .is_none());
+        assert!(collector
+            .push_line("Compiling .pio/build/esp32/src/main.cpp.o")
+            .is_none());
+    }
+
+    #[test]
+    fn test_linker_and_platformio_errors() {
+        let mut collector = collector();
+
+        let undefined = collector
+            .push_line("/home/me/.platformio/packages/toolchain/bin/xtensa-esp32-elf-ld: .pio/build/esp32/src/main.cpp.o:(.literal._Z5setupv+0x8): undefined reference to `initAudio()'")
+            .unwrap();
+        assert_eq!(undefined.source, DiagnosticSource::Linker);
+        assert_eq!(
+            undefined.file.as_deref(),
+            Some("apps/dj-booth/src/main.cpp")
+        );
+        assert_eq!(undefined.message, "undefined reference to `initAudio()'");
+
+        let archived = collector
+            .push_line("/opt/bin/xtensa-esp32-elf-ld: .pio/build/esp32/lib5a7/libAudioInput.a(AudioInput.cpp.o):(.literal._ZN10AudioInput5beginEv+0x4): undefined reference to `i2s_driver_install'")
+            .unwrap();
+        assert_eq!(
+            archived.file.as_deref(),
+            Some("shared/lib/AudioInput/src/AudioInput.cpp")
+        );
+        let unarchived = collector
+            .push_line(
+                ".pio/build/esp32/lib5a7/LEDStrip/LEDStrip.cpp.o: multiple definition of `strip'",
+            )
+            .unwrap();
+        assert_eq!(
+            unarchived.file.as_deref(),
+            Some("shared/lib/LEDStrip/src/LEDStrip.cpp")
+        );
+
+        let overflow = collector
+            .push_line(
+                "/opt/bin/xtensa-esp32-elf-ld: region `iram0_0_seg' overflowed by 1234 bytes",
+            )
+            .unwrap();
+        assert_eq!(overflow.source, DiagnosticSource::Linker);
+        assert_eq!(
+            overflow.message,
+            "region `iram0_0_seg' overflowed by 1234 bytes"
+        );
+
+        let unknown_env = collector
+            .push_line("Error: Unknown environment names 'esp33'. Valid names are 'esp32, native'")
+            .unwrap();
+        assert_eq!(unknown_env.source, DiagnosticSource::PlatformIO);
+        assert!(unknown_env.message.starts_with("Unknown environment names"));
+
+        let package = collector
+            .push_line("UnknownPackageError: Could not find the package with 'toolchain-xtensa' requirements")
+            .unwrap();
+        assert!(package.message.starts_with("UnknownPackageError:"));
+    }
+
+    #[test]
+    fn test_summary_counts_unique_diagnostics() {
+        let mut collector = collector();
+        let line = "include/config.h:12:9: warning: \"LED_PIN\" redefined";
+        assert!(collector.push_line(line).is_some());
+        assert!(collector.push_line(line).is_none());
+        collector.push_line("src/main.cpp:1:1: error: expected ';'");
+        collector.push_line("src/main.cpp:1:1: note: in expansion of macro");
+
+        let summary = collector.summary();
+        assert_eq!((summary.errors, summary.warnings), (1, 1));
+        assert_eq!(summary.diagnostics.len(), 3);
+    }
+}
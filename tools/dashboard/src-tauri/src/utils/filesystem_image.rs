@@ -0,0 +1,337 @@
+use crate::utils::pio_parser::DiscoveredEnvironment;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Share of the filesystem partition that `data/` may use before warning. LittleFS and
+/// SPIFFS need free blocks for metadata and wear levelling.
+const PARTITION_WARN_PERCENT: f64 = 90.0;
+
+/// Size and content hash of one file in `data/`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Hex SHA-256 of the content.
+    pub hash: String,
+}
+
+/// `data/` as it was when the image was last uploaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedData {
+    /// Unix timestamp in seconds.
+    pub uploaded_at: u64,
+    pub files: BTreeMap<String, FileStamp>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DataChange {
+    /// Relative to `data/`, with `/` separators.
+    pub path: String,
+    pub kind: DataChangeKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesystemReport {
+    /// `littlefs`, `spiffs` or `fatfs`.
+    pub filesystem: String,
+    pub file_count: usize,
+    /// Total size of the files in `data/`.
+    pub data_bytes: u64,
+    /// Size of the built image, once `buildfs` has produced it.
+    pub image_bytes: Option<u64>,
+    /// Partition the image is flashed to, from `board_build.partitions`.
+    pub partition: Option<String>,
+    pub partition_size: Option<u64>,
+    pub last_uploaded_at: Option<u64>,
+    /// Differences from the last upload; every file is `added` if there was none.
+    pub changes: Vec<DataChange>,
+}
+
+/// Filesystem `buildfs` formats the image as. PlatformIO defaults to SPIFFS.
+pub fn filesystem_name(env: &DiscoveredEnvironment) -> String {
+    match env.board_build.filesystem.as_deref() {
+        Some("fat") | Some("ffat") => "fatfs".to_string(),
+        Some(fs) => fs.to_string(),
+        None => "spiffs".to_string(),
+    }
+}
+
+/// Where `buildfs` writes the image, e.g. `.pio/build/esp32/littlefs.bin`.
+pub fn image_path(app_path: &Path, env: &DiscoveredEnvironment) -> PathBuf {
+    app_path
+        .join(".pio")
+        .join("build")
+        .join(&env.name)
+        .join(format!("{}.bin", filesystem_name(env)))
+}
+
+/// Stamps every file under `data_dir`, keyed by its path relative to it.
+pub fn scan_data_dir(data_dir: &Path) -> Result<BTreeMap<String, FileStamp>, String> {
+    let mut files = BTreeMap::new();
+    if data_dir.is_dir() {
+        scan_into(data_dir, data_dir, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn scan_into(
+    root: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<String, FileStamp>,
+) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            scan_into(root, &path, files)?;
+            continue;
+        }
+        let content =
+            fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        files.insert(
+            relative,
+            FileStamp {
+                size: content.len() as u64,
+                hash: format!("{:x}", Sha256::digest(&content)),
+            },
+        );
+    }
+    Ok(())
+}
+
+pub fn diff_data(
+    previous: &BTreeMap<String, FileStamp>,
+    current: &BTreeMap<String, FileStamp>,
+) -> Vec<DataChange> {
+    let mut changes: Vec<DataChange> = current
+        .iter()
+        .filter_map(|(path, stamp)| {
+            let kind = match previous.get(path) {
+                None => DataChangeKind::Added,
+                Some(before) if before != stamp => DataChangeKind::Changed,
+                Some(_) => return None,
+            };
+            Some(DataChange {
+                path: path.clone(),
+                kind,
+            })
+        })
+        .collect();
+    changes.extend(
+        previous
+            .keys()
+            .filter(|path| !current.contains_key(*path))
+            .map(|path| DataChange {
+                path: path.clone(),
+                kind: DataChangeKind::Removed,
+            }),
+    );
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+fn uploaded_path(state_dir: &Path, app_name: &str, environment: &str) -> PathBuf {
+    state_dir
+        .join(app_name.replace(['/', '\\'], "_"))
+        .join(format!("{}.json", environment))
+}
+
+pub fn load_uploaded(
+    state_dir: &Path,
+    app_name: &str,
+    environment: &str,
+) -> Result<Option<UploadedData>, String> {
+    match fs::read_to_string(uploaded_path(state_dir, app_name, environment)) {
+        Ok(content) => serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse filesystem upload state: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read filesystem upload state: {}", e)),
+    }
+}
+
+/// Remembers the `data/` contents that were just flashed.
+pub fn record_upload(
+    state_dir: &Path,
+    app_name: &str,
+    environment: &str,
+    uploaded: &UploadedData,
+) -> Result<(), String> {
+    let path = uploaded_path(state_dir, app_name, environment);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create filesystem state directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(uploaded)
+        .map_err(|e| format!("Failed to serialize filesystem upload state: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write filesystem upload state: {}", e))
+}
+
+/// Describes `data/` and the built image against the environment's filesystem partition
+/// and the last upload.
+pub fn filesystem_report(
+    app_path: &Path,
+    env: &DiscoveredEnvironment,
+    files: &BTreeMap<String, FileStamp>,
+    uploaded: Option<&UploadedData>,
+) -> FilesystemReport {
+    let partition = env.partitions.as_ref().and_then(|layout| {
+        let name = layout.filesystem_partition.as_ref()?;
+        layout.partitions.iter().find(|p| &p.name == name)
+    });
+    let image_bytes = fs::metadata(image_path(app_path, env))
+        .ok()
+        .map(|meta| meta.len());
+
+    FilesystemReport {
+        filesystem: filesystem_name(env),
+        file_count: files.len(),
+        data_bytes: files.values().map(|stamp| stamp.size).sum(),
+        image_bytes,
+        partition: partition.map(|p| p.name.clone()),
+        partition_size: partition.map(|p| p.size),
+        last_uploaded_at: uploaded.map(|u| u.uploaded_at),
+        changes: diff_data(
+            &uploaded.map(|u| u.files.clone()).unwrap_or_default(),
+            files,
+        ),
+    }
+}
+
+/// Why the image can't be flashed: it is larger than its partition.
+pub fn image_overflow(report: &FilesystemReport) -> Option<String> {
+    let (image, partition) = (report.image_bytes?, report.partition_size?);
+    (image > partition).then(|| {
+        format!(
+            "{} image ({} bytes) is larger than the '{}' partition ({} bytes)",
+            report.filesystem,
+            image,
+            report.partition.as_deref().unwrap_or("filesystem"),
+            partition
+        )
+    })
+}
+
+/// Warns when `data/` nearly fills the filesystem partition. Without a known partition
+/// there is nothing to compare against and no warning.
+pub fn size_warnings(report: &FilesystemReport) -> Vec<String> {
+    let Some(partition_size) = report.partition_size.filter(|&size| size > 0) else {
+        return Vec::new();
+    };
+    let percent = report.data_bytes as f64 * 100.0 / partition_size as f64;
+    if percent < PARTITION_WARN_PERCENT {
+        return Vec::new();
+    }
+    vec![format!(
+        "data/ holds {} bytes, {:.1}% of the {} byte '{}' partition",
+        report.data_bytes,
+        percent,
+        partition_size,
+        report.partition.as_deref().unwrap_or("filesystem")
+    )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pio_parser;
+    use tempfile::tempdir;
+
+    fn dj_booth(app: &Path) -> DiscoveredEnvironment {
+        fs::write(
+            app.join("platformio.ini"),
+            "[env:esp32]\nplatform = espressif32\nboard = esp32dev\nboard_build.partitions = partitions.csv\nboard_build.filesystem = littlefs\n",
+        )
+        .unwrap();
+        fs::write(
+            app.join("partitions.csv"),
+            "nvs, data, nvs, 0x9000, 0x5000\nfactory, app, factory, 0x10000, 0x300000\nstorage, data, spiffs, 0x310000, 0x1000\n",
+        )
+        .unwrap();
+        pio_parser::parse_platformio_ini(&app.join("platformio.ini"))
+            .unwrap()
+            .environments
+            .remove(0)
+    }
+
+    #[test]
+    fn test_detects_data_changes_since_upload() {
+        let temp = tempdir().unwrap();
+        let data = temp.path().join("data");
+        fs::create_dir_all(data.join("css")).unwrap();
+        fs::write(data.join("index.html"), "<h1>DJ Booth</h1>").unwrap();
+        fs::write(data.join("css/site.css"), "body {}").unwrap();
+
+        let env = dj_booth(temp.path());
+        let files = scan_data_dir(&data).unwrap();
+        assert_eq!(
+            files["css/site.css"].hash,
+            "62368a1a29259b30bac235c0e75dc700c9b3bacf1513ad5708e4fe4a6c0d6560"
+        );
+        let report = filesystem_report(temp.path(), &env, &files, None);
+        assert_eq!(report.filesystem, "littlefs");
+        assert_eq!(report.partition.as_deref(), Some("storage"));
+        assert_eq!(report.changes.len(), 2);
+
+        let state = temp.path().join("state");
+        let uploaded = UploadedData {
+            uploaded_at: 1,
+            files,
+        };
+        record_upload(&state, "dj-booth", "esp32", &uploaded).unwrap();
+        let uploaded = load_uploaded(&state, "dj-booth", "esp32").unwrap().unwrap();
+
+        fs::write(data.join("index.html"), "<h1>DJ Booth v2</h1>").unwrap();
+        fs::remove_file(data.join("css/site.css")).unwrap();
+        fs::write(data.join("app.js"), "init()").unwrap();
+        let files = scan_data_dir(&data).unwrap();
+        let report = filesystem_report(temp.path(), &env, &files, Some(&uploaded));
+        let changes: Vec<(&str, &DataChangeKind)> = report
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), &c.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("app.js", &DataChangeKind::Added),
+                ("css/site.css", &DataChangeKind::Removed),
+                ("index.html", &DataChangeKind::Changed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_checked_against_partition() {
+        let temp = tempdir().unwrap();
+        let data = temp.path().join("data");
+        fs::create_dir_all(&data).unwrap();
+        fs::write(data.join("index.html"), vec![b'x'; 4000]).unwrap();
+
+        let env = dj_booth(temp.path());
+        let image = image_path(temp.path(), &env);
+        fs::create_dir_all(image.parent().unwrap()).unwrap();
+        fs::write(&image, vec![0u8; 0x2000]).unwrap();
+
+        let files = scan_data_dir(&data).unwrap();
+        let report = filesystem_report(temp.path(), &env, &files, None);
+        assert_eq!(report.image_bytes, Some(0x2000));
+        assert!(image_overflow(&report).unwrap().contains("'storage'"));
+        assert_eq!(size_warnings(&report).len(), 1);
+    }
+}
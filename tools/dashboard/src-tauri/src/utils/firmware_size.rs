@@ -0,0 +1,610 @@
+use crate::utils::partitions::PartitionLayout;
+use crate::utils::pin_database;
+use crate::utils::pio_parser::DiscoveredEnvironment;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Reports kept per app/environment.
+pub const MAX_SIZE_REPORTS: usize = 50;
+/// Flash usage, as a share of the app partition, that triggers a warning.
+const PARTITION_WARN_PERCENT: f64 = 90.0;
+const TOP_SYMBOLS: usize = 20;
+const TOP_OBJECTS: usize = 20;
+/// Linker map written into the build directory by `linker_map_flag`.
+const LINKER_MAP: &str = "firmware.map";
+/// Output sections that are not loaded onto the chip.
+const NON_ALLOC_SECTIONS: &[&str] = &[
+    ".debug",
+    ".comment",
+    ".note",
+    ".stab",
+    ".xtensa.info",
+    ".xt.",
+    ".riscv.attributes",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct MemoryUsage {
+    pub used: u64,
+    pub total: u64,
+    pub percent: f64,
+}
+
+/// The `RAM:` and `Flash:` lines PlatformIO prints after linking.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReportedUsage {
+    pub ram: Option<MemoryUsage>,
+    pub flash: Option<MemoryUsage>,
+}
+
+impl ReportedUsage {
+    pub fn push_line(&mut self, line: &str) {
+        // `RAM:   [==        ]  15.2% (used 49848 bytes from 327680 bytes)`
+        static USAGE_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^(RAM|Flash):\s*\[[^\]]*\]\s*([\d.]+)%\s*\(used (\d+) bytes from (\d+) bytes\)",
+            )
+            .expect("memory usage regex")
+        });
+
+        let Some(caps) = USAGE_RE.captures(line.trim()) else {
+            return;
+        };
+        let (Ok(percent), Ok(used), Ok(total)) =
+            (caps[2].parse(), caps[3].parse(), caps[4].parse())
+        else {
+            return;
+        };
+        let usage = Some(MemoryUsage {
+            used,
+            total,
+            percent,
+        });
+        match &caps[1] {
+            "RAM" => self.ram = usage,
+            _ => self.flash = usage,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SectionSize {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SymbolSize {
+    pub name: String,
+    pub size: u64,
+    /// `nm` symbol type, e.g. `T` for code or `B` for zero-initialized data.
+    pub kind: String,
+}
+
+/// Bytes an object file contributes to the firmware, from the linker map.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObjectSize {
+    /// `src/main.cpp.o` for app sources, `libFoo.a(foo.cpp.o)` for library members.
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SectionDelta {
+    pub name: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+/// Change against the previous successful build of the same app/environment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SizeDelta {
+    pub previous_recorded_at: u64,
+    pub ram_bytes: Option<i64>,
+    pub flash_bytes: Option<i64>,
+    /// Sections whose size changed, largest change first.
+    pub sections: Vec<SectionDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FirmwareSizeReport {
+    pub app_name: String,
+    pub environment: String,
+    /// Unix timestamp in seconds.
+    pub recorded_at: u64,
+    pub ram: Option<MemoryUsage>,
+    pub flash: Option<MemoryUsage>,
+    /// Non-empty ELF sections, largest first. Empty when the toolchain's `size` isn't found.
+    pub sections: Vec<SectionSize>,
+    pub top_symbols: Vec<SymbolSize>,
+    /// Largest object files in loaded sections. Empty when the link wrote no linker map.
+    #[serde(default)]
+    pub top_objects: Vec<ObjectSize>,
+    /// Smallest factory/OTA slot in the `board_build.partitions` table, if one is configured.
+    pub app_partition_size: Option<u64>,
+    pub delta: Option<SizeDelta>,
+}
+
+/// Builds the size report for a finished build from PlatformIO's usage lines, the linker
+/// map and, for ESP targets, the toolchain's `size` and `nm` run on
+/// `.pio/build/<env>/firmware.elf`. The tools run synchronously.
+pub fn build_report(
+    app_path: &Path,
+    app_name: &str,
+    env: &DiscoveredEnvironment,
+    usage: ReportedUsage,
+    recorded_at: u64,
+) -> FirmwareSizeReport {
+    let build_dir = app_path.join(".pio").join("build").join(&env.name);
+    let elf = build_dir.join("firmware.elf");
+    let tools = pin_database::environment_profile(&env.platform, env.board.as_deref())
+        .and_then(|profile| toolchain_prefix(&profile.chip_id))
+        .and_then(|prefix| Some((prefix, platformio_core_dir()?)));
+
+    let (sections, top_symbols) = match tools {
+        Some((prefix, core_dir)) if elf.exists() => {
+            let sections = find_toolchain_tool(&core_dir, prefix, "size")
+                .and_then(|size| run_tool(&size, &["-A"], &elf))
+                .map(|output| parse_size_sections(&output))
+                .unwrap_or_default();
+            let symbols = find_toolchain_tool(&core_dir, prefix, "nm")
+                .and_then(|nm| run_tool(&nm, &["-S", "-C", "--size-sort", "-r"], &elf))
+                .map(|output| parse_nm_symbols(&output, TOP_SYMBOLS))
+                .unwrap_or_default();
+            (sections, symbols)
+        }
+        _ => (Vec::new(), Vec::new()),
+    };
+    let top_objects = fs::read_to_string(build_dir.join(LINKER_MAP))
+        .map(|map| parse_map_objects(&map, &env.name, TOP_OBJECTS))
+        .unwrap_or_default();
+
+    FirmwareSizeReport {
+        app_name: app_name.to_string(),
+        environment: env.name.clone(),
+        recorded_at,
+        ram: usage.ram,
+        flash: usage.flash,
+        sections,
+        top_symbols,
+        top_objects,
+        app_partition_size: env
+            .partitions
+            .as_ref()
+            .and_then(PartitionLayout::app_size_limit),
+        delta: None,
+    }
+}
+
+/// GCC target prefix of the toolchain PlatformIO installs for an ESP chip.
+fn toolchain_prefix(chip_id: &str) -> Option<&'static str> {
+    match chip_id {
+        "esp32" => Some("xtensa-esp32-elf"),
+        "esp32s2" => Some("xtensa-esp32s2-elf"),
+        "esp32s3" => Some("xtensa-esp32s3-elf"),
+        "esp32c3" | "esp32c6" | "esp32h2" => Some("riscv32-esp-elf"),
+        "esp8266" => Some("xtensa-lx106-elf"),
+        _ => None,
+    }
+}
+
+fn platformio_core_dir() -> Option<PathBuf> {
+    match std::env::var_os("PLATFORMIO_CORE_DIR") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => dirs::home_dir().map(|home| home.join(".platformio")),
+    }
+}
+
+/// Looks for `<prefix>-<tool>` in any installed `toolchain-*` package.
+fn find_toolchain_tool(core_dir: &Path, prefix: &str, tool: &str) -> Option<PathBuf> {
+    let binary = format!("{}-{}{}", prefix, tool, std::env::consts::EXE_SUFFIX);
+    let mut packages: Vec<PathBuf> = fs::read_dir(core_dir.join("packages"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("toolchain-"))
+        })
+        .collect();
+    packages.sort();
+    packages
+        .into_iter()
+        .map(|package| package.join("bin").join(&binary))
+        .find(|path| path.is_file())
+}
+
+fn run_tool(tool: &Path, args: &[&str], elf: &Path) -> Option<String> {
+    let output = Command::new(tool).args(args).arg(elf).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses `size -A` output into non-empty sections, largest first.
+fn parse_size_sections(output: &str) -> Vec<SectionSize> {
+    let mut sections: Vec<SectionSize> = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next().filter(|name| name.starts_with('.'))?;
+            let size = fields.next()?.parse().ok()?;
+            Some(SectionSize {
+                name: name.to_string(),
+                size,
+            })
+        })
+        .filter(|section| section.size > 0)
+        .collect();
+    sections.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    sections
+}
+
+/// Parses `nm -S -C --size-sort -r` output (`<addr> <size> <type> <name>`).
+fn parse_nm_symbols(output: &str, limit: usize) -> Vec<SymbolSize> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ' ');
+            let _address = fields.next()?;
+            let size = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let kind = fields.next()?;
+            let name = fields.next()?.trim();
+            Some(SymbolSize {
+                name: name.to_string(),
+                size,
+                kind: kind.to_string(),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Linker flag that makes the build write the map `build_report` reads per-object sizes
+/// from. PlatformIO doesn't write one by default; SCons expands `$BUILD_DIR` at link time.
+pub fn linker_map_flag() -> String {
+    format!("-Wl,-Map,$BUILD_DIR/{}", LINKER_MAP)
+}
+
+/// Sums the input sections of a GNU ld map (`-Wl,-Map`) per object file, largest first.
+/// Only the memory map is read, so discarded sections are not counted.
+fn parse_map_objects(map: &str, environment: &str, limit: usize) -> Vec<ObjectSize> {
+    // ` .text.setup  0x400d0f28  0x2c .pio/build/esp32/src/main.cpp.o`, where a long
+    // section name puts the address, size and object on the following line
+    static INPUT_SECTION_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^\s+(?:\S+\s+)?0x[0-9a-fA-F]+\s+0x([0-9a-fA-F]+)\s+(\S.*)$")
+            .expect("linker map input section regex")
+    });
+
+    let build_prefix = format!(".pio/build/{}/", environment);
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    let mut loaded = false;
+    for line in map
+        .lines()
+        .skip_while(|line| !line.starts_with("Linker script and memory map"))
+    {
+        // Output sections start at column 0
+        if line.starts_with('.') {
+            loaded = !NON_ALLOC_SECTIONS
+                .iter()
+                .any(|prefix| line.starts_with(prefix));
+            continue;
+        }
+        let Some(caps) = INPUT_SECTION_RE.captures(line).filter(|_| loaded) else {
+            continue;
+        };
+        let Ok(size) = u64::from_str_radix(&caps[1], 16) else {
+            continue;
+        };
+        if size == 0 {
+            continue;
+        }
+        // Toolchain and framework archives are named by file, e.g. `libfreertos.a(tasks.c.obj)`
+        let object = caps[2].trim();
+        let name = match object.strip_prefix(&build_prefix) {
+            Some(relative) => relative.to_string(),
+            None => match Path::new(object).file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => object.to_string(),
+            },
+        };
+        *sizes.entry(name).or_default() += size;
+    }
+
+    let mut objects: Vec<ObjectSize> = sizes
+        .into_iter()
+        .map(|(name, size)| ObjectSize { name, size })
+        .collect();
+    objects.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    objects.truncate(limit);
+    objects
+}
+
+/// Warnings for flash usage close to the app partition (or PlatformIO's flash limit).
+pub fn size_warnings(report: &FirmwareSizeReport) -> Vec<String> {
+    let Some(flash) = report.flash else {
+        return Vec::new();
+    };
+    let (limit, what) = match report.app_partition_size {
+        Some(size) => (size, "app partition"),
+        None => (flash.total, "available flash"),
+    };
+    if limit == 0 {
+        return Vec::new();
+    }
+
+    let percent = flash.used as f64 * 100.0 / limit as f64;
+    if percent < PARTITION_WARN_PERCENT {
+        return Vec::new();
+    }
+    vec![format!(
+        "Firmware uses {:.1}% of the {} ({} of {} bytes, {} bytes free)",
+        percent,
+        what,
+        flash.used,
+        limit,
+        limit.saturating_sub(flash.used)
+    )]
+}
+
+fn reports_path(sizes_dir: &Path, app_name: &str, environment: &str) -> PathBuf {
+    sizes_dir
+        .join(app_name.replace(['/', '\\'], "_"))
+        .join(format!("{}.json", environment))
+}
+
+/// Reports for an app/environment, oldest first.
+pub fn load_reports(
+    sizes_dir: &Path,
+    app_name: &str,
+    environment: &str,
+) -> Result<Vec<FirmwareSizeReport>, String> {
+    let path = reports_path(sizes_dir, app_name, environment);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse size history: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read size history: {}", e)),
+    }
+}
+
+/// Fills in the delta against the previous report and appends the report to the history.
+pub fn record_report(
+    sizes_dir: &Path,
+    mut report: FirmwareSizeReport,
+) -> Result<FirmwareSizeReport, String> {
+    let mut reports = load_reports(sizes_dir, &report.app_name, &report.environment)?;
+    report.delta = reports.last().map(|previous| size_delta(previous, &report));
+    reports.push(report.clone());
+    let excess = reports.len().saturating_sub(MAX_SIZE_REPORTS);
+    reports.drain(..excess);
+
+    let path = reports_path(sizes_dir, &report.app_name, &report.environment);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create size history directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&reports)
+        .map_err(|e| format!("Failed to serialize size history: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write size history: {}", e))?;
+    Ok(report)
+}
+
+fn size_delta(previous: &FirmwareSizeReport, current: &FirmwareSizeReport) -> SizeDelta {
+    let used_delta = |before: Option<MemoryUsage>, after: Option<MemoryUsage>| {
+        Some(after?.used as i64 - before?.used as i64)
+    };
+    let size_of = |sections: &[SectionSize], name: &str| {
+        sections
+            .iter()
+            .find(|s| s.name == name)
+            .map_or(0, |s| s.size)
+    };
+
+    let mut names: Vec<&str> = previous
+        .sections
+        .iter()
+        .chain(&current.sections)
+        .map(|s| s.name.as_str())
+        .collect();
+    names.sort();
+    names.dedup();
+    let mut sections: Vec<SectionDelta> = names
+        .into_iter()
+        .map(|name| SectionDelta {
+            name: name.to_string(),
+            before: size_of(&previous.sections, name),
+            after: size_of(&current.sections, name),
+        })
+        .filter(|delta| delta.before != delta.after)
+        .collect();
+    sections.sort_by_key(|delta| std::cmp::Reverse(delta.after.abs_diff(delta.before)));
+
+    SizeDelta {
+        previous_recorded_at: previous.recorded_at,
+        ram_bytes: used_delta(previous.ram, current.ram),
+        flash_bytes: used_delta(previous.flash, current.flash),
+        sections,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn report(flash_used: u64, sections: &[(&str, u64)]) -> FirmwareSizeReport {
+        FirmwareSizeReport {
+            app_name: "dj-booth".to_string(),
+            environment: "esp32".to_string(),
+            recorded_at: flash_used,
+            ram: None,
+            flash: Some(MemoryUsage {
+                used: flash_used,
+                total: 3_145_728,
+                percent: 0.0,
+            }),
+            sections: sections
+                .iter()
+                .map(|(name, size)| SectionSize {
+                    name: name.to_string(),
+                    size: *size,
+                })
+                .collect(),
+            top_symbols: Vec::new(),
+            top_objects: Vec::new(),
+            app_partition_size: Some(0x300000),
+            delta: None,
+        }
+    }
+
+    #[test]
+    fn test_parses_platformio_and_toolchain_output() {
+        let mut usage = ReportedUsage::default();
+        usage.push_line("RAM:   [==        ]  15.2% (used 49848 bytes from 327680 bytes)");
+        usage.push_line("Flash: [=====     ]  45.1% (used 1418541 bytes from 3145728 bytes)");
+        usage.push_line("Building .pio/build/esp32/firmware.bin");
+        assert_eq!(usage.ram.unwrap().used, 49848);
+        assert_eq!(usage.flash.unwrap().total, 3145728);
+
+        let size = "\
+.pio/build/esp32/firmware.elf  :
+section                 size         addr
+.iram0.vectors          1027   1074266112
+.dram0.bss             21960   1073470296
+.rtc.text                  0   1074528256
+.flash.text           923410   1074593824
+Total                1203456
+";
+        let sections = parse_size_sections(size);
+        let names: Vec<&str> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, [".flash.text", ".dram0.bss", ".iram0.vectors"]);
+
+        let nm = "\
+3ffc1f30 00002000 B s_ledBuffer
+400d2a10 00000468 T AudioInput::process(short const*, unsigned int)
+";
+        let symbols = parse_nm_symbols(nm, 1);
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].size, 0x2000);
+        assert_eq!(symbols[0].kind, "B");
+    }
+
+    #[test]
+    fn test_parses_linker_map_objects() {
+        let map = "\
+Discarded input sections
+
+ .text.unused   0x00000000       0x40 .pio/build/esp32/src/main.cpp.o
+
+Linker script and memory map
+
+LOAD .pio/build/esp32/src/main.cpp.o
+
+.flash.text     0x400d0020    0x1a000
+ *(.literal .text .literal.* .text.*)
+ .text.setup    0x400d0020       0x2c .pio/build/esp32/src/main.cpp.o
+                0x400d0020                setup()
+ .text._ZN10AudioInput5beginEv
+                0x400d004c      0x11c .pio/build/esp32/lib5a7/libAudioInput.a(AudioInput.cpp.o)
+ *fill*         0x400d0168        0x8
+ .text.loop     0x400d0170       0x30 .pio/build/esp32/src/main.cpp.o
+ .text.xTaskCreate
+                0x400d01a0       0x90 /home/me/.platformio/packages/framework-arduinoespressif32/tools/sdk/esp32/lib/libfreertos.a(tasks.c.obj)
+
+.dram0.bss      0x3ffc1f30     0x2000
+ .bss.s_ledBuffer
+                0x3ffc1f30     0x2000 .pio/build/esp32/src/main.cpp.o
+
+.debug_info     0x00000000    0x2ff1a
+ .debug_info    0x00000000    0x2ff1a .pio/build/esp32/src/main.cpp.o
+";
+        let objects = parse_map_objects(map, "esp32", 2);
+        assert_eq!(
+            objects,
+            vec![
+                ObjectSize {
+                    name: "src/main.cpp.o".to_string(),
+                    size: 0x2c + 0x30 + 0x2000,
+                },
+                ObjectSize {
+                    name: "lib5a7/libAudioInput.a(AudioInput.cpp.o)".to_string(),
+                    size: 0x11c,
+                },
+            ]
+        );
+        let all = parse_map_objects(map, "esp32", TOP_OBJECTS);
+        assert_eq!(all[2].name, "libfreertos.a(tasks.c.obj)");
+    }
+
+    #[test]
+    fn test_build_report_reads_map_named_by_linker_flag() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("platformio.ini"),
+            "[env:esp32]\nplatform = espressif32\nboard = esp32dev\n",
+        )
+        .unwrap();
+        let config =
+            crate::utils::pio_parser::parse_platformio_ini(&temp.path().join("platformio.ini"))
+                .unwrap();
+        let env = &config.environments[0];
+
+        let build_dir = temp.path().join(".pio").join("build").join("esp32");
+        let report = build_report(temp.path(), "app", env, ReportedUsage::default(), 0);
+        assert!(report.top_objects.is_empty());
+
+        let flag = linker_map_flag();
+        let map_name = flag.strip_prefix("-Wl,-Map,$BUILD_DIR/").unwrap();
+        fs::create_dir_all(&build_dir).unwrap();
+        fs::write(
+            build_dir.join(map_name),
+            "Linker script and memory map\n\n\
+             .flash.text     0x400d0020       0x2c\n \
+             .text.setup    0x400d0020       0x2c .pio/build/esp32/src/main.cpp.o\n",
+        )
+        .unwrap();
+        let report = build_report(temp.path(), "app", env, ReportedUsage::default(), 0);
+        assert_eq!(report.top_objects[0].name, "src/main.cpp.o");
+    }
+
+    #[test]
+    fn test_size_warnings_near_partition_limit() {
+        assert!(size_warnings(&report(1_500_000, &[])).is_empty());
+        let warnings = size_warnings(&report(3_000_000, &[]));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("app partition"));
+    }
+
+    #[test]
+    fn test_record_report_computes_delta() {
+        let temp = tempdir().unwrap();
+        let first = record_report(temp.path(), report(1000, &[(".flash.text", 800)])).unwrap();
+        assert!(first.delta.is_none());
+
+        let second = record_report(
+            temp.path(),
+            report(1100, &[(".flash.text", 900), (".dram0.bss", 50)]),
+        )
+        .unwrap();
+        let delta = second.delta.unwrap();
+        assert_eq!(delta.flash_bytes, Some(100));
+        assert_eq!(delta.ram_bytes, None);
+        assert_eq!(delta.sections[0].name, ".flash.text");
+        assert_eq!(delta.sections[1].before, 0);
+
+        assert_eq!(
+            load_reports(temp.path(), "dj-booth", "esp32")
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,517 @@
+use std::fs;
+use std::path::Path;
+
+/// Format-preserving model of an INI file such as platformio.ini.
+///
+/// The document keeps every original line, so comments, blank lines, key order and
+/// indentation survive a load/edit/save cycle. Edits only touch the lines of the option
+/// or section being changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IniDocument {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+/// Line range covered by one option: the `key = value` line plus its continuation lines.
+#[derive(Debug, Clone, Copy)]
+struct OptionSpan {
+    start: usize,
+    end: usize,
+}
+
+/// Indentation used for new continuation lines when the file offers no example.
+const DEFAULT_INDENT: &str = "    ";
+
+impl IniDocument {
+    pub fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(|l| l.to_string()).collect(),
+            trailing_newline: text.ends_with('\n') || text.is_empty(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(Self::parse(&text))
+    }
+
+    /// Writes the document via a temporary file so a failed write never truncates the ini.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let tmp_path = path.with_extension("ini.tmp");
+        fs::write(&tmp_path, self.render())
+            .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, path)
+            .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = self.lines.join("\n");
+        if self.trailing_newline && !self.lines.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Section names in file order.
+    pub fn sections(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .filter_map(|line| section_name(line))
+            .collect()
+    }
+
+    pub fn has_section(&self, section: &str) -> bool {
+        self.section_header(section).is_some()
+    }
+
+    /// Returns an option's value with continuation lines joined by `\n` and comments removed.
+    pub fn get(&self, section: &str, key: &str) -> Option<String> {
+        let span = self.find_option(section, key)?;
+        let first = option_value(&self.lines[span.start]).unwrap_or_default();
+        let mut parts: Vec<String> = Vec::new();
+        if !first.is_empty() {
+            parts.push(first);
+        }
+        parts.extend(self.continuation_items(span));
+        Some(parts.join("\n"))
+    }
+
+    /// Returns a multi-value option as one item per line.
+    pub fn get_list(&self, section: &str, key: &str) -> Vec<String> {
+        self.get(section, key)
+            .map(|v| v.lines().map(|l| l.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Sets a single-line option, replacing any existing value (and its continuation lines)
+    /// or appending it to the end of the section.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) -> Result<(), String> {
+        self.replace_option(section, key, vec![format!("{} = {}", key, value)])
+    }
+
+    /// Sets a multi-value option written one item per indented line, the way the apps'
+    /// `build_flags` and `lib_deps` are laid out.
+    pub fn set_list(&mut self, section: &str, key: &str, items: &[String]) -> Result<(), String> {
+        let indent = self.list_indent(section, key);
+        let mut new_lines = vec![format!("{} =", key)];
+        new_lines.extend(items.iter().map(|item| format!("{}{}", indent, item)));
+        self.replace_option(section, key, new_lines)
+    }
+
+    /// Appends an item to a multi-value option, keeping existing lines and their comments.
+    /// Returns false when the item is already present.
+    pub fn add_list_item(&mut self, section: &str, key: &str, item: &str) -> Result<bool, String> {
+        let item = item.trim();
+        if self
+            .get_list(section, key)
+            .iter()
+            .any(|existing| existing == item)
+        {
+            return Ok(false);
+        }
+
+        match self.find_option(section, key) {
+            Some(span) => {
+                let indent = self.list_indent(section, key);
+                let first = option_value(&self.lines[span.start]).unwrap_or_default();
+                if span.end == span.start + 1 && !first.is_empty() {
+                    // Single-line value: move it onto its own line before appending
+                    let key_line = format!("{} =", key);
+                    let existing = format!(
+                        "{}{}",
+                        indent,
+                        self.lines[span.start]
+                            .split_once(['=', ':'])
+                            .map(|(_, v)| v.trim())
+                            .unwrap_or_default()
+                    );
+                    self.lines
+                        .splice(span.start..span.end, [key_line, existing]);
+                    self.lines
+                        .insert(span.start + 2, format!("{}{}", indent, item));
+                } else {
+                    self.lines.insert(span.end, format!("{}{}", indent, item));
+                }
+                Ok(true)
+            }
+            None => {
+                self.set_list(section, key, &[item.to_string()])?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Removes an item from a multi-value option. Returns false when the item was not found.
+    pub fn remove_list_item(
+        &mut self,
+        section: &str,
+        key: &str,
+        item: &str,
+    ) -> Result<bool, String> {
+        let item = item.trim();
+        let span = match self.find_option(section, key) {
+            Some(span) => span,
+            None => return Ok(false),
+        };
+
+        for index in span.start + 1..span.end {
+            if strip_inline_comment(self.lines[index].trim()) == item {
+                self.lines.remove(index);
+                return Ok(true);
+            }
+        }
+
+        if option_value(&self.lines[span.start]).as_deref() == Some(item) {
+            self.lines[span.start] = format!("{} =", key);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn remove_option(&mut self, section: &str, key: &str) -> bool {
+        match self.find_option(section, key) {
+            Some(span) => {
+                self.lines.drain(span.start..span.end);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Appends a new section after the last one, separated by a blank line.
+    pub fn add_section(&mut self, section: &str) -> Result<(), String> {
+        if self.has_section(section) {
+            return Err(format!("Section [{}] already exists", section));
+        }
+
+        while self.lines.last().is_some_and(|l| l.trim().is_empty()) {
+            self.lines.pop();
+        }
+        if !self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.lines.push(format!("[{}]", section));
+        self.trailing_newline = true;
+        Ok(())
+    }
+
+    /// Removes a section, its options and the comment block directly above its header.
+    pub fn remove_section(&mut self, section: &str) -> bool {
+        let header = match self.section_header(section) {
+            Some(header) => header,
+            None => return false,
+        };
+
+        let mut start = header;
+        while start > 0 && is_comment(&self.lines[start - 1]) {
+            start -= 1;
+        }
+        // Drop the blank separator that preceded the section as well
+        if start > 0 && self.lines[start - 1].trim().is_empty() {
+            start -= 1;
+        }
+
+        let end = self.section_body_end(header);
+        self.lines.drain(start..end);
+        true
+    }
+
+    fn section_header(&self, section: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|line| section_name(line).as_deref() == Some(section))
+    }
+
+    /// Index one past the section's last line, excluding trailing blank lines and the
+    /// comment block attached to the next section header.
+    fn section_body_end(&self, header: usize) -> usize {
+        let next_header = self.lines[header + 1..]
+            .iter()
+            .position(|line| section_name(line).is_some())
+            .map(|offset| header + 1 + offset)
+            .unwrap_or(self.lines.len());
+
+        let mut end = next_header;
+        if next_header < self.lines.len() {
+            while end > header + 1 && is_comment(&self.lines[end - 1]) {
+                end -= 1;
+            }
+        }
+        while end > header + 1 && self.lines[end - 1].trim().is_empty() {
+            end -= 1;
+        }
+        end
+    }
+
+    fn find_option(&self, section: &str, key: &str) -> Option<OptionSpan> {
+        let header = self.section_header(section)?;
+        let body_end = self.section_body_end(header);
+
+        let start =
+            (header + 1..body_end).find(|&i| option_key(&self.lines[i]).as_deref() == Some(key))?;
+        let mut end = start + 1;
+        while end < body_end && is_continuation(&self.lines[end]) {
+            end += 1;
+        }
+        Some(OptionSpan { start, end })
+    }
+
+    fn continuation_items(&self, span: OptionSpan) -> Vec<String> {
+        self.lines[span.start + 1..span.end]
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.starts_with(';') && !line.starts_with('#'))
+            .map(|line| strip_inline_comment(line).to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    fn replace_option(
+        &mut self,
+        section: &str,
+        key: &str,
+        new_lines: Vec<String>,
+    ) -> Result<(), String> {
+        if let Some(span) = self.find_option(section, key) {
+            self.lines.splice(span.start..span.end, new_lines);
+            return Ok(());
+        }
+
+        let header = self
+            .section_header(section)
+            .ok_or_else(|| format!("Section [{}] not found", section))?;
+        let insert_at = self.section_body_end(header);
+        self.lines.splice(insert_at..insert_at, new_lines);
+        Ok(())
+    }
+
+    /// Reuses the indentation of an existing continuation line, preferring the option
+    /// being edited, then anything else in the file.
+    fn list_indent(&self, section: &str, key: &str) -> String {
+        let from_option = self.find_option(section, key).and_then(|span| {
+            self.lines[span.start + 1..span.end]
+                .iter()
+                .find(|line| is_continuation(line))
+                .map(|line| leading_whitespace(line))
+        });
+
+        from_option
+            .or_else(|| {
+                self.lines
+                    .iter()
+                    .find(|line| is_continuation(line) && !is_comment(line))
+                    .map(|line| leading_whitespace(line))
+            })
+            .unwrap_or_else(|| DEFAULT_INDENT.to_string())
+    }
+}
+
+fn section_name(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    if line.starts_with(char::is_whitespace) || !trimmed.starts_with('[') {
+        return None;
+    }
+    let end = trimmed.find(']')?;
+    Some(trimmed[1..end].trim().to_string())
+}
+
+fn is_comment(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with(';') || trimmed.starts_with('#')
+}
+
+fn is_continuation(line: &str) -> bool {
+    line.starts_with(char::is_whitespace) && !line.trim().is_empty()
+}
+
+fn option_key(line: &str) -> Option<String> {
+    if line.starts_with(char::is_whitespace) || is_comment(line) || section_name(line).is_some() {
+        return None;
+    }
+    let (key, _) = line.split_once(['=', ':'])?;
+    let key = key.trim();
+    if key.is_empty() {
+        None
+    } else {
+        Some(key.to_string())
+    }
+}
+
+fn option_value(line: &str) -> Option<String> {
+    let (_, value) = line.split_once(['=', ':'])?;
+    Some(strip_inline_comment(value.trim()).to_string())
+}
+
+/// Strips a `;` or `#` comment that is preceded by whitespace, as PlatformIO does.
+fn strip_inline_comment(value: &str) -> &str {
+    let bytes = value.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        if (b == b';' || b == b'#') && i > 0 && bytes[i - 1].is_ascii_whitespace() {
+            return value[..i].trim_end();
+        }
+    }
+    value
+}
+
+fn leading_whitespace(line: &str) -> String {
+    line.chars().take_while(|c| c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const LED_PANEL_INI: &str = r#"; LED Panel app — 24x36 (864 pixel) RGBW panel
+;
+; Override defaults via build flags:
+;   -D PANEL_WIDTH=48 -D STRIP_TYPE=1 -D WIRING_PATTERN=2
+
+[env]
+lib_extra_dirs = ../../shared/lib
+monitor_speed = 115200
+
+[env:esp32]
+platform = espressif32
+board = esp32dev
+framework = arduino
+build_flags =
+    -std=gnu++17
+    -DCORE_DEBUG_LEVEL=3
+lib_deps =
+    makuna/NeoPixelBus@^2.8.0
+
+; Host-side unit tests
+[env:native]
+platform = native
+build_flags =
+    -std=c++17
+    -DNATIVE_BUILD
+"#;
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let doc = IniDocument::parse(LED_PANEL_INI);
+        assert_eq!(doc.render(), LED_PANEL_INI);
+        assert_eq!(doc.sections(), vec!["env", "env:esp32", "env:native"]);
+    }
+
+    #[test]
+    fn test_get_values() {
+        let doc = IniDocument::parse(LED_PANEL_INI);
+        assert_eq!(doc.get("env", "monitor_speed"), Some("115200".to_string()));
+        assert_eq!(
+            doc.get_list("env:esp32", "build_flags"),
+            vec!["-std=gnu++17", "-DCORE_DEBUG_LEVEL=3"]
+        );
+        assert_eq!(doc.get("env:esp32", "missing"), None);
+    }
+
+    #[test]
+    fn test_add_and_remove_list_items() {
+        let mut doc = IniDocument::parse(LED_PANEL_INI);
+        assert!(doc
+            .add_list_item("env:esp32", "build_flags", "-DPANEL_WIDTH=48")
+            .unwrap());
+        assert!(!doc
+            .add_list_item("env:esp32", "build_flags", "-DPANEL_WIDTH=48")
+            .unwrap());
+        assert!(doc
+            .remove_list_item("env:esp32", "lib_deps", "makuna/NeoPixelBus@^2.8.0")
+            .unwrap());
+
+        let rendered = doc.render();
+        assert!(rendered.contains("    -DCORE_DEBUG_LEVEL=3\n    -DPANEL_WIDTH=48\nlib_deps =\n"));
+        assert!(rendered.starts_with("; LED Panel app"));
+        assert!(rendered.contains("; Host-side unit tests\n[env:native]"));
+        assert!(doc.get_list("env:esp32", "lib_deps").is_empty());
+    }
+
+    #[test]
+    fn test_add_list_item_expands_single_line_value() {
+        let mut doc = IniDocument::parse("[env:native]\nlib_ignore = LEDStrip\n");
+        doc.add_list_item("env:native", "lib_ignore", "AudioInput")
+            .unwrap();
+        assert_eq!(
+            doc.render(),
+            "[env:native]\nlib_ignore =\n    LEDStrip\n    AudioInput\n"
+        );
+    }
+
+    #[test]
+    fn test_add_section_with_options() {
+        let mut doc = IniDocument::parse(LED_PANEL_INI);
+        doc.add_section("env:booth").unwrap();
+        doc.set("env:booth", "extends", "env:esp32").unwrap();
+        doc.set_list(
+            "env:booth",
+            "build_flags",
+            &[
+                "${env:esp32.build_flags}".to_string(),
+                "-DPANEL_WIDTH=26".to_string(),
+            ],
+        )
+        .unwrap();
+
+        let rendered = doc.render();
+        assert!(rendered.starts_with(LED_PANEL_INI));
+        assert!(rendered.ends_with(
+            "    -DNATIVE_BUILD\n\n[env:booth]\nextends = env:esp32\nbuild_flags =\n    ${env:esp32.build_flags}\n    -DPANEL_WIDTH=26\n"
+        ));
+        assert!(doc.add_section("env:booth").is_err());
+    }
+
+    #[test]
+    fn test_set_replaces_value_in_place() {
+        let mut doc = IniDocument::parse(LED_PANEL_INI);
+        doc.set("env", "monitor_speed", "921600").unwrap();
+        doc.set_list("env:esp32", "lib_deps", &["fastled/FastLED".to_string()])
+            .unwrap();
+
+        let rendered = doc.render();
+        assert!(rendered.contains("lib_extra_dirs = ../../shared/lib\nmonitor_speed = 921600\n"));
+        assert!(rendered.contains("lib_deps =\n    fastled/FastLED\n\n; Host-side"));
+    }
+
+    #[test]
+    fn test_remove_section_takes_attached_comment() {
+        let mut doc = IniDocument::parse(LED_PANEL_INI);
+        assert!(doc.remove_section("env:native"));
+        assert!(!doc.remove_section("env:native"));
+
+        let rendered = doc.render();
+        assert!(!rendered.contains("Host-side"));
+        assert!(rendered.ends_with("lib_deps =\n    makuna/NeoPixelBus@^2.8.0\n"));
+
+        assert!(doc.remove_section("env:esp32"));
+        assert!(doc.render().ends_with("monitor_speed = 115200\n"));
+    }
+
+    #[test]
+    fn test_inline_comments_ignored_in_values() {
+        let doc = IniDocument::parse(
+            "[env:esp32]\nboard = esp32dev ; devkit\nbuild_flags =\n    -DA=1 ; first\n    ; disabled\n    -DB=2\n",
+        );
+        assert_eq!(doc.get("env:esp32", "board"), Some("esp32dev".to_string()));
+        assert_eq!(
+            doc.get_list("env:esp32", "build_flags"),
+            vec!["-DA=1", "-DB=2"]
+        );
+    }
+
+    #[test]
+    fn test_save_writes_file() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("platformio.ini");
+        fs::write(&path, LED_PANEL_INI).unwrap();
+
+        let mut doc = IniDocument::load(&path).unwrap();
+        doc.set("env:native", "test_build_src", "true").unwrap();
+        doc.save(&path).unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.ends_with("    -DNATIVE_BUILD\ntest_build_src = true\n"));
+        assert!(!temp.path().join("platformio.ini.tmp").exists());
+    }
+}
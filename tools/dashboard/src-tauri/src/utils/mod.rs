@@ -1,7 +1,20 @@
+pub mod build_history;
 pub mod config_schema;
+pub mod dashboard_manifest;
+pub mod diagnostics;
+pub mod filesystem_image;
+pub mod firmware_size;
+pub mod ini_document;
 pub mod monorepo;
+pub mod partitions;
 pub mod path_security;
+pub mod pin_database;
 pub mod pin_validator;
 pub mod pio_parser;
 pub mod pio_path;
+pub mod power;
+pub mod preflight;
 pub mod profile_paths;
+pub mod resource_planner;
+pub mod secrets;
+pub mod test_report;
@@ -1,8 +1,14 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Discovers the monorepo path by looking for repo markers.
 pub fn find_monorepo_root() -> Result<PathBuf, String> {
-    let mut current = std::env::current_dir().map_err(|e| e.to_string())?;
+    let current = std::env::current_dir().map_err(|e| e.to_string())?;
+    find_monorepo_root_from(&current)
+}
+
+/// Discovers the monorepo containing `start` by looking for repo markers.
+pub fn find_monorepo_root_from(start: &Path) -> Result<PathBuf, String> {
+    let mut current = start.to_path_buf();
 
     loop {
         if is_monorepo_root(&current) {
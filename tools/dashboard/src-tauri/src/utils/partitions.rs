@@ -0,0 +1,466 @@
+use crate::utils::pin_database;
+use crate::utils::pin_validator::Severity;
+use crate::utils::pio_parser::DiscoveredEnvironment;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// The bootloader and partition table occupy flash below this offset.
+const FIRST_PARTITION_OFFSET: u64 = 0x9000;
+const APP_ALIGNMENT: u64 = 0x10000;
+const DATA_ALIGNMENT: u64 = 0x1000;
+const MAX_NAME_LEN: usize = 16;
+
+const APP_SUBTYPES: &[&str] = &["factory", "test"];
+const DATA_SUBTYPES: &[&str] = &[
+    "ota",
+    "phy",
+    "nvs",
+    "coredump",
+    "nvs_keys",
+    "efuse",
+    "undefined",
+    "esphttpd",
+    "fat",
+    "spiffs",
+    "littlefs",
+];
+/// Data subtypes PlatformIO can write a `data/` filesystem image to.
+const FILESYSTEM_SUBTYPES: &[&str] = &["spiffs", "littlefs", "fat"];
+
+/// One row of an ESP-IDF partition CSV, with the offset filled in when the CSV left it blank.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Partition {
+    pub name: String,
+    /// `app`, `data` or a custom numeric type.
+    pub kind: String,
+    pub subtype: String,
+    pub offset: u64,
+    pub size: u64,
+    pub flags: Vec<String>,
+    /// 1-based line in the CSV.
+    pub line: usize,
+}
+
+impl Partition {
+    /// First byte past the partition. `parse_partition_csv` rejects rows where this overflows.
+    pub fn end(&self) -> u64 {
+        self.offset.saturating_add(self.size)
+    }
+
+    fn is_app(&self) -> bool {
+        self.kind == "app"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PartitionIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The partition table an environment selects with `board_build.partitions`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PartitionLayout {
+    /// As written in `board_build.partitions`.
+    pub file: String,
+    pub partitions: Vec<Partition>,
+    /// From `board_upload.flash_size` or the board database.
+    pub flash_size: Option<u64>,
+    /// Partition the `data/` filesystem image is written to.
+    pub filesystem_partition: Option<String>,
+    pub issues: Vec<PartitionIssue>,
+}
+
+impl PartitionLayout {
+    /// Largest firmware that fits every app slot, i.e. the smallest factory/OTA partition.
+    pub fn app_size_limit(&self) -> Option<u64> {
+        self.partitions
+            .iter()
+            .filter(|p| p.is_app() && p.subtype != "test")
+            .map(|p| p.size)
+            .min()
+    }
+
+    /// Checks a firmware image size against the app slots.
+    pub fn check_app_fits(&self, app_size: u64) -> Option<PartitionIssue> {
+        let limit = self.app_size_limit()?;
+        (app_size > limit).then(|| PartitionIssue {
+            severity: Severity::Error,
+            message: format!(
+                "Firmware ({} bytes) does not fit the {} byte app partition in {}",
+                app_size, limit, self.file
+            ),
+        })
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+}
+
+/// Parses a size or offset: decimal, `0x` hex, or with a `K`/`M` (or `KB`/`MB`) suffix.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    let number = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, multiplier) = if let Some(n) = number.strip_suffix('K') {
+        (n, 1024)
+    } else if let Some(n) = number.strip_suffix('M') {
+        (n, 1024 * 1024)
+    } else {
+        (number, 1)
+    };
+
+    let parsed = match number.strip_prefix("0X") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => number.parse::<u64>().ok()?,
+    };
+    parsed.checked_mul(multiplier)
+}
+
+fn is_numeric(value: &str) -> bool {
+    parse_size(value).is_some() && value.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Parses an ESP-IDF partition CSV (`Name, Type, SubType, Offset, Size, Flags`). Blank
+/// offsets follow the previous partition, aligned like `gen_esp32part.py` does.
+pub fn parse_partition_csv(content: &str) -> Result<Vec<Partition>, String> {
+    let mut partitions: Vec<Partition> = Vec::new();
+    let mut next_offset = FIRST_PARTITION_OFFSET;
+
+    for (index, raw) in content.lines().enumerate() {
+        let line = index + 1;
+        let row = raw.split('#').next().unwrap_or("").trim();
+        if row.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        if fields.len() < 5 {
+            return Err(format!(
+                "Line {}: expected Name, Type, SubType, Offset, Size",
+                line
+            ));
+        }
+        let kind = fields[1].to_string();
+        let alignment = if kind == "app" {
+            APP_ALIGNMENT
+        } else {
+            DATA_ALIGNMENT
+        };
+
+        let offset = if fields[3].is_empty() {
+            next_offset
+                .checked_next_multiple_of(alignment)
+                .ok_or_else(|| {
+                    format!("Line {}: offset is past the end of the address space", line)
+                })?
+        } else {
+            parse_size(fields[3])
+                .ok_or_else(|| format!("Line {}: invalid offset '{}'", line, fields[3]))?
+        };
+        let size = parse_size(fields[4])
+            .ok_or_else(|| format!("Line {}: invalid size '{}'", line, fields[4]))?;
+        next_offset = offset.checked_add(size).ok_or_else(|| {
+            format!(
+                "Line {}: offset {:#x} plus size {:#x} is past the end of the address space",
+                line, offset, size
+            )
+        })?;
+
+        partitions.push(Partition {
+            name: fields[0].to_string(),
+            kind,
+            subtype: fields[2].to_string(),
+            offset,
+            size,
+            flags: fields
+                .get(5)
+                .map(|flags| {
+                    flags
+                        .split(':')
+                        .map(str::trim)
+                        .filter(|flag| !flag.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            line,
+        });
+    }
+
+    Ok(partitions)
+}
+
+fn known_subtype(partition: &Partition) -> bool {
+    let subtype = partition.subtype.as_str();
+    match partition.kind.as_str() {
+        "app" => {
+            APP_SUBTYPES.contains(&subtype)
+                || subtype
+                    .strip_prefix("ota_")
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .is_some_and(|n| n < 16)
+                || is_numeric(subtype)
+        }
+        "data" => DATA_SUBTYPES.contains(&subtype) || is_numeric(subtype),
+        // Custom types (0x40-0xFE) define their own subtypes
+        _ => is_numeric(&partition.kind),
+    }
+}
+
+/// Checks names, types, alignment, overlaps and the total size against the flash chip.
+pub fn validate_partitions(
+    partitions: &[Partition],
+    flash_size: Option<u64>,
+) -> Vec<PartitionIssue> {
+    let mut issues = Vec::new();
+    let mut error = |message: String| {
+        issues.push(PartitionIssue {
+            severity: Severity::Error,
+            message,
+        })
+    };
+
+    let mut names = HashSet::new();
+    for p in partitions {
+        if p.name.is_empty() || p.name.len() > MAX_NAME_LEN {
+            error(format!(
+                "Line {}: partition name '{}' must be 1-{} characters",
+                p.line, p.name, MAX_NAME_LEN
+            ));
+        }
+        if !names.insert(p.name.as_str()) {
+            error(format!(
+                "Line {}: duplicate partition name '{}'",
+                p.line, p.name
+            ));
+        }
+        if !known_subtype(p) {
+            error(format!(
+                "Line {}: unknown {} subtype '{}' for '{}'",
+                p.line, p.kind, p.subtype, p.name
+            ));
+        }
+        if p.size == 0 {
+            error(format!("Line {}: '{}' has zero size", p.line, p.name));
+        }
+
+        let alignment = if p.is_app() {
+            APP_ALIGNMENT
+        } else {
+            DATA_ALIGNMENT
+        };
+        if p.offset % alignment != 0 {
+            error(format!(
+                "Line {}: '{}' at {:#x} must be aligned to {:#x}",
+                p.line, p.name, p.offset, alignment
+            ));
+        }
+        if p.offset < FIRST_PARTITION_OFFSET {
+            error(format!(
+                "Line {}: '{}' at {:#x} overlaps the bootloader or partition table (below {:#x})",
+                p.line, p.name, p.offset, FIRST_PARTITION_OFFSET
+            ));
+        }
+        if let Some(flash_size) = flash_size.filter(|&size| p.end() > size) {
+            error(format!(
+                "Line {}: '{}' ends at {:#x}, past the end of {} MB flash",
+                p.line,
+                p.name,
+                p.end(),
+                flash_size / (1024 * 1024)
+            ));
+        }
+    }
+
+    let mut sorted: Vec<&Partition> = partitions.iter().collect();
+    sorted.sort_by_key(|p| p.offset);
+    for pair in sorted.windows(2) {
+        if pair[1].offset < pair[0].end() {
+            error(format!(
+                "'{}' ({:#x}-{:#x}) overlaps '{}' ({:#x}-{:#x})",
+                pair[0].name,
+                pair[0].offset,
+                pair[0].end(),
+                pair[1].name,
+                pair[1].offset,
+                pair[1].end()
+            ));
+        }
+    }
+
+    if !partitions.iter().any(Partition::is_app) {
+        error("No app partition, so there is nowhere to flash the firmware".to_string());
+    }
+
+    let has_ota_slots = partitions
+        .iter()
+        .any(|p| p.is_app() && p.subtype.starts_with("ota_"));
+    let has_ota_data = partitions
+        .iter()
+        .any(|p| p.kind == "data" && p.subtype == "ota");
+    if has_ota_slots != has_ota_data {
+        issues.push(PartitionIssue {
+            severity: Severity::Warning,
+            message: if has_ota_slots {
+                "OTA app slots need an 'ota' data partition (otadata) to boot from".to_string()
+            } else {
+                "'ota' data partition without any ota_N app slots".to_string()
+            },
+        });
+    }
+
+    issues
+}
+
+/// Flash size from `board_upload.flash_size`, falling back to the board database.
+pub fn board_flash_size(env: &DiscoveredEnvironment) -> Option<u64> {
+    env.extra_options
+        .get("board_upload.flash_size")
+        .cloned()
+        .or_else(|| {
+            pin_database::environment_profile(&env.platform, env.board.as_deref())?.flash_size
+        })
+        .and_then(|size| parse_size(&size))
+}
+
+/// Loads and validates the table an environment's `board_build.partitions` names. Tables
+/// not in the project are PlatformIO built-ins, which are not checked.
+pub fn load_layout(project_dir: &Path, env: &DiscoveredEnvironment) -> Option<PartitionLayout> {
+    let file = env.board_build.partitions.clone()?;
+    let flash_size = board_flash_size(env);
+    let mut layout = PartitionLayout {
+        file,
+        partitions: Vec::new(),
+        flash_size,
+        filesystem_partition: None,
+        issues: Vec::new(),
+    };
+
+    let path = project_dir.join(&layout.file);
+    if !path.is_file() {
+        layout.issues.push(PartitionIssue {
+            severity: Severity::Info,
+            message: format!(
+                "{} is not in the project, so PlatformIO uses the framework's built-in table",
+                layout.file
+            ),
+        });
+        return Some(layout);
+    }
+
+    match fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", layout.file, e))
+        .and_then(|content| parse_partition_csv(&content))
+    {
+        Ok(partitions) => {
+            layout.issues = validate_partitions(&partitions, flash_size);
+            layout.filesystem_partition = partitions
+                .iter()
+                .find(|p| p.kind == "data" && FILESYSTEM_SUBTYPES.contains(&p.subtype.as_str()))
+                .map(|p| p.name.clone());
+            layout.partitions = partitions;
+        }
+        Err(e) => layout.issues.push(PartitionIssue {
+            severity: Severity::Error,
+            message: format!("{}: {}", layout.file, e),
+        }),
+    }
+    Some(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pio_parser;
+    use tempfile::tempdir;
+
+    const DJ_BOOTH: &str = "\
+# Name,    Type, SubType, Offset,   Size
+nvs,        data, nvs,     0x9000,   0x5000
+phy_init,   data, phy,     0xe000,   0x1000
+factory,    app,  factory, 0x10000,  0x300000
+storage,    data, spiffs,  0x310000, 0x0F0000
+";
+
+    #[test]
+    fn test_parse_sizes_and_blank_offsets() {
+        assert_eq!(parse_size("0x300000"), Some(0x300000));
+        assert_eq!(parse_size("960K"), Some(960 * 1024));
+        assert_eq!(parse_size("4MB"), Some(4 * 1024 * 1024));
+        assert_eq!(parse_size("24576"), Some(0x6000));
+        assert_eq!(parse_size("big"), None);
+
+        let partitions = parse_partition_csv(
+            "nvs, data, nvs, , 0x6000\notadata, data, ota, , 8K\napp0, app, ota_0, , 1M, encrypted\n",
+        )
+        .unwrap();
+        assert_eq!(partitions[0].offset, 0x9000);
+        assert_eq!(partitions[1].offset, 0xf000);
+        assert_eq!(partitions[2].offset, 0x20000);
+        assert_eq!(partitions[2].flags, vec!["encrypted"]);
+        assert!(validate_partitions(&partitions, Some(4 * 1024 * 1024)).is_empty());
+
+        assert!(parse_partition_csv("nvs, data, nvs, 0x9000\n").is_err());
+        let overflow = parse_partition_csv(
+            "nvs, data, nvs, 0x9000, 0x6000\nbig, data, spiffs, 0xffffffffffff0000, 1M\n",
+        );
+        assert!(overflow.unwrap_err().starts_with("Line 2:"));
+        let overflow = parse_partition_csv(
+            "big, data, spiffs, 0xfffffffffffff000, 0x800\napp0, app, ota_0, , 1M\n",
+        );
+        assert!(overflow.unwrap_err().starts_with("Line 2:"));
+    }
+
+    #[test]
+    fn test_validate_reports_layout_problems() {
+        let partitions = parse_partition_csv(DJ_BOOTH).unwrap();
+        assert!(validate_partitions(&partitions, Some(4 * 1024 * 1024)).is_empty());
+
+        // 0x310000 + 0xF0000 is exactly 4 MB, so a 2 MB chip is too small
+        let issues = validate_partitions(&partitions, Some(2 * 1024 * 1024));
+        assert!(issues.iter().any(|i| i.message.contains("past the end")));
+
+        let broken = parse_partition_csv(
+            "nvs, data, nvs, 0x9000, 0x6000\nfactory, app, factory, 0x18000, 1M\nfs, data, lfs, 0x10000, 64K\napp1, app, ota_1, 0x200000, 1M\n",
+        )
+        .unwrap();
+        let messages: Vec<String> = validate_partitions(&broken, None)
+            .into_iter()
+            .map(|i| i.message)
+            .collect();
+        assert!(messages.iter().any(|m| m.contains("aligned to 0x10000")));
+        assert!(messages
+            .iter()
+            .any(|m| m.contains("unknown data subtype 'lfs'")));
+        assert!(messages.iter().any(|m| m.contains("overlaps 'factory'")));
+        assert!(messages.iter().any(|m| m.contains("otadata")));
+    }
+
+    #[test]
+    fn test_load_layout_for_environment() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("platformio.ini"),
+            "[env:esp32]\nplatform = espressif32\nboard = esp32dev\nboard_build.partitions = partitions.csv\n\n[env:builtin]\nplatform = espressif32\nboard = esp32dev\nboard_build.partitions = huge_app.csv\n",
+        )
+        .unwrap();
+        fs::write(temp.path().join("partitions.csv"), DJ_BOOTH).unwrap();
+
+        let config = pio_parser::parse_platformio_ini(&temp.path().join("platformio.ini")).unwrap();
+        let layout = config.environments[0].partitions.as_ref().unwrap();
+        assert_eq!(layout.flash_size, Some(4 * 1024 * 1024));
+        assert_eq!(layout.filesystem_partition.as_deref(), Some("storage"));
+        assert_eq!(layout.app_size_limit(), Some(0x300000));
+        assert!(!layout.has_errors());
+        assert!(layout.check_app_fits(0x2f0000).is_none());
+        assert!(layout.check_app_fits(0x310000).is_some());
+
+        let builtin = config.environments[1].partitions.as_ref().unwrap();
+        assert!(builtin.partitions.is_empty());
+        assert_eq!(builtin.issues[0].severity, Severity::Info);
+    }
+}
@@ -0,0 +1,331 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Chip, module and board pin capabilities, embedded from `pin_database.toml`.
+static DATABASE: Lazy<PinDatabase> = Lazy::new(|| {
+    toml::from_str(include_str!("pin_database.toml")).expect("pin_database.toml is valid")
+});
+
+/// A set of GPIO numbers, written in the data file as pins and `"first-last"` ranges.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PinSet(BTreeSet<u8>);
+
+impl PinSet {
+    pub fn contains(&self, pin: u8) -> bool {
+        self.0.contains(&pin)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl<'de> Deserialize<'de> for PinSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Entry {
+            Pin(u8),
+            Range(String),
+        }
+
+        let mut pins = BTreeSet::new();
+        for entry in Vec::<Entry>::deserialize(deserializer)? {
+            match entry {
+                Entry::Pin(pin) => {
+                    pins.insert(pin);
+                }
+                Entry::Range(range) => {
+                    let (first, last) = range
+                        .split_once('-')
+                        .and_then(|(a, b)| {
+                            Some((a.trim().parse::<u8>().ok()?, b.trim().parse::<u8>().ok()?))
+                        })
+                        .filter(|(first, last)| first <= last)
+                        .ok_or_else(|| {
+                            serde::de::Error::custom(format!("invalid pin range '{}'", range))
+                        })?;
+                    pins.extend(first..=last);
+                }
+            }
+        }
+        Ok(PinSet(pins))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PinDatabase {
+    chips: HashMap<String, ChipSpec>,
+    modules: HashMap<String, ModuleSpec>,
+    boards: HashMap<String, BoardSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChipSpec {
+    pub name: String,
+    /// "GPIO" for ESP chips, "Pin" for Arduino-numbered AVR pins.
+    #[serde(default = "default_pin_label")]
+    pub pin_label: String,
+    pub gpios: PinSet,
+    #[serde(default)]
+    pub flash: PinSet,
+    #[serde(default)]
+    pub input_only: PinSet,
+    #[serde(default)]
+    pub adc1: PinSet,
+    /// ADC2 channels, which are unavailable while WiFi is active.
+    #[serde(default)]
+    pub adc2: PinSet,
+    #[serde(default)]
+    pub touch: PinSet,
+    #[serde(default)]
+    pub rtc: PinSet,
+    /// Native USB / USB-Serial-JTAG pins.
+    #[serde(default)]
+    pub usb: PinSet,
+    /// Default UART used by `Serial` and the bootloader.
+    #[serde(default)]
+    pub uart0: PinSet,
+    #[serde(default)]
+    pub no_pwm: PinSet,
+    #[serde(default)]
+    pub no_i2c: PinSet,
+    /// Pins recommended for general use.
+    #[serde(default)]
+    pub safe: PinSet,
+    #[serde(default)]
+    pub strapping: Vec<StrappingPin>,
+    /// Arduino `A0`..`An` aliases, numbered from `first`.
+    pub analog: Option<AnalogAliases>,
+    #[serde(default)]
+    pub peripherals: PeripheralLimits,
+}
+
+/// Hardware channels the shared libraries allocate from.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PeripheralLimits {
+    /// RMT transmit channels, one per `LEDStrip`.
+    pub rmt_tx: Option<u32>,
+    /// I2S ports. `AudioInput` always uses port 0.
+    pub i2s: Option<u32>,
+    /// LEDC channels, one per `LEDPWM` output pin.
+    pub ledc_channels: Option<u32>,
+    /// LEDC timers, shared by outputs with the same frequency and resolution.
+    pub ledc_timers: Option<u32>,
+}
+
+fn default_pin_label() -> String {
+    "GPIO".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrappingPin {
+    pub pin: u8,
+    pub severity: String,
+    pub note: String,
+    /// Driving the pin as an output can keep the chip from booting.
+    #[serde(default)]
+    pub output_error: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AnalogAliases {
+    pub first: u8,
+    pub count: u8,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ModuleSpec {
+    name: String,
+    chip: String,
+    #[serde(default)]
+    psram: PinSet,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BoardSpec {
+    name: String,
+    chip: String,
+    module: Option<String>,
+    led: Option<u8>,
+    flash_size: Option<String>,
+}
+
+/// Pin capabilities of one chip as fitted to a particular module or board.
+#[derive(Debug, Clone, Serialize)]
+pub struct PinProfile {
+    /// Chip id in the database, e.g. `esp32s3`.
+    pub chip_id: String,
+    pub chip: ChipSpec,
+    pub board: Option<String>,
+    pub module: Option<String>,
+    /// Display name of the board, falling back to the module or chip name.
+    pub display_name: String,
+    /// Pins the module reserves for PSRAM.
+    pub psram: PinSet,
+    /// Pin wired to the board's built-in LED.
+    pub led: Option<u8>,
+    /// Default flash size of the board, e.g. `4MB`.
+    pub flash_size: Option<String>,
+}
+
+impl PinProfile {
+    pub fn strapping(&self, pin: u8) -> Option<&StrappingPin> {
+        self.chip.strapping.iter().find(|s| s.pin == pin)
+    }
+
+    pub fn has_adc(&self) -> bool {
+        !self.chip.adc1.is_empty() || !self.chip.adc2.is_empty()
+    }
+
+    /// Recommended pins, without any the module reserves.
+    pub fn safe_pins(&self) -> Vec<u8> {
+        self.chip
+            .safe
+            .iter()
+            .filter(|pin| !self.psram.contains(*pin) && !self.chip.flash.contains(*pin))
+            .collect()
+    }
+
+    /// Resolves an Arduino analog alias such as `A0`.
+    pub fn analog_pin(&self, alias: &str) -> Option<u8> {
+        let analog = self.chip.analog?;
+        let index = alias.strip_prefix('A')?.parse::<u8>().ok()?;
+        (index < analog.count).then(|| analog.first + index)
+    }
+}
+
+/// Builds the profile for a chip, optionally fitted to a module.
+pub fn chip_profile(chip_id: &str, module_id: Option<&str>) -> Option<PinProfile> {
+    let chip = DATABASE.chips.get(chip_id)?.clone();
+    let module = match module_id {
+        Some(id) => Some(DATABASE.modules.get(id).filter(|m| m.chip == chip_id)?),
+        None => None,
+    };
+
+    Some(PinProfile {
+        chip_id: chip_id.to_string(),
+        display_name: module
+            .map(|m| format!("{} {}", chip.name, m.name))
+            .unwrap_or_else(|| chip.name.clone()),
+        board: None,
+        module: module_id.map(str::to_string),
+        psram: module.map(|m| m.psram.clone()).unwrap_or_default(),
+        led: None,
+        flash_size: None,
+        chip,
+    })
+}
+
+/// Looks up a PlatformIO board id, e.g. `esp32dev` or `megaatmega2560`.
+pub fn board_profile(board_id: &str) -> Option<PinProfile> {
+    let board = DATABASE.boards.get(board_id)?;
+    let mut profile = chip_profile(&board.chip, board.module.as_deref())?;
+    profile.board = Some(board_id.to_string());
+    profile.display_name = board.name.clone();
+    profile.led = board.led;
+    profile.flash_size = board.flash_size.clone();
+    Some(profile)
+}
+
+/// Resolves the profile for a platformio.ini environment. Unknown boards fall back to the
+/// chip implied by the platform and board name, like `environment_macros` does.
+pub fn environment_profile(platform: &str, board: Option<&str>) -> Option<PinProfile> {
+    if let Some(profile) = board.and_then(board_profile) {
+        return Some(profile);
+    }
+
+    let platform = platform.to_lowercase();
+    let board = board.unwrap_or("").to_lowercase();
+    if platform.contains("espressif32") {
+        let chip = ["s2", "s3", "c3", "c6"]
+            .iter()
+            .find(|variant| board.contains(*variant))
+            .map(|variant| format!("esp32{}", variant))
+            .unwrap_or_else(|| "esp32".to_string());
+        chip_profile(&chip, None)
+    } else if platform.contains("espressif8266") {
+        chip_profile("esp8266", None)
+    } else if platform.contains("atmelavr") && board.contains("mega") {
+        board_profile("megaatmega2560")
+    } else if platform.contains("atmelavr") {
+        board_profile("uno")
+    } else {
+        None
+    }
+}
+
+/// Board ids in the database, sorted.
+pub fn supported_boards() -> Vec<String> {
+    let mut boards: Vec<String> = DATABASE.boards.keys().cloned().collect();
+    boards.sort();
+    boards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_is_consistent() {
+        for (id, board) in &DATABASE.boards {
+            assert!(board_profile(id).is_some(), "board {} does not resolve", id);
+            assert!(DATABASE.chips.contains_key(&board.chip), "board {}", id);
+        }
+        for (id, chip) in &DATABASE.chips {
+            for set in [
+                &chip.flash,
+                &chip.input_only,
+                &chip.adc1,
+                &chip.adc2,
+                &chip.safe,
+            ] {
+                assert!(
+                    set.iter().all(|pin| chip.gpios.contains(pin)),
+                    "chip {}",
+                    id
+                );
+            }
+            for strap in &chip.strapping {
+                assert!(chip.gpios.contains(strap.pin), "chip {}", id);
+                assert!(
+                    matches!(strap.severity.as_str(), "info" | "warning"),
+                    "chip {}",
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_lookup() {
+        let wrover = board_profile("esp-wrover-kit").unwrap();
+        assert_eq!(wrover.chip_id, "esp32");
+        assert!(wrover.psram.contains(16));
+        assert!(!wrover.safe_pins().contains(&16));
+
+        let mega = board_profile("megaatmega2560").unwrap();
+        assert_eq!(mega.analog_pin("A15"), Some(69));
+        assert_eq!(mega.analog_pin("A16"), None);
+        assert_eq!(mega.led, Some(13));
+
+        assert!(board_profile("not-a-board").is_none());
+    }
+
+    #[test]
+    fn test_environment_fallback() {
+        let s3 = environment_profile("espressif32", Some("my-custom-s3-board")).unwrap();
+        assert_eq!(s3.chip_id, "esp32s3");
+        assert!(s3.chip.usb.contains(19));
+
+        let c3 = environment_profile("espressif32", Some("esp32-c3-devkitm-1")).unwrap();
+        assert_eq!(c3.board.as_deref(), Some("esp32-c3-devkitm-1"));
+
+        assert!(environment_profile("native", None).is_none());
+    }
+}
@@ -1,4 +1,9 @@
+use crate::utils::config_schema::{AppConfigSchema, ValueType};
+use crate::utils::pin_database::{self, PinProfile};
+use crate::utils::resource_planner;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PinValidation {
@@ -84,206 +89,450 @@ impl PinPurpose {
     }
 }
 
+/// Resolves the pin profile for the legacy platform/module selection.
+pub fn platform_profile(platform: Platform, module: Option<Module>) -> PinProfile {
+    let profile = match platform {
+        Platform::Esp32 => {
+            let module = match module {
+                Some(Module::Wrover) => "wrover",
+                Some(Module::Pico) => "pico",
+                Some(Module::Standard) | None => "wroom",
+            };
+            pin_database::chip_profile("esp32", Some(module))
+        }
+        Platform::Esp8266 => pin_database::chip_profile("esp8266", None),
+        Platform::Avr => pin_database::board_profile("uno"),
+    };
+    profile.expect("pin database covers every Platform")
+}
+
 /// Validates a GPIO pin for a given purpose on a specific platform.
 ///
-/// Based on docs/pin-reference.md:
-/// - GPIO 6-11: SPI flash - NEVER use
-/// - GPIO 34-39: Input-only (no pull-ups)
-/// - ADC2 pins: Unavailable when WiFi active
-/// - GPIO 12: HIGH at boot prevents normal boot
-/// - GPIO 16/17: Unavailable on WROVER/PICO (PSRAM)
+/// See [`validate_board_pin`] for the rules; the pin data lives in `pin_database.toml`
+/// and follows docs/pin-reference.md.
 pub fn validate_pin(
     pin: u8,
     purpose: PinPurpose,
     platform: Platform,
     module: Option<Module>,
 ) -> PinValidation {
-    match platform {
-        Platform::Esp32 => validate_esp32_pin(pin, purpose, module),
-        Platform::Esp8266 => validate_esp8266_pin(pin, purpose),
-        Platform::Avr => validate_avr_pin(pin, purpose),
-    }
+    validate_board_pin(pin, purpose, &platform_profile(platform, module))
 }
 
-fn validate_esp32_pin(pin: u8, purpose: PinPurpose, module: Option<Module>) -> PinValidation {
+/// Validates a pin against a chip/board profile from the pin database.
+///
+/// - Flash and PSRAM pins, and pins the chip does not have: error
+/// - Input-only pins driven as outputs, strapping pins that break boot when driven: error
+/// - ADC2 (unavailable with WiFi), strapping, default UART and USB pins: warning
+/// - Built-in LED and pins outside the recommended list: info
+pub fn validate_board_pin(pin: u8, purpose: PinPurpose, profile: &PinProfile) -> PinValidation {
+    let chip = &profile.chip;
+    let label = chip.pin_label.as_str();
+
     // === ERRORS (block these) ===
 
-    // GPIO 6-11: SPI flash - NEVER use
-    if (6..=11).contains(&pin) {
-        return PinValidation::error(
-            "GPIO 6-11 are connected to SPI flash. Using them will crash the ESP32.",
-        );
+    if !chip.gpios.contains(pin) {
+        return PinValidation::error(&format!(
+            "{} {} is out of range for {}.",
+            label, pin, profile.display_name
+        ));
     }
 
-    // GPIO 12: HIGH at boot prevents normal boot
-    if pin == 12 && purpose.is_output() {
-        return PinValidation::error(
-            "GPIO 12 is a dangerous strapping pin. HIGH at boot selects 1.8V flash voltage, \
-             preventing boot. Use only with 10k pull-down or avoid entirely.",
-        );
+    if chip.flash.contains(pin) {
+        return PinValidation::error(&format!(
+            "{} {} is connected to SPI flash on {}. Using it will crash the chip.",
+            label, pin, chip.name
+        ));
     }
 
-    // Input-only pins for output purposes
-    if (34..=39).contains(&pin) && purpose.is_output() {
-        return PinValidation::error("GPIO 34-39 are input-only (no output driver).");
+    if profile.psram.contains(pin) {
+        return PinValidation::error(&format!(
+            "{} {} is used for PSRAM on {}.",
+            label, pin, profile.display_name
+        ));
     }
 
-    // WROVER/PICO: GPIO 16, 17 unavailable
-    if matches!(module, Some(Module::Wrover) | Some(Module::Pico)) && (pin == 16 || pin == 17) {
-        return PinValidation::error("GPIO 16/17 are used for PSRAM on WROVER/PICO modules.");
+    let strapping = profile.strapping(pin);
+    if let Some(strap) = strapping.filter(|s| s.output_error && purpose.is_output()) {
+        return PinValidation::error(&format!(
+            "{} {} is a dangerous strapping pin: {}.",
+            label, pin, strap.note
+        ));
+    }
+
+    if chip.input_only.contains(pin) && purpose.is_output() {
+        return PinValidation::error(&format!(
+            "{} {} is input-only (no output driver).",
+            label, pin
+        ));
+    }
+
+    if purpose == PinPurpose::Pwm && chip.no_pwm.contains(pin) {
+        return PinValidation::error(&format!(
+            "{} {} does not support PWM on {}.",
+            label, pin, chip.name
+        ));
+    }
+
+    if purpose == PinPurpose::I2c && chip.no_i2c.contains(pin) {
+        return PinValidation::error(&format!(
+            "{} {} does not support I2C on {}.",
+            label, pin, chip.name
+        ));
+    }
+
+    if purpose == PinPurpose::Adc
+        && profile.has_adc()
+        && !chip.adc1.contains(pin)
+        && !chip.adc2.contains(pin)
+    {
+        return PinValidation::error(&format!(
+            "{} {} has no ADC channel on {}.",
+            label, pin, chip.name
+        ));
     }
 
     // === WARNINGS ===
 
-    // ADC2 pins with WiFi conflict
-    let adc2_pins = [0, 2, 4, 12, 13, 14, 15, 25, 26, 27];
-    if adc2_pins.contains(&pin) && purpose == PinPurpose::Adc {
-        return PinValidation::warning(
-            "GPIO is on ADC2. ADC2 is unavailable when WiFi is active. \
-             Use ADC1 pins (32-39) for reliable analog input.",
-        );
+    if purpose == PinPurpose::Adc && chip.adc2.contains(pin) {
+        return PinValidation::warning(&format!(
+            "{} {} is on ADC2. ADC2 is unavailable when WiFi is active. \
+             Use ADC1 pins for reliable analog input.",
+            label, pin
+        ));
     }
 
-    // GPIO 0: Boot mode (must be HIGH for normal boot)
-    if pin == 0 {
-        return PinValidation::warning(
-            "GPIO 0 is a boot strapping pin. Must be HIGH at boot for normal operation. \
-             Safe for output after boot, but external loads pulling LOW will prevent boot.",
-        );
+    if let Some(strap) = strapping {
+        let message = format!("{} {} is a boot strapping pin: {}.", label, pin, strap.note);
+        return if strap.severity == "info" {
+            PinValidation::info(&message)
+        } else {
+            PinValidation::warning(&message)
+        };
     }
 
-    // GPIO 2: Built-in LED on many boards
-    if pin == 2 {
-        return PinValidation::warning(
-            "GPIO 2 is connected to the built-in LED on many boards and is a boot strapping pin. \
-             Will flicker during boot.",
-        );
+    if chip.uart0.contains(pin) {
+        return PinValidation::warning(&format!(
+            "{} {} is used by the default Serial port. Using it will interfere with USB programming and logging.",
+            label, pin
+        ));
     }
 
-    // GPIO 5: Strapping pin (low risk)
-    if pin == 5 {
-        return PinValidation::info(
-            "GPIO 5 is a strapping pin affecting SDIO timing. Low risk for LED projects.",
-        );
+    if chip.usb.contains(pin) {
+        return PinValidation::warning(&format!(
+            "{} {} is a native USB pin. Using it disconnects the USB serial console.",
+            label, pin
+        ));
     }
 
-    // GPIO 15: Boot log suppression
-    if pin == 15 {
-        return PinValidation::warning(
-            "GPIO 15 is a strapping pin. LOW at boot suppresses UART boot messages.",
-        );
+    // === INFO ===
+
+    if profile.led == Some(pin) {
+        return PinValidation::info(&format!(
+            "{} {} has a built-in LED on {} which may flicker or draw slight current.",
+            label, pin, profile.display_name
+        ));
     }
 
-    // Valid pins check
-    let safe_pins = [4, 13, 14, 16, 17, 18, 19, 21, 22, 23, 25, 26, 27, 32, 33];
-    if safe_pins.contains(&pin) {
+    if chip.safe.contains(pin) {
         return PinValidation::ok();
     }
 
     // Input-only pins are fine for input/ADC
-    if (34..=39).contains(&pin) && !purpose.is_output() {
+    if chip.input_only.contains(pin) {
         return PinValidation::ok();
     }
 
     // Other pins might work but weren't explicitly validated
     PinValidation::info(&format!(
-        "GPIO {} is not in the safe GPIO list. Verify it's appropriate for your board.",
-        pin
+        "{} {} is not in the safe pin list for {}. Verify it's appropriate for your board.",
+        label, pin, profile.display_name
     ))
 }
 
-fn validate_esp8266_pin(pin: u8, purpose: PinPurpose) -> PinValidation {
-    // ESP8266 has fewer pins and different constraints
+/// Returns a list of safe GPIO pins for a platform.
+pub fn get_safe_pins(platform: Platform, module: Option<Module>) -> Vec<u8> {
+    platform_profile(platform, module).safe_pins()
+}
 
-    // GPIO 6-11: Flash pins - never use
-    if (6..=11).contains(&pin) {
-        return PinValidation::error(
-            "GPIO 6-11 are connected to SPI flash on ESP8266. Never use.",
-        );
-    }
+/// A pin-typed define with the purpose inferred from its name.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinAssignment {
+    pub name: String,
+    pub pin: u8,
+    pub purpose: PinPurpose,
+}
 
-    // GPIO 0: Boot mode
-    if pin == 0 {
-        return PinValidation::warning(
-            "GPIO 0 is a boot strapping pin. Must be HIGH at boot. \
-             Can be used after boot but may interfere with programming.",
-        );
-    }
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PinIssueKind {
+    /// The pin is unusable or risky for its purpose on its own.
+    Invalid,
+    /// Several defines share a GPIO for the same purpose.
+    Duplicate,
+    /// Several defines share a GPIO for different purposes, or the pin is claimed elsewhere.
+    Conflict,
+    /// The pin's level at reset selects the boot mode.
+    Strapping,
+    /// An ADC2 pin is read while the app uses WiFi.
+    Adc2Wifi,
+}
 
-    // GPIO 2: Must be HIGH at boot
-    if pin == 2 {
-        return PinValidation::warning(
-            "GPIO 2 must be HIGH at boot. Also connected to built-in LED on many boards.",
-        );
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinIssue {
+    pub kind: PinIssueKind,
+    pub severity: Severity,
+    pub pin: u8,
+    pub defines: Vec<String>,
+    pub message: String,
+}
 
-    // GPIO 15: Must be LOW at boot
-    if pin == 15 {
-        return PinValidation::warning(
-            "GPIO 15 must be LOW at boot (via pull-down). Safe to use after boot.",
-        );
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinMapReport {
+    /// False when any issue is an error.
+    pub valid: bool,
+    pub assignments: Vec<PinAssignment>,
+    pub issues: Vec<PinIssue>,
+}
 
-    // GPIO 16: Special - no PWM, no I2C, limited features
-    if pin == 16 && matches!(purpose, PinPurpose::Pwm | PinPurpose::I2c) {
-        return PinValidation::error(
-            "GPIO 16 does not support PWM or I2C on ESP8266.",
-        );
+/// Infers what a pin define is used for from its name, e.g. `PIN_I2S_WS` -> I2s,
+/// `PIN_BRIGHTNESS` -> Adc, `PIN_SWITCH` -> Input. Anything unrecognised is an output.
+pub fn infer_pin_purpose(name: &str) -> PinPurpose {
+    let upper = name.to_uppercase();
+    let stem = upper
+        .strip_prefix("PIN_")
+        .or_else(|| upper.strip_suffix("_PIN"))
+        .unwrap_or(&upper);
+    let words: Vec<&str> = stem.split('_').collect();
+    let has = |candidates: &[&str]| words.iter().any(|word| candidates.contains(word));
+
+    if has(&["I2S", "MCLK", "BCLK", "BCK", "LRCK", "LRCLK"]) {
+        PinPurpose::I2s
+    } else if has(&["I2C", "SDA", "SCL"]) {
+        PinPurpose::I2c
+    } else if has(&["SPI", "MOSI", "MISO", "SCK", "SCLK", "CS", "SS"]) {
+        PinPurpose::Spi
+    } else if has(&[
+        "BRIGHTNESS",
+        "POT",
+        "ADC",
+        "ANALOG",
+        "VBAT",
+        "BATTERY",
+        "LDR",
+    ]) {
+        PinPurpose::Adc
+    } else if has(&["SWITCH", "BUTTON", "BTN", "PIR", "SENSOR", "ENCODER", "RX"]) {
+        PinPurpose::Input
+    } else if has(&["PWM", "DIMMER", "FAN"]) {
+        PinPurpose::Pwm
+    } else {
+        PinPurpose::Output
     }
+}
 
-    // Safe pins for general use
-    let safe_pins = [4, 5, 12, 13, 14];
-    if safe_pins.contains(&pin) {
-        return PinValidation::ok();
+/// Parses a pin define value. Arduino analog aliases (`A0`, `A15`) map to their digital
+/// numbers on boards that have them. Negative values (commonly `-1` for "not connected")
+/// are treated as unassigned.
+pub fn parse_pin_value(value: &str, profile: &PinProfile) -> Option<u8> {
+    let value = value.trim();
+    if value.starts_with('A') {
+        return profile.analog_pin(value);
     }
-
-    PinValidation::info(&format!(
-        "GPIO {} may have constraints on ESP8266. Verify it's appropriate for your use.",
-        pin
-    ))
+    value.parse::<u8>().ok()
 }
 
-fn validate_avr_pin(pin: u8, _purpose: PinPurpose) -> PinValidation {
-    // AVR (Arduino Uno) has pins 0-19 (D0-D13, A0-A5)
-    if pin > 19 {
-        return PinValidation::error(&format!(
-            "Pin {} is out of range for Arduino Uno (0-19).",
-            pin
-        ));
+/// Collects the pin-typed defines of a schema with the config.h defaults overridden by
+/// `defines`. Pins declared under platform conditionals come from the bucket of the
+/// profile's chip. With an environment, defines its platform conditionals exclude are skipped.
+pub fn schema_pins(
+    schema: &AppConfigSchema,
+    environment: Option<&str>,
+    defines: &HashMap<String, String>,
+    profile: &PinProfile,
+) -> Vec<(String, u8)> {
+    let conditional = schema
+        .platform_conditional
+        .get(&platform_target(&profile.chip_id))
+        .into_iter()
+        .flatten();
+
+    let mut pins: Vec<(String, u8)> = Vec::new();
+    for define in schema.defines.iter().chain(conditional) {
+        if define.value_type != ValueType::Pin || pins.iter().any(|(name, _)| *name == define.name)
+        {
+            continue;
+        }
+        if let Some(env) = environment {
+            if !define.environments.is_empty() && !define.environments.iter().any(|e| e == env) {
+                continue;
+            }
+        }
+        let value = defines.get(&define.name).unwrap_or(&define.default_value);
+        if let Some(pin) = parse_pin_value(value, profile) {
+            pins.push((define.name.clone(), pin));
+        }
     }
+    pins
+}
 
-    // Pins 0, 1 are UART (Serial)
-    if pin == 0 || pin == 1 {
-        return PinValidation::warning(
-            "Pins 0/1 are used for Serial communication. Using them will interfere with USB programming.",
-        );
+/// The `platform_conditional` bucket a chip's defines are filed under, e.g. `esp32s3` ->
+/// `ESP32S3`, `atmega2560` -> `__AVR__`.
+fn platform_target(chip_id: &str) -> String {
+    if chip_id.starts_with("atmega") {
+        "__AVR__".to_string()
+    } else {
+        chip_id.to_uppercase()
     }
+}
 
-    // Pin 13 has built-in LED
-    if pin == 13 {
-        return PinValidation::info(
-            "Pin 13 has a built-in LED which may cause slight current draw.",
-        );
-    }
+/// Whether any of the app's sources, in `src/` or its subdirectories, include a WiFi stack
+/// header.
+pub fn app_uses_wifi(app_path: &Path) -> bool {
+    let mut sources = Vec::new();
+    if resource_planner::collect_sources(app_path, &app_path.join("src"), &mut sources).is_err() {
+        return false;
+    }
+    sources.iter().any(|(_, content)| {
+        content.lines().any(|line| {
+            let line = line.trim();
+            line.starts_with("#include")
+                && ["WiFi.h", "ESP8266WiFi.h", "esp_wifi.h"]
+                    .iter()
+                    .any(|header| line.contains(header))
+        })
+    })
+}
 
-    PinValidation::ok()
+fn purpose_label(purpose: &PinPurpose) -> &'static str {
+    match purpose {
+        PinPurpose::Output => "output",
+        PinPurpose::Input => "input",
+        PinPurpose::Adc => "ADC",
+        PinPurpose::I2c => "I2C",
+        PinPurpose::Spi => "SPI",
+        PinPurpose::I2s => "I2S",
+        PinPurpose::Pwm => "PWM",
+    }
 }
 
-/// Returns a list of safe GPIO pins for a platform.
-pub fn get_safe_pins(platform: Platform, module: Option<Module>) -> Vec<u8> {
-    match platform {
-        Platform::Esp32 => {
-            let mut pins = vec![4, 13, 14, 18, 19, 21, 22, 23, 25, 26, 27, 32, 33];
-            // Add 16, 17 only if not WROVER/PICO
-            if !matches!(module, Some(Module::Wrover) | Some(Module::Pico)) {
-                pins.push(16);
-                pins.push(17);
+/// Validates every pin define of a configuration together.
+///
+/// On top of the per-pin rules of [`validate_board_pin`], this reports pins shared between
+/// defines, boot strapping risks and ADC2 reads in apps that use WiFi.
+pub fn validate_pin_map(
+    pins: &[(String, u8)],
+    profile: &PinProfile,
+    wifi_enabled: bool,
+) -> PinMapReport {
+    let label = profile.chip.pin_label.as_str();
+    let assignments: Vec<PinAssignment> = pins
+        .iter()
+        .map(|(name, pin)| PinAssignment {
+            name: name.clone(),
+            pin: *pin,
+            purpose: infer_pin_purpose(name),
+        })
+        .collect();
+    let mut issues = Vec::new();
+
+    for assignment in &assignments {
+        let PinAssignment { name, pin, purpose } = assignment;
+        let pin = *pin;
+
+        if wifi_enabled && *purpose == PinPurpose::Adc && profile.chip.adc2.contains(pin) {
+            issues.push(PinIssue {
+                kind: PinIssueKind::Adc2Wifi,
+                severity: Severity::Error,
+                pin,
+                defines: vec![name.clone()],
+                message: format!(
+                    "{} reads {} {} on ADC2, which is unavailable while WiFi is active. \
+                     Move it to an ADC1 pin.",
+                    name, label, pin
+                ),
+            });
+            continue;
+        }
+
+        let validation = validate_board_pin(pin, purpose.clone(), profile);
+        if validation.severity != Severity::Error {
+            if let Some(strap) = profile.strapping(pin) {
+                issues.push(PinIssue {
+                    kind: PinIssueKind::Strapping,
+                    severity: if strap.severity == "info" {
+                        Severity::Info
+                    } else {
+                        Severity::Warning
+                    },
+                    pin,
+                    defines: vec![name.clone()],
+                    message: format!(
+                        "{} ({}) uses {} {}, a boot strapping pin: {}.",
+                        name,
+                        purpose_label(purpose),
+                        label,
+                        pin,
+                        strap.note
+                    ),
+                });
+                continue;
             }
-            pins.sort();
-            pins
         }
-        Platform::Esp8266 => vec![4, 5, 12, 13, 14],
-        Platform::Avr => (2..=13).chain(14..=19).collect(),
+
+        if validation.severity != Severity::Ok {
+            issues.push(PinIssue {
+                kind: PinIssueKind::Invalid,
+                severity: validation.severity,
+                pin,
+                defines: vec![name.clone()],
+                message: format!("{}: {}", name, validation.message),
+            });
+        }
+    }
+
+    let mut used_pins: Vec<u8> = assignments.iter().map(|a| a.pin).collect();
+    used_pins.sort();
+    used_pins.dedup();
+    for pin in used_pins {
+        let sharing: Vec<&PinAssignment> = assignments.iter().filter(|a| a.pin == pin).collect();
+        if sharing.len() < 2 {
+            continue;
+        }
+        let defines: Vec<String> = sharing.iter().map(|a| a.name.clone()).collect();
+        let same_purpose = sharing.iter().all(|a| a.purpose == sharing[0].purpose);
+        issues.push(if same_purpose {
+            PinIssue {
+                kind: PinIssueKind::Duplicate,
+                severity: Severity::Error,
+                pin,
+                message: format!("{} {} is assigned to {}.", label, pin, defines.join(", ")),
+                defines,
+            }
+        } else {
+            let uses: Vec<String> = sharing
+                .iter()
+                .map(|a| format!("{} ({})", a.name, purpose_label(&a.purpose)))
+                .collect();
+            PinIssue {
+                kind: PinIssueKind::Conflict,
+                severity: Severity::Error,
+                pin,
+                message: format!(
+                    "{} {} has conflicting uses: {}.",
+                    label,
+                    pin,
+                    uses.join(", ")
+                ),
+                defines,
+            }
+        });
+    }
+
+    PinMapReport {
+        valid: !issues.iter().any(|issue| issue.severity == Severity::Error),
+        assignments,
+        issues,
     }
 }
 
@@ -342,7 +591,12 @@ mod tests {
 
     #[test]
     fn test_esp32_wrover_psram_pins() {
-        let result = validate_pin(16, PinPurpose::Output, Platform::Esp32, Some(Module::Wrover));
+        let result = validate_pin(
+            16,
+            PinPurpose::Output,
+            Platform::Esp32,
+            Some(Module::Wrover),
+        );
         assert!(!result.valid);
         assert!(result.message.contains("PSRAM"));
 
@@ -373,4 +627,318 @@ mod tests {
         assert!(result.valid);
         assert_eq!(result.severity, Severity::Warning);
     }
+
+    #[test]
+    fn test_safe_pins_unchanged_for_platforms() {
+        assert_eq!(
+            get_safe_pins(Platform::Esp32, None),
+            vec![4, 13, 14, 16, 17, 18, 19, 21, 22, 23, 25, 26, 27, 32, 33]
+        );
+        assert!(!get_safe_pins(Platform::Esp32, Some(Module::Wrover)).contains(&16));
+        assert_eq!(
+            get_safe_pins(Platform::Esp8266, None),
+            vec![4, 5, 12, 13, 14]
+        );
+        assert_eq!(
+            get_safe_pins(Platform::Avr, None),
+            (2..=19).collect::<Vec<u8>>()
+        );
+    }
+
+    #[test]
+    fn test_validate_board_pin() {
+        let cases = [
+            (
+                "esp32-s3-devkitc-1",
+                19,
+                PinPurpose::Output,
+                Severity::Warning,
+            ),
+            (
+                "esp32-s3-devkitc-1",
+                27,
+                PinPurpose::Output,
+                Severity::Error,
+            ),
+            ("esp32-s3-devkitc-1", 35, PinPurpose::Output, Severity::Ok),
+            (
+                "esp32-s3-devkitc-1-n8r8",
+                35,
+                PinPurpose::Output,
+                Severity::Error,
+            ),
+            ("esp32-s2-saola-1", 46, PinPurpose::Output, Severity::Error),
+            (
+                "esp32-c3-devkitm-1",
+                9,
+                PinPurpose::Input,
+                Severity::Warning,
+            ),
+            ("esp32-c3-devkitm-1", 7, PinPurpose::Adc, Severity::Error),
+            ("esp32-c6-devkitc-1", 6, PinPurpose::Adc, Severity::Ok),
+            (
+                "esp32-c6-devkitc-1",
+                31,
+                PinPurpose::Output,
+                Severity::Error,
+            ),
+            ("nodemcuv2", 16, PinPurpose::Pwm, Severity::Error),
+            ("uno", 20, PinPurpose::Output, Severity::Error),
+            ("uno", 13, PinPurpose::Output, Severity::Info),
+            ("megaatmega2560", 60, PinPurpose::Adc, Severity::Ok),
+            ("megaatmega2560", 40, PinPurpose::Output, Severity::Ok),
+            ("megaatmega2560", 70, PinPurpose::Output, Severity::Error),
+        ];
+        for (board, pin, purpose, expected) in cases {
+            let profile = pin_database::board_profile(board).unwrap();
+            let result = validate_board_pin(pin, purpose, &profile);
+            assert_eq!(
+                result.severity, expected,
+                "{} pin {}: {}",
+                board, pin, result.message
+            );
+        }
+    }
+
+    fn pin_map(pins: &[(&str, u8)]) -> Vec<(String, u8)> {
+        pins.iter()
+            .map(|(name, pin)| (name.to_string(), *pin))
+            .collect()
+    }
+
+    #[test]
+    fn test_infer_pin_purpose() {
+        let cases = [
+            ("PIN_I2S_MCLK", PinPurpose::I2s),
+            ("PIN_I2S_WS", PinPurpose::I2s),
+            ("PIN_BRIGHTNESS", PinPurpose::Adc),
+            ("PIN_LED_DATA", PinPurpose::Output),
+            ("PIN_SWITCH", PinPurpose::Input),
+            ("BUTTON_PIN", PinPurpose::Input),
+            ("PIN_SDA", PinPurpose::I2c),
+            ("PIN_SPI_MOSI", PinPurpose::Spi),
+            ("PIN_FAN_PWM", PinPurpose::Pwm),
+            ("LED_PIN", PinPurpose::Output),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(infer_pin_purpose(name), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_parse_pin_value() {
+        let cases = [
+            ("13", "esp32dev", Some(13)),
+            ("A0", "uno", Some(14)),
+            ("A5", "uno", Some(19)),
+            ("A6", "uno", None),
+            ("A15", "megaatmega2560", Some(69)),
+            ("A0", "esp32dev", None),
+            ("-1", "esp32dev", None),
+            ("LED_BUILTIN", "uno", None),
+        ];
+        for (value, board, expected) in cases {
+            let profile = pin_database::board_profile(board).unwrap();
+            assert_eq!(parse_pin_value(value, &profile), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_validate_pin_map() {
+        struct Case {
+            label: &'static str,
+            pins: &'static [(&'static str, u8)],
+            board: &'static str,
+            wifi: bool,
+            expected: &'static [(PinIssueKind, Severity)],
+        }
+
+        let cases = [
+            Case {
+                label: "clean esp32 map",
+                pins: &[
+                    ("PIN_LED_DATA", 4),
+                    ("PIN_SWITCH", 13),
+                    ("PIN_BRIGHTNESS", 32),
+                ],
+                board: "esp32dev",
+                wifi: true,
+                expected: &[],
+            },
+            Case {
+                label: "led data and switch share a gpio",
+                pins: &[("PIN_LED_DATA", 13), ("PIN_SWITCH", 13)],
+                board: "esp32dev",
+                wifi: false,
+                expected: &[(PinIssueKind::Conflict, Severity::Error)],
+            },
+            Case {
+                label: "two outputs on one gpio",
+                pins: &[("PIN_LED_DATA", 4), ("PIN_RELAY", 4)],
+                board: "esp32dev",
+                wifi: false,
+                expected: &[(PinIssueKind::Duplicate, Severity::Error)],
+            },
+            Case {
+                label: "mclk on strapping gpio 0",
+                pins: &[("PIN_I2S_MCLK", 0), ("PIN_I2S_SCK", 26)],
+                board: "esp32dev",
+                wifi: false,
+                expected: &[(PinIssueKind::Strapping, Severity::Warning)],
+            },
+            Case {
+                label: "adc2 brightness with wifi",
+                pins: &[("PIN_BRIGHTNESS", 25)],
+                board: "esp32dev",
+                wifi: true,
+                expected: &[(PinIssueKind::Adc2Wifi, Severity::Error)],
+            },
+            Case {
+                label: "adc2 brightness without wifi",
+                pins: &[("PIN_BRIGHTNESS", 25)],
+                board: "esp32dev",
+                wifi: false,
+                expected: &[(PinIssueKind::Invalid, Severity::Warning)],
+            },
+            Case {
+                label: "led data on flash pin",
+                pins: &[("PIN_LED_DATA", 6)],
+                board: "esp32dev",
+                wifi: false,
+                expected: &[(PinIssueKind::Invalid, Severity::Error)],
+            },
+            Case {
+                label: "uno analog and digital pins",
+                pins: &[("PIN_LED_DATA", 6), ("PIN_BRIGHTNESS", 14)],
+                board: "uno",
+                wifi: false,
+                expected: &[],
+            },
+            Case {
+                label: "mega analog pin",
+                pins: &[("PIN_LED_DATA", 6), ("PIN_BRIGHTNESS", 54)],
+                board: "megaatmega2560",
+                wifi: false,
+                expected: &[],
+            },
+            Case {
+                label: "s3 adc2 brightness with wifi",
+                pins: &[("PIN_BRIGHTNESS", 11)],
+                board: "esp32-s3-devkitc-1",
+                wifi: true,
+                expected: &[(PinIssueKind::Adc2Wifi, Severity::Error)],
+            },
+            Case {
+                label: "c3 led data on usb-jtag pin",
+                pins: &[("PIN_LED_DATA", 18)],
+                board: "esp32-c3-devkitm-1",
+                wifi: false,
+                expected: &[(PinIssueKind::Invalid, Severity::Warning)],
+            },
+        ];
+
+        for case in cases {
+            let profile = pin_database::board_profile(case.board).unwrap();
+            let report = validate_pin_map(&pin_map(case.pins), &profile, case.wifi);
+            let found: Vec<(PinIssueKind, Severity)> = report
+                .issues
+                .iter()
+                .map(|issue| (issue.kind.clone(), issue.severity.clone()))
+                .collect();
+            assert_eq!(found, case.expected, "{}", case.label);
+            let has_error = case.expected.iter().any(|(_, s)| *s == Severity::Error);
+            assert_eq!(report.valid, !has_error, "{}", case.label);
+        }
+    }
+
+    /// Pin report for one environment of a monorepo app, using its checked-in config.h.
+    fn app_pin_report(app: &str, environment: &str) -> PinMapReport {
+        let app_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../../apps")
+            .join(app);
+        let schema = crate::utils::config_schema::parse_config_schema(&app_path).unwrap();
+        let config =
+            crate::utils::pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))
+                .unwrap();
+        let env = config
+            .environments
+            .iter()
+            .find(|e| e.name == environment)
+            .unwrap();
+        let profile =
+            pin_database::environment_profile(&env.platform, env.board.as_deref()).unwrap();
+
+        let pins = schema_pins(&schema, Some(environment), &HashMap::new(), &profile);
+        validate_pin_map(&pins, &profile, app_uses_wifi(&app_path))
+    }
+
+    fn assigned_pins(report: &PinMapReport) -> Vec<(&str, u8)> {
+        report
+            .assignments
+            .iter()
+            .map(|a| (a.name.as_str(), a.pin))
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_pins_for_real_app_configs() {
+        let booth = app_pin_report("dj-booth", "esp32");
+        assert_eq!(
+            assigned_pins(&booth),
+            vec![
+                ("PIN_LED_DATA", 4),
+                ("PIN_SWITCH", 13),
+                ("PIN_BRIGHTNESS", 32),
+                ("PIN_I2S_SCK", 26),
+                ("PIN_I2S_WS", 25),
+                ("PIN_I2S_SD", 33),
+                ("PIN_I2S_MCLK", 27),
+            ]
+        );
+
+        // The ESP32 branch puts the I2S master clock on strapping GPIO 0
+        let panel = app_pin_report("led-panel", "esp32");
+        assert_eq!(panel.assignments.len(), 7);
+        assert!(panel
+            .issues
+            .iter()
+            .any(|issue| issue.kind == PinIssueKind::Strapping
+                && issue.pin == 0
+                && issue.defines == vec!["PIN_I2S_MCLK"]));
+
+        // The ESP8266 branch; its A0 is the dedicated ADC input, not a GPIO
+        let panel_8266 = app_pin_report("led-panel", "esp8266");
+        assert_eq!(
+            assigned_pins(&panel_8266),
+            vec![("PIN_LED_DATA", 2), ("PIN_SWITCH", 14)]
+        );
+        assert!(panel_8266
+            .issues
+            .iter()
+            .any(|issue| issue.kind == PinIssueKind::Strapping && issue.pin == 2));
+    }
+
+    #[test]
+    fn test_conflict_lists_defines() {
+        let report = validate_pin_map(
+            &pin_map(&[("PIN_LED_DATA", 13), ("PIN_SWITCH", 13)]),
+            &platform_profile(Platform::Esp32, None),
+            false,
+        );
+        assert_eq!(report.issues[0].defines, vec!["PIN_LED_DATA", "PIN_SWITCH"]);
+        assert!(report.issues[0].message.contains("PIN_SWITCH (input)"));
+    }
+
+    #[test]
+    fn test_app_uses_wifi_scans_nested_sources() {
+        let temp = tempfile::tempdir().unwrap();
+        let net = temp.path().join("src").join("net");
+        std::fs::create_dir_all(&net).unwrap();
+        let main = temp.path().join("src").join("main.cpp");
+        std::fs::write(main, "#include <Arduino.h>\n").unwrap();
+        assert!(!app_uses_wifi(temp.path()));
+
+        std::fs::write(net.join("wifi.cpp"), "#include <WiFi.h>\n").unwrap();
+        assert!(app_uses_wifi(temp.path()));
+    }
 }
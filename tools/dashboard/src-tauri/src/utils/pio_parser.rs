@@ -1,13 +1,23 @@
+use crate::utils::partitions::{self, PartitionLayout};
 use configparser::ini::Ini;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlatformioConfig {
     pub environments: Vec<DiscoveredEnvironment>,
+    /// Environments `pio run` builds without `-e` (from `[platformio] default_envs`).
+    pub default_envs: Vec<String>,
+    /// Extra ini files merged via `[platformio] extra_configs`, relative to the project.
+    pub extra_configs: Vec<String>,
+    pub src_dir: String,
+    pub data_dir: String,
+    pub test_dir: String,
+    pub diagnostics: Vec<IniDiagnostic>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,174 +27,1008 @@ pub struct DiscoveredEnvironment {
     pub platform: String,
     pub board: Option<String>,
     pub framework: Option<String>,
+    /// Effective compiler flags after `extends` and `${...}` expansion, one flag per item.
     pub build_flags: Vec<String>,
+    /// `-D` macros from `build_flags`, last definition wins, with the section that set each.
+    pub defines: Vec<BuildDefine>,
     pub lib_deps: Vec<String>,
+    pub lib_extra_dirs: Vec<String>,
+    /// Libraries excluded from dependency discovery, e.g. shared libs a native env skips.
+    pub lib_ignore: Vec<String>,
+    /// `+<...>`/`-<...>` rules from `build_src_filter` (or the legacy `src_filter`).
+    pub build_src_filter: Vec<String>,
+    pub board_build: BoardBuildOptions,
+    /// Parsed and validated `board_build.partitions` table.
+    pub partitions: Option<PartitionLayout>,
+    pub upload: UploadOptions,
+    pub monitor: MonitorOptions,
+    /// Same as `monitor.speed`; kept for callers of the original flat field.
+    pub monitor_speed: Option<u32>,
+    pub test: TestOptions,
+    /// Options the dashboard does not model, keyed by their ini name.
+    pub extra_options: BTreeMap<String, String>,
     pub is_hardware_target: bool,
     pub can_upload: bool,
+    /// Whether `pio run -t buildfs` produces an image (filesystem configured or `data/` present).
+    pub has_filesystem_image: bool,
+    /// Built by a plain `pio run` (listed in `default_envs`, or no `default_envs` set).
+    pub is_default: bool,
+    /// Ini file that declares the `[env:*]` section, relative to the project.
+    pub source_file: String,
 }
 
-/// Parses a platformio.ini file with support for `extends` and variable interpolation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BuildDefine {
+    pub name: String,
+    /// `None` for a bare `-DNAME`, which the compiler treats as `1`.
+    pub value: Option<String>,
+    /// Section that wrote the flag, without the `env:` prefix (`native`, `env`, `common`).
+    pub origin_env: String,
+}
+
+/// How a profile's define relates to the value the environment already sets.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DefineStatus {
+    /// Set by the environment only.
+    Inherited,
+    /// The profile repeats the inherited value.
+    Unchanged,
+    /// The profile replaces the inherited value.
+    Overridden,
+    /// The profile introduces a define the environment does not set.
+    Added,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefineComparison {
+    pub name: String,
+    pub inherited: Option<BuildDefine>,
+    pub profile_value: Option<String>,
+    pub status: DefineStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BoardBuildOptions {
+    pub partitions: Option<String>,
+    pub filesystem: Option<String>,
+    pub flash_mode: Option<String>,
+    pub f_cpu: Option<String>,
+    pub f_flash: Option<String>,
+    /// Remaining `board_build.*` keys, without the prefix.
+    pub other: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UploadOptions {
+    pub port: Option<String>,
+    pub speed: Option<u32>,
+    pub protocol: Option<String>,
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MonitorOptions {
+    pub port: Option<String>,
+    pub speed: Option<u32>,
+    pub filters: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TestOptions {
+    /// `test_build_src`: compile the project's `src/` into the test binary.
+    pub build_src: bool,
+    pub framework: Option<String>,
+    pub filter: Vec<String>,
+    pub ignore: Vec<String>,
+}
+
+/// A problem found while resolving `${...}` references in platformio.ini.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IniDiagnostic {
+    pub kind: IniDiagnosticKind,
+    /// Section whose value contains the reference (e.g. `env:esp32`).
+    pub section: String,
+    pub option: String,
+    /// The reference exactly as written, e.g. `${common.build_flags}`.
+    pub reference: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IniDiagnosticKind {
+    UnresolvedReference,
+    InterpolationCycle,
+}
+
+/// Name of the shared base section merged into every `[env:*]` section.
+const BASE_ENV_SECTION: &str = "env";
+
+/// Parses a platformio.ini file with support for `[env]`, `extends`, `extra_configs`
+/// and variable interpolation.
 pub fn parse_platformio_ini(path: &Path) -> Result<PlatformioConfig, String> {
-    let mut ini = Ini::new();
-    ini.load(path).map_err(|e| format!("Failed to load platformio.ini: {}", e))?;
-
-    let sections = ini.sections();
-    let mut raw_envs: HashMap<String, HashMap<String, String>> = HashMap::new();
-
-    // First pass: collect all environments
-    for section in &sections {
-        if let Some(env_name) = section.strip_prefix("env:") {
-            let props = ini
-                .get_map()
-                .ok_or("Failed to get INI map")?
-                .get(section)
-                .cloned()
-                .unwrap_or_default();
+    parse_platformio_ini_with_env(path, &|name| std::env::var(name).ok())
+}
+
+/// Like `parse_platformio_ini`, but reads `${sysenv.*}` and `PLATFORMIO_CORE_DIR` through
+/// `sysenv` instead of the process environment.
+pub fn parse_platformio_ini_with_env(
+    path: &Path,
+    sysenv: &dyn Fn(&str) -> Option<String>,
+) -> Result<PlatformioConfig, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to load platformio.ini: {}", e))?;
+    let project_dir = path.parent().unwrap_or_else(|| Path::new("."));
 
+    // PlatformIO option names are case-sensitive and values may span multiple lines.
+    let mut ini = Ini::new_cs();
+    ini.set_multiline(true);
+    ini.read(content.clone())
+        .map_err(|e| format!("Failed to parse platformio.ini: {}", e))?;
+
+    // Extra configs are read once, from the main file only, and may override its options
+    let mut sources: Vec<(String, String)> = vec![(file_label(project_dir, path), content)];
+    let extra_patterns = ini
+        .get("platformio", "extra_configs")
+        .map(|v| split_multi_values(&v))
+        .unwrap_or_default();
+    for pattern in &extra_patterns {
+        for extra_path in expand_config_glob(project_dir, pattern) {
+            let extra = fs::read_to_string(&extra_path).map_err(|e| {
+                format!(
+                    "Failed to load extra config {}: {}",
+                    extra_path.display(),
+                    e
+                )
+            })?;
+            ini.read_and_append(extra.clone()).map_err(|e| {
+                format!(
+                    "Failed to parse extra config {}: {}",
+                    extra_path.display(),
+                    e
+                )
+            })?;
+            sources.push((file_label(project_dir, &extra_path), extra));
+        }
+    }
+
+    // First pass: collect every section so `[env]` and non-env `extends` targets are visible
+    let sections: HashMap<String, HashMap<String, String>> = ini
+        .get_map_ref()
+        .iter()
+        .map(|(section, props)| {
             // Convert Option<String> values to String
             let clean_props: HashMap<String, String> = props
-                .into_iter()
-                .filter_map(|(k, v)| v.map(|val| (k, val)))
+                .iter()
+                .filter_map(|(k, v)| v.as_ref().map(|val| (k.clone(), val.clone())))
                 .collect();
+            (section.clone(), clean_props)
+        })
+        .collect();
 
-            raw_envs.insert(env_name.to_string(), clean_props);
+    // Keep environments in file order so the UI lists them the way the ini does,
+    // remembering which file declared each one
+    let mut env_sources: Vec<(String, String)> = Vec::new();
+    for (label, text) in &sources {
+        for section in section_order(text) {
+            if let Some(env_name) = section.strip_prefix("env:") {
+                if !env_sources.iter().any(|(name, _)| name == env_name) {
+                    env_sources.push((env_name.to_string(), label.clone()));
+                }
+            }
         }
     }
 
-    // Second pass: resolve extends
-    let env_names: Vec<String> = raw_envs.keys().cloned().collect();
+    // Second pass: merge `[env]`, the `extends` chain and the environment's own section,
+    // expanding `${...}` references as each option is read
+    let mut interpolator = Interpolator::new(&sections, project_dir, sysenv);
     let mut resolved_envs: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut flag_lines: HashMap<String, Vec<(String, String)>> = HashMap::new();
 
-    for env_name in &env_names {
-        let resolved = resolve_extends(&raw_envs, env_name)?;
-        resolved_envs.insert(env_name.clone(), resolved);
-    }
-
-    // Third pass: resolve variable interpolation
-    for env_name in &env_names {
-        let mut props = resolved_envs.get(env_name).expect("resolved env must exist").clone();
-        resolve_interpolation(&mut props, &resolved_envs);
+    for (env_name, _) in &env_sources {
+        let section = format!("env:{}", env_name);
+        let mut props = HashMap::new();
+        for option in resolve_extends(&sections, env_name)?.into_keys() {
+            if let Ok(value) = interpolator.get(&section, &option) {
+                props.insert(option, value);
+            }
+        }
         resolved_envs.insert(env_name.clone(), props);
+
+        let lines = interpolator.trace_lines(&section, "build_flags", &mut Vec::new());
+        flag_lines.insert(env_name.clone(), lines);
     }
 
+    let default_envs = interpolator
+        .get("platformio", "default_envs")
+        .map(|v| split_multi_values(&v))
+        .unwrap_or_default();
+    let mut project_path = |option: &str| -> String {
+        let value = interpolator.get("platformio", option).unwrap_or_default();
+        resolve_project_path(project_dir, value.trim())
+    };
+    let src_dir = project_path("src_dir");
+    let data_dir = project_path("data_dir");
+    let test_dir = project_path("test_dir");
+    let diagnostics = interpolator.into_diagnostics();
+
     // Convert to DiscoveredEnvironment structs
-    let environments: Vec<DiscoveredEnvironment> = env_names
+    let has_data_dir = Path::new(&data_dir).is_dir();
+    let environments: Vec<DiscoveredEnvironment> = env_sources
         .iter()
-        .map(|name| {
+        .map(|(name, source_file)| {
             let props = resolved_envs.get(name).expect("resolved env must exist");
-            let platform = props.get("platform").cloned().unwrap_or_default();
-            let is_native = platform == "native";
-
-            DiscoveredEnvironment {
-                name: name.clone(),
-                extends: raw_envs
-                    .get(name)
-                    .and_then(|p| p.get("extends"))
-                    .map(|s| s.strip_prefix("env:").unwrap_or(s).to_string()),
-                platform: platform.clone(),
-                board: props.get("board").cloned(),
-                framework: props.get("framework").cloned(),
-                build_flags: parse_multiline_list(props.get("build_flags")),
-                lib_deps: parse_multiline_list(props.get("lib_deps")),
-                is_hardware_target: !is_native,
-                can_upload: !is_native,
+            let mut env = build_environment(name, props);
+            let (build_flags, defines) = effective_build_flags(&flag_lines[name]);
+            env.build_flags = build_flags;
+            env.defines = defines;
+            env.extends = sections
+                .get(&format!("env:{}", name))
+                .and_then(|p| p.get("extends"))
+                .map(|s| s.trim())
+                .map(|s| s.strip_prefix("env:").unwrap_or(s).to_string());
+            env.has_filesystem_image =
+                env.is_hardware_target && (env.board_build.filesystem.is_some() || has_data_dir);
+            env.is_default = default_envs.is_empty() || default_envs.contains(name);
+            env.source_file = source_file.clone();
+            env.partitions = partitions::load_layout(project_dir, &env);
+            env
+        })
+        .collect();
+
+    Ok(PlatformioConfig {
+        environments,
+        default_envs,
+        extra_configs: sources
+            .into_iter()
+            .skip(1)
+            .map(|(label, _)| label)
+            .collect(),
+        src_dir,
+        data_dir,
+        test_dir,
+        diagnostics,
+    })
+}
+
+/// Options consumed by the typed fields of `DiscoveredEnvironment`.
+const MODELED_OPTIONS: &[&str] = &[
+    "platform",
+    "board",
+    "framework",
+    "build_flags",
+    "lib_deps",
+    "lib_extra_dirs",
+    "lib_ignore",
+    "build_src_filter",
+    "src_filter",
+    "upload_port",
+    "upload_speed",
+    "upload_protocol",
+    "upload_flags",
+    "monitor_port",
+    "monitor_speed",
+    "monitor_filters",
+    "test_build_src",
+    "test_framework",
+    "test_filter",
+    "test_ignore",
+];
+
+/// Maps an environment's merged, interpolated options onto the typed model.
+///
+/// File-level details (`extends`, source file, defaults) are filled in by the caller.
+fn build_environment(name: &str, props: &HashMap<String, String>) -> DiscoveredEnvironment {
+    let get = |key: &str| props.get(key).map(|v| v.trim().to_string());
+    let get_u32 = |key: &str| props.get(key).and_then(|v| v.trim().parse().ok());
+    let get_list = |key: &str| {
+        props
+            .get(key)
+            .map(|v| split_multi_values(v))
+            .unwrap_or_default()
+    };
+
+    let platform = get("platform").unwrap_or_default();
+    let is_native = platform == "native";
+
+    let mut board_build = BoardBuildOptions::default();
+    let mut extra_options = BTreeMap::new();
+    for (key, value) in props {
+        if let Some(board_key) = key.strip_prefix("board_build.") {
+            let value = value.trim().to_string();
+            match board_key {
+                "partitions" => board_build.partitions = Some(value),
+                "filesystem" => board_build.filesystem = Some(value),
+                "flash_mode" => board_build.flash_mode = Some(value),
+                "f_cpu" => board_build.f_cpu = Some(value),
+                "f_flash" => board_build.f_flash = Some(value),
+                _ => {
+                    board_build.other.insert(board_key.to_string(), value);
+                }
+            }
+        } else if !MODELED_OPTIONS.contains(&key.as_str()) {
+            extra_options.insert(key.clone(), value.trim().to_string());
+        }
+    }
+
+    let monitor = MonitorOptions {
+        port: get("monitor_port"),
+        speed: get_u32("monitor_speed"),
+        filters: get_list("monitor_filters"),
+    };
+    let build_src_filter = get("build_src_filter")
+        .or_else(|| get("src_filter"))
+        .map(|v| parse_src_filter(&v))
+        .unwrap_or_default();
+
+    DiscoveredEnvironment {
+        name: name.to_string(),
+        extends: None,
+        platform,
+        board: get("board"),
+        framework: get("framework"),
+        build_flags: Vec::new(),
+        defines: Vec::new(),
+        lib_deps: parse_multiline_list(props.get("lib_deps")),
+        lib_extra_dirs: get_list("lib_extra_dirs"),
+        lib_ignore: get_list("lib_ignore"),
+        build_src_filter,
+        board_build,
+        partitions: None,
+        upload: UploadOptions {
+            port: get("upload_port"),
+            speed: get_u32("upload_speed"),
+            protocol: get("upload_protocol"),
+            flags: parse_multiline_list(props.get("upload_flags")),
+        },
+        monitor_speed: monitor.speed,
+        monitor,
+        test: TestOptions {
+            build_src: get("test_build_src")
+                .map(|v| matches!(v.to_lowercase().as_str(), "true" | "yes" | "1" | "on"))
+                .unwrap_or(false),
+            framework: get("test_framework"),
+            filter: get_list("test_filter"),
+            ignore: get_list("test_ignore"),
+        },
+        extra_options,
+        is_hardware_target: !is_native,
+        can_upload: !is_native,
+        has_filesystem_image: false,
+        is_default: false,
+        source_file: String::new(),
+    }
+}
+
+/// Splits traced `build_flags` lines into individual flags and collects their `-D` macros.
+///
+/// Lines are tokenized like a shell (`shlex`), `-D NAME` is joined into `-DNAME`, and a
+/// later `-D`/`-U` for the same macro replaces the earlier one, as it does for GCC.
+fn effective_build_flags(lines: &[(String, String)]) -> (Vec<String>, Vec<BuildDefine>) {
+    let mut flags = Vec::new();
+    let mut defines: Vec<BuildDefine> = Vec::new();
+
+    for (line, origin) in lines {
+        let mut tokens = split_shell_words(line).into_iter();
+        while let Some(token) = tokens.next() {
+            let flag = match token.as_str() {
+                "-D" | "-U" => match tokens.next() {
+                    Some(arg) => format!("{}{}", token, arg),
+                    None => token,
+                },
+                _ => token,
+            };
+
+            if let Some(macro_def) = flag.strip_prefix("-D") {
+                let (name, value) = match macro_def.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (macro_def, None),
+                };
+                defines.retain(|d| d.name != name);
+                defines.push(BuildDefine {
+                    name: name.to_string(),
+                    value,
+                    origin_env: origin.strip_prefix("env:").unwrap_or(origin).to_string(),
+                });
+            } else if let Some(name) = flag.strip_prefix("-U") {
+                defines.retain(|d| d.name != name);
+            }
+            flags.push(flag);
+        }
+    }
+
+    (flags, defines)
+}
+
+/// Splits a line into words using POSIX shell quoting rules.
+fn split_shell_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for q in chars.by_ref() {
+                    if q == '\'' {
+                        break;
+                    }
+                    current.push(q);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(q) = chars.next() {
+                    match q {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(e @ ('"' | '\\' | '$' | '`')) => current.push(e),
+                            Some(e) => {
+                                current.push('\\');
+                                current.push(e);
+                            }
+                            None => current.push('\\'),
+                        },
+                        _ => current.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(e) = chars.next() {
+                    current.push(e);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// Splits `-DNAME=value` build flags into a define map; bare `-DNAME` becomes `1`.
+pub fn flag_defines(build_flags: &[String]) -> HashMap<String, String> {
+    build_flags
+        .iter()
+        .filter_map(|flag| flag.strip_prefix("-D"))
+        .map(|define| match define.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (define.to_string(), "1".to_string()),
+        })
+        .collect()
+}
+
+/// Compares a profile's define overrides with the defines an environment already sets.
+///
+/// Inherited defines come first in build-flag order, followed by defines only the
+/// profile adds, sorted by name.
+pub fn compare_profile_defines(
+    env: &DiscoveredEnvironment,
+    overrides: &HashMap<String, String>,
+) -> Vec<DefineComparison> {
+    let mut comparisons: Vec<DefineComparison> = env
+        .defines
+        .iter()
+        .map(|define| {
+            let profile_value = overrides.get(&define.name).cloned();
+            let inherited_value = define.value.as_deref().unwrap_or("1");
+            let status = match profile_value.as_deref() {
+                None => DefineStatus::Inherited,
+                Some(v) if v == inherited_value => DefineStatus::Unchanged,
+                Some(_) => DefineStatus::Overridden,
+            };
+            DefineComparison {
+                name: define.name.clone(),
+                inherited: Some(define.clone()),
+                profile_value,
+                status,
             }
         })
         .collect();
 
-    Ok(PlatformioConfig { environments })
+    let mut added: Vec<(&String, &String)> = overrides
+        .iter()
+        .filter(|(name, _)| !env.defines.iter().any(|d| &d.name == *name))
+        .collect();
+    added.sort();
+    comparisons.extend(added.into_iter().map(|(name, value)| DefineComparison {
+        name: name.clone(),
+        inherited: None,
+        profile_value: Some(value.clone()),
+        status: DefineStatus::Added,
+    }));
+
+    comparisons
 }
 
-/// Recursively resolves `extends` inheritance.
-fn resolve_extends(
-    envs: &HashMap<String, HashMap<String, String>>,
-    env_name: &str,
-) -> Result<HashMap<String, String>, String> {
-    let mut visited = HashSet::new();
-    resolve_extends_internal(envs, env_name, &mut visited)
+/// Splits a `build_src_filter` value into its `+<...>` / `-<...>` rules.
+fn parse_src_filter(value: &str) -> Vec<String> {
+    static SRC_FILTER_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"[+-]<[^>]*>").expect("src filter regex"));
+
+    SRC_FILTER_RE
+        .find_iter(value)
+        .map(|m| m.as_str().to_string())
+        .collect()
+}
+
+/// Splits a multi-value option the way PlatformIO does: one item per line, or
+/// comma-separated when the value fits on a single line. `;` starts a comment.
+fn split_multi_values(value: &str) -> Vec<String> {
+    let items: Vec<&str> = if value.contains('\n') {
+        value.lines().collect()
+    } else {
+        value.split(',').collect()
+    };
+
+    items
+        .into_iter()
+        .map(|item| item.split(';').next().unwrap_or("").trim())
+        .filter(|item| !item.is_empty() && !item.starts_with('#'))
+        .map(|item| item.to_string())
+        .collect()
 }
 
-fn resolve_extends_internal(
-    envs: &HashMap<String, HashMap<String, String>>,
+/// Makes a `[platformio]` directory option absolute, relative to the project directory.
+fn resolve_project_path(project_dir: &Path, value: &str) -> String {
+    let expanded = match value.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => PathBuf::from(value),
+    };
+    if expanded.is_absolute() {
+        expanded.to_string_lossy().to_string()
+    } else {
+        project_dir.join(expanded).to_string_lossy().to_string()
+    }
+}
+
+/// Labels an ini file relative to the project directory when possible.
+fn file_label(project_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(project_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Expands an `extra_configs` pattern into existing files, sorted by path.
+///
+/// Supports `*` and `?` wildcards in any path component, matching Python's `glob.glob`
+/// closely enough for config file lists; patterns without wildcards must name a file.
+fn expand_config_glob(project_dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let pattern_path = match pattern.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => project_dir.join(pattern),
+    };
+
+    let mut candidates = vec![PathBuf::new()];
+    for component in pattern_path.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !part.contains(['*', '?']) {
+            for candidate in candidates.iter_mut() {
+                candidate.push(component.as_os_str());
+            }
+            continue;
+        }
+
+        let regex_source = format!(
+            "^{}$",
+            regex::escape(&part)
+                .replace(r"\*", "[^/]*")
+                .replace(r"\?", "[^/]")
+        );
+        let Ok(matcher) = Regex::new(&regex_source) else {
+            return Vec::new();
+        };
+
+        let mut next = Vec::new();
+        for dir in &candidates {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Like Python's glob, wildcards do not match hidden files
+                if !name.starts_with('.') && matcher.is_match(&name) {
+                    next.push(dir.join(name));
+                }
+            }
+        }
+        candidates = next;
+    }
+
+    let mut files: Vec<PathBuf> = candidates.into_iter().filter(|p| p.is_file()).collect();
+    files.sort();
+    files
+}
+
+/// Returns section names in the order they appear in the file.
+fn section_order(content: &str) -> Vec<String> {
+    static SECTION_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\s*\[([^\]]+)\]").expect("section regex"));
+
+    let mut seen = HashSet::new();
+    content
+        .lines()
+        .filter_map(|line| SECTION_RE.captures(line))
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().trim().to_string()))
+        .filter(|name| seen.insert(name.clone()))
+        .collect()
+}
+
+/// Merges the options visible to an environment using PlatformIO's precedence rules.
+///
+/// The environment's own section wins, then its `extends` chain (last listed parent first,
+/// depth-first), and finally the shared `[env]` section.
+fn resolve_extends(
+    sections: &HashMap<String, HashMap<String, String>>,
     env_name: &str,
-    visited: &mut HashSet<String>,
 ) -> Result<HashMap<String, String>, String> {
-    if !visited.insert(env_name.to_string()) {
-        return Err(format!("Circular extends detected for environment '{}'", env_name));
-    }
-
-    let props = envs
-        .get(env_name)
-        .ok_or_else(|| format!("Environment '{}' not found", env_name))?
-        .clone();
-
-    if let Some(extends) = props.get("extends") {
-        let parent_name = extends.strip_prefix("env:").unwrap_or(extends);
-        let parent_props = resolve_extends_internal(envs, parent_name, visited)?;
-
-        // Merge: child overrides parent
-        let mut merged = parent_props;
-        for (key, value) in props {
-            if key != "extends" {
-                // For multi-line values like build_flags, we might want to append
-                // For now, child completely overrides parent
-                merged.insert(key, value);
+    let root = format!("env:{}", env_name);
+    if !sections.contains_key(&root) {
+        return Err(format!("Environment '{}' not found", env_name));
+    }
+
+    let mut merged: HashMap<String, String> = HashMap::new();
+    for section in walk_sections(sections, &root)? {
+        if let Some(props) = sections.get(&section) {
+            for (key, value) in props {
+                if key != "extends" {
+                    // First section to define an option wins
+                    merged.entry(key.clone()).or_insert_with(|| value.clone());
+                }
             }
         }
-        Ok(merged)
-    } else {
-        Ok(props)
-    }
-}
-
-/// Resolves ${env.key} and ${env:name.key} interpolation.
-fn resolve_interpolation(
-    props: &mut HashMap<String, String>,
-    all_envs: &HashMap<String, HashMap<String, String>>,
-) {
-    // Replace ${env.key} and ${env:name.key} references within the environment values.
-    static ENV_INTERPOLATION_RE: Lazy<Regex> =
-        Lazy::new(|| Regex::new(r"\$\{env(?::([^.}]+))?\.([^}]+)\}").expect("env regex"));
-
-    // Iterate to resolve nested references; cap iterations to avoid infinite loops.
-    const MAX_ITERATIONS: usize = 10;
-    for _ in 0..MAX_ITERATIONS {
-        let mut changed = false;
-        let keys: Vec<String> = props.keys().cloned().collect();
-        for key in keys {
-            if let Some(value) = props.get(&key).cloned() {
-                let resolved = ENV_INTERPOLATION_RE
-                    .replace_all(&value, |caps: &regex::Captures| {
-                        let env_name = caps.get(1).map(|m| m.as_str());
-                        let ref_key = caps.get(2).map(|m| m.as_str()).unwrap_or("");
-
-                        if ref_key.is_empty() {
-                            return caps.get(0).map(|m| m.as_str()).unwrap_or("").to_string();
-                        }
-
-                        match env_name {
-                            None => props.get(ref_key).cloned().unwrap_or_default(),
-                            Some(name) => all_envs
-                                .get(name)
-                                .and_then(|e| e.get(ref_key))
-                                .cloned()
-                                .unwrap_or_default(),
-                        }
-                    })
-                    .to_string();
-
-                if resolved != value {
-                    changed = true;
-                    props.insert(key, resolved);
+    }
+    Ok(merged)
+}
+
+/// Lists the sections consulted for an environment, highest precedence first.
+///
+/// Mirrors PlatformIO's `walk_options`: `[env]` is queued before the environment so it is
+/// visited last, and each section's `extends` targets are pushed onto the same stack.
+fn walk_sections(
+    sections: &HashMap<String, HashMap<String, String>>,
+    root: &str,
+) -> Result<Vec<String>, String> {
+    check_extends_cycle(sections, root, &mut Vec::new())?;
+
+    let mut queue = vec![BASE_ENV_SECTION.to_string(), root.to_string()];
+    let mut visited: Vec<String> = Vec::new();
+
+    while let Some(section) = queue.pop() {
+        // Diamond-shaped chains reach the same parent twice; the first visit has precedence.
+        if visited.contains(&section) {
+            continue;
+        }
+        queue.extend(extends_targets(sections, &section));
+        visited.push(section);
+    }
+
+    Ok(visited)
+}
+
+fn check_extends_cycle(
+    sections: &HashMap<String, HashMap<String, String>>,
+    section: &str,
+    path: &mut Vec<String>,
+) -> Result<(), String> {
+    if path.iter().any(|s| s == section) {
+        return Err(format!(
+            "Circular extends detected for section '{}'",
+            section
+        ));
+    }
+
+    path.push(section.to_string());
+    for parent in extends_targets(sections, section) {
+        check_extends_cycle(sections, &parent, path)?;
+    }
+    path.pop();
+    Ok(())
+}
+
+/// Parses a section's `extends` option into the list of parent section names.
+fn extends_targets(
+    sections: &HashMap<String, HashMap<String, String>>,
+    section: &str,
+) -> Vec<String> {
+    sections
+        .get(section)
+        .and_then(|p| p.get("extends"))
+        .map(|extends| split_multi_values(extends))
+        .unwrap_or_default()
+}
+
+/// Default `[platformio]` directory options, expressed the way PlatformIO defines them so that
+/// overriding `core_dir` or `workspace_dir` cascades into the derived directories.
+fn platformio_dir_defaults(
+    project_dir: &Path,
+    sysenv: &dyn Fn(&str) -> Option<String>,
+) -> HashMap<String, String> {
+    let core_dir = sysenv("PLATFORMIO_CORE_DIR").unwrap_or_else(|| {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join(".platformio")
+            .to_string_lossy()
+            .to_string()
+    });
+    let project = project_dir.to_string_lossy();
+
+    [
+        ("core_dir", core_dir),
+        ("globallib_dir", "${platformio.core_dir}/lib".to_string()),
+        (
+            "platforms_dir",
+            "${platformio.core_dir}/platforms".to_string(),
+        ),
+        (
+            "packages_dir",
+            "${platformio.core_dir}/packages".to_string(),
+        ),
+        ("cache_dir", "${platformio.core_dir}/.cache".to_string()),
+        ("workspace_dir", format!("{}/.pio", project)),
+        ("build_dir", "${platformio.workspace_dir}/build".to_string()),
+        (
+            "libdeps_dir",
+            "${platformio.workspace_dir}/libdeps".to_string(),
+        ),
+        ("include_dir", format!("{}/include", project)),
+        ("src_dir", format!("{}/src", project)),
+        ("lib_dir", format!("{}/lib", project)),
+        ("data_dir", format!("{}/data", project)),
+        ("test_dir", format!("{}/test", project)),
+        ("boards_dir", format!("{}/boards", project)),
+        ("monitor_dir", format!("{}/monitor", project)),
+        ("shared_dir", format!("{}/shared", project)),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect()
+}
+
+enum LookupError {
+    Missing,
+    Cycle,
+}
+
+/// Expands `${section.option}`, `${this.option}`, `${platformio.*}` and `${sysenv.*}`
+/// references following PlatformIO's `ProjectConfig` semantics.
+///
+/// References inside `[env:*]` values see the environment's merged view (`[env]` and
+/// `extends` included). Section-less `${VAR}` references belong to SCons and are left alone.
+struct Interpolator<'a> {
+    sections: &'a HashMap<String, HashMap<String, String>>,
+    sysenv: &'a dyn Fn(&str) -> Option<String>,
+    platformio_defaults: HashMap<String, String>,
+    cache: HashMap<(String, String), String>,
+    stack: Vec<(String, String)>,
+    diagnostics: Vec<IniDiagnostic>,
+}
+
+impl<'a> Interpolator<'a> {
+    fn new(
+        sections: &'a HashMap<String, HashMap<String, String>>,
+        project_dir: &Path,
+        sysenv: &'a dyn Fn(&str) -> Option<String>,
+    ) -> Self {
+        Self {
+            sections,
+            sysenv,
+            platformio_defaults: platformio_dir_defaults(project_dir, sysenv),
+            cache: HashMap::new(),
+            stack: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn into_diagnostics(self) -> Vec<IniDiagnostic> {
+        self.diagnostics
+    }
+
+    /// Returns the fully expanded value of an option.
+    fn get(&mut self, section: &str, option: &str) -> Result<String, LookupError> {
+        let key = (section.to_string(), option.to_string());
+        if let Some(value) = self.cache.get(&key) {
+            return Ok(value.clone());
+        }
+        if self.stack.contains(&key) {
+            return Err(LookupError::Cycle);
+        }
+
+        let raw = self
+            .raw_value(section, option)
+            .ok_or(LookupError::Missing)?;
+        self.stack.push(key.clone());
+        let value = self.expand(section, option, &raw);
+        self.stack.pop();
+
+        self.cache.insert(key, value.clone());
+        Ok(value)
+    }
+
+    fn raw_value(&self, section: &str, option: &str) -> Option<String> {
+        self.raw_value_with_owner(section, option)
+            .map(|(value, _)| value)
+    }
+
+    /// Returns an option's raw value together with the section that actually defines it.
+    fn raw_value_with_owner(&self, section: &str, option: &str) -> Option<(String, String)> {
+        if section.starts_with("env:") {
+            // Cycles in `extends` are rejected before interpolation starts.
+            let walk = walk_sections(self.sections, section).ok()?;
+            return walk.into_iter().find_map(|s| {
+                let value = self.sections.get(&s).and_then(|p| p.get(option))?.clone();
+                Some((value, s))
+            });
+        }
+
+        self.sections
+            .get(section)
+            .and_then(|p| p.get(option))
+            .cloned()
+            .or_else(|| {
+                if section == "platformio" {
+                    self.platformio_defaults.get(option).cloned()
+                } else {
+                    None
+                }
+            })
+            .map(|value| (value, section.to_string()))
+    }
+
+    /// Expands a multi-line option line by line, tagging each line with the section that
+    /// wrote it. A line consisting solely of `${section.option}` is replaced by the traced
+    /// lines of the referenced option, so inherited flags keep their original section.
+    fn trace_lines(
+        &mut self,
+        section: &str,
+        option: &str,
+        stack: &mut Vec<(String, String)>,
+    ) -> Vec<(String, String)> {
+        static WHOLE_REFERENCE_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^\$\{([^.}()]+)\.([^}]+)\}$").expect("whole reference regex")
+        });
+
+        let Some((raw, owner)) = self.raw_value_with_owner(section, option) else {
+            return Vec::new();
+        };
+        stack.push((section.to_string(), option.to_string()));
+
+        let mut lines = Vec::new();
+        for line in raw.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            if let Some(caps) = WHOLE_REFERENCE_RE.captures(line) {
+                let ref_section = match &caps[1] {
+                    "this" => section.to_string(),
+                    other => other.to_string(),
+                };
+                let target = (ref_section, caps[2].to_string());
+                if target.0 != "sysenv"
+                    && target.1 != "__env__"
+                    && !stack.contains(&target)
+                    && self.raw_value(&target.0, &target.1).is_some()
+                {
+                    lines.extend(self.trace_lines(&target.0, &target.1, stack));
+                    continue;
                 }
             }
+            lines.push((self.expand(section, option, line), owner.clone()));
         }
 
-        if !changed {
-            break;
+        stack.pop();
+        lines
+    }
+
+    fn expand(&mut self, section: &str, option: &str, raw: &str) -> String {
+        static INTERPOLATION_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"\$\{(?:([^.}()]+)\.)?([^}]+)\}").expect("interpolation regex")
+        });
+
+        INTERPOLATION_RE
+            .replace_all(raw, |caps: &regex::Captures| {
+                let reference = caps.get(0).map(|m| m.as_str()).unwrap_or("");
+                let ref_option = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+
+                let ref_section = match caps.get(1).map(|m| m.as_str()) {
+                    // SCons construction variable such as ${BUILD_DIR}
+                    None => return reference.to_string(),
+                    Some("sysenv") => {
+                        return match (self.sysenv)(ref_option) {
+                            Some(value) => value,
+                            None => {
+                                self.report(
+                                    IniDiagnosticKind::UnresolvedReference,
+                                    section,
+                                    option,
+                                    reference,
+                                    format!("Environment variable '{}' is not set", ref_option),
+                                );
+                                reference.to_string()
+                            }
+                        };
+                    }
+                    Some("this") if ref_option == "__env__" => {
+                        return match section.strip_prefix("env:") {
+                            Some(env_name) => env_name.to_string(),
+                            None => {
+                                self.report(
+                                    IniDiagnosticKind::UnresolvedReference,
+                                    section,
+                                    option,
+                                    reference,
+                                    "${this.__env__} is only valid inside [env:*] sections"
+                                        .to_string(),
+                                );
+                                reference.to_string()
+                            }
+                        };
+                    }
+                    Some("this") => section.to_string(),
+                    Some(other) => other.to_string(),
+                };
+
+                match self.get(&ref_section, ref_option) {
+                    Ok(value) => value.trim().to_string(),
+                    Err(LookupError::Missing) => {
+                        self.report(
+                            IniDiagnosticKind::UnresolvedReference,
+                            section,
+                            option,
+                            reference,
+                            format!("Option '{}' not found in [{}]", ref_option, ref_section),
+                        );
+                        reference.to_string()
+                    }
+                    Err(LookupError::Cycle) => {
+                        self.report(
+                            IniDiagnosticKind::InterpolationCycle,
+                            section,
+                            option,
+                            reference,
+                            format!(
+                                "Circular reference: [{}] {} refers back to itself",
+                                ref_section, ref_option
+                            ),
+                        );
+                        reference.to_string()
+                    }
+                }
+            })
+            .to_string()
+    }
+
+    fn report(
+        &mut self,
+        kind: IniDiagnosticKind,
+        section: &str,
+        option: &str,
+        reference: &str,
+        message: String,
+    ) {
+        let diagnostic = IniDiagnostic {
+            kind,
+            section: section.to_string(),
+            option: option.to_string(),
+            reference: reference.to_string(),
+            message,
+        };
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
         }
     }
-    // If still changing after MAX_ITERATIONS, we stop to avoid infinite interpolation loops.
 }
 
 /// Parses a multiline INI value into a list of items.
@@ -297,7 +1141,7 @@ lib_deps = lib1
 
 [env:child]
 extends = env:base
-framework = ${env.platform}
+framework = ${this.platform}
 lib_deps = ${env:base.lib_deps}
 "#,
         )
@@ -359,11 +1203,728 @@ extra = ${env.build_flags}
             .iter()
             .find(|e| e.name == "dev")
             .unwrap();
-        // Build flags on one line become one item after parsing
-        assert!(!env.build_flags.is_empty());
+        // Flags written on one line are split into individual flags
+        assert_eq!(env.build_flags.len(), 2);
         let flags_str = env.build_flags.join(" ");
-        assert!(flags_str.contains("-DFOO=1"), "Should contain interpolated FOO flag");
+        assert!(
+            flags_str.contains("-DFOO=1"),
+            "Should contain interpolated FOO flag"
+        );
         assert!(flags_str.contains("-DBAR=2"), "Should contain BAR flag");
         assert!(env.lib_deps.is_empty());
     }
+
+    #[test]
+    fn test_base_env_section_merged() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+; Header comment
+[env]
+lib_extra_dirs = ../../shared/lib
+monitor_speed = 115200
+
+[env:esp32]
+platform = espressif32
+board = esp32dev
+build_flags =
+    -std=gnu++17
+    -DCORE_DEBUG_LEVEL=3
+lib_deps =
+    makuna/NeoPixelBus@^2.8.0
+
+[env:native]
+platform = native
+monitor_speed = 9600
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let names: Vec<&str> = config
+            .environments
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["esp32", "native"]);
+
+        let esp32 = &config.environments[0];
+        assert_eq!(esp32.lib_extra_dirs, vec!["../../shared/lib".to_string()]);
+        assert_eq!(esp32.monitor_speed, Some(115200));
+        assert_eq!(
+            esp32.build_flags,
+            vec!["-std=gnu++17", "-DCORE_DEBUG_LEVEL=3"]
+        );
+        assert_eq!(esp32.lib_deps, vec!["makuna/NeoPixelBus@^2.8.0"]);
+
+        let native = &config.environments[1];
+        assert_eq!(native.monitor_speed, Some(9600));
+        assert_eq!(native.lib_extra_dirs, vec!["../../shared/lib".to_string()]);
+    }
+
+    #[test]
+    fn test_extends_precedence_over_base_env() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env]
+framework = arduino
+board = base-board
+monitor_speed = 115200
+
+[common]
+board = common-board
+monitor_speed = 57600
+
+[env:esp32]
+extends = common
+platform = espressif32
+monitor_speed = 921600
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let env = &config.environments[0];
+
+        assert_eq!(env.framework, Some("arduino".to_string()));
+        assert_eq!(env.board, Some("common-board".to_string()));
+        assert_eq!(env.monitor_speed, Some(921600));
+        assert_eq!(env.monitor.speed, env.monitor_speed);
+        assert_eq!(env.extends, Some("common".to_string()));
+    }
+
+    #[test]
+    fn test_interpolates_arbitrary_sections() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[common]
+build_flags =
+    -DFOO=1
+    -DBAR=2
+
+[env]
+monitor_speed = 115200
+
+[env:native]
+platform = native
+build_flags =
+    ${common.build_flags}
+    -DNATIVE_BUILD
+monitor_filters = ${env.monitor_speed}
+
+[env:native-progressive-h]
+extends = env:native
+build_flags =
+    ${env:native.build_flags}
+    -DWIRING_PATTERN=1
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+
+        let native = &config.environments[0];
+        assert_eq!(
+            native.build_flags,
+            vec!["-DFOO=1", "-DBAR=2", "-DNATIVE_BUILD"]
+        );
+
+        let child = &config.environments[1];
+        assert_eq!(
+            child.build_flags,
+            vec!["-DFOO=1", "-DBAR=2", "-DNATIVE_BUILD", "-DWIRING_PATTERN=1"]
+        );
+    }
+
+    #[test]
+    fn test_interpolates_platformio_and_this() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[platformio]
+core_dir = /opt/pio
+
+[env:esp32]
+platform = espressif32
+board = ${platformio.packages_dir}/${this.__env__}
+framework = ${this.platform}
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let env = &config.environments[0];
+        assert_eq!(env.board, Some("/opt/pio/packages/esp32".to_string()));
+        assert_eq!(env.framework, Some("espressif32".to_string()));
+    }
+
+    #[test]
+    fn test_interpolates_sysenv() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:esp32]
+platform = espressif32
+board = ${sysenv.RGBW_DASHBOARD_TEST_BOARD}
+upload_port = ${sysenv.RGBW_DASHBOARD_TEST_UNSET}
+"#,
+        )
+        .unwrap();
+
+        let vars = HashMap::from([(
+            "RGBW_DASHBOARD_TEST_BOARD".to_string(),
+            "esp32-s3-devkitc-1".to_string(),
+        )]);
+        let config =
+            parse_platformio_ini_with_env(&ini_path, &|name| vars.get(name).cloned()).unwrap();
+        assert_eq!(
+            config.environments[0].board,
+            Some("esp32-s3-devkitc-1".to_string())
+        );
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.reference == "${sysenv.RGBW_DASHBOARD_TEST_UNSET}"));
+    }
+
+    #[test]
+    fn test_scons_variables_left_untouched() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:esp32]
+platform = espressif32
+build_flags = -I${PROJECT_DIR}/include
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        assert!(config.diagnostics.is_empty());
+        assert_eq!(
+            config.environments[0].build_flags,
+            vec!["-I${PROJECT_DIR}/include"]
+        );
+    }
+
+    #[test]
+    fn test_unresolved_reference_reported() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:esp32]
+platform = espressif32
+build_flags =
+    ${common.build_flags}
+    -DFOO=1
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let env = &config.environments[0];
+        // The reference is kept verbatim instead of silently becoming an empty string
+        assert_eq!(env.build_flags, vec!["${common.build_flags}", "-DFOO=1"]);
+
+        assert_eq!(config.diagnostics.len(), 1);
+        let diagnostic = &config.diagnostics[0];
+        assert_eq!(diagnostic.kind, IniDiagnosticKind::UnresolvedReference);
+        assert_eq!(diagnostic.section, "env:esp32");
+        assert_eq!(diagnostic.option, "build_flags");
+        assert_eq!(diagnostic.reference, "${common.build_flags}");
+    }
+
+    #[test]
+    fn test_interpolation_cycle_reported() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:esp32]
+platform = espressif32
+build_flags = ${this.lib_deps}
+lib_deps = ${this.build_flags}
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        assert!(config
+            .diagnostics
+            .iter()
+            .any(|d| d.kind == IniDiagnosticKind::InterpolationCycle));
+    }
+
+    #[test]
+    fn test_platformio_section_options() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[platformio]
+default_envs = esp32
+src_dir = firmware
+
+[env:esp32]
+platform = espressif32
+
+[env:native]
+platform = native
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        assert_eq!(config.default_envs, vec!["esp32".to_string()]);
+        assert_eq!(
+            config.src_dir,
+            temp.path().join("firmware").to_string_lossy().to_string()
+        );
+        assert_eq!(
+            config.data_dir,
+            temp.path().join("data").to_string_lossy().to_string()
+        );
+        assert_eq!(
+            config.test_dir,
+            temp.path().join("test").to_string_lossy().to_string()
+        );
+
+        let esp32 = &config.environments[0];
+        let native = &config.environments[1];
+        assert!(esp32.is_default);
+        assert!(!native.is_default);
+        assert_eq!(esp32.source_file, "platformio.ini");
+    }
+
+    #[test]
+    fn test_all_envs_default_without_default_envs() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:esp32]
+platform = espressif32
+
+[env:native]
+platform = native
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        assert!(config.default_envs.is_empty());
+        assert!(config.environments.iter().all(|e| e.is_default));
+    }
+
+    #[test]
+    fn test_extra_configs_glob() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[platformio]
+extra_configs =
+    envs/*.ini
+    missing.ini
+default_envs = esp32, booth
+
+[env]
+monitor_speed = 115200
+
+[env:esp32]
+platform = espressif32
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(temp.path().join("envs")).unwrap();
+        fs::write(
+            temp.path().join("envs/booth.ini"),
+            r#"
+[env:booth]
+extends = env:esp32
+board = esp32dev
+"#,
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join("envs/override.ini"),
+            r#"
+[env:esp32]
+board = esp32-s3-devkitc-1
+"#,
+        )
+        .unwrap();
+        fs::write(temp.path().join("envs/notes.txt"), "[env:ignored]").unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        assert_eq!(
+            config.extra_configs,
+            vec![
+                "envs/booth.ini".to_string(),
+                "envs/override.ini".to_string()
+            ]
+        );
+        assert_eq!(config.default_envs, vec!["esp32", "booth"]);
+
+        let names: Vec<&str> = config
+            .environments
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["esp32", "booth"]);
+
+        let esp32 = &config.environments[0];
+        assert_eq!(esp32.source_file, "platformio.ini");
+        assert_eq!(esp32.board, Some("esp32-s3-devkitc-1".to_string()));
+
+        let booth = &config.environments[1];
+        assert_eq!(booth.source_file, "envs/booth.ini");
+        assert_eq!(booth.platform, "espressif32");
+        assert_eq!(booth.board, Some("esp32dev".to_string()));
+        assert_eq!(booth.monitor.speed, Some(115200));
+        assert!(booth.is_default);
+    }
+
+    /// The checked-in platformio.ini of one of the monorepo's apps.
+    fn app_ini(app: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../../apps")
+            .join(app)
+            .join("platformio.ini")
+    }
+
+    #[test]
+    fn test_dj_booth_option_model() {
+        let config = parse_platformio_ini(&app_ini("dj-booth")).unwrap();
+        let names: Vec<&str> = config
+            .environments
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["esp32", "native"]);
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+
+        let esp32 = &config.environments[0];
+        assert_eq!(esp32.board, Some("esp32dev".to_string()));
+        assert_eq!(
+            esp32.board_build.partitions,
+            Some("partitions.csv".to_string())
+        );
+        assert_eq!(esp32.board_build.filesystem, Some("littlefs".to_string()));
+        assert!(esp32.board_build.other.is_empty());
+        assert!(esp32.partitions.is_some());
+        assert_eq!(esp32.monitor.speed, Some(115200));
+        assert_eq!(esp32.monitor_speed, Some(115200));
+        assert_eq!(esp32.upload, UploadOptions::default());
+        assert_eq!(esp32.lib_deps, vec!["makuna/NeoPixelBus@^2.8.0"]);
+        assert!(esp32.has_filesystem_image);
+        assert!(esp32.extra_options.is_empty(), "{:?}", esp32.extra_options);
+
+        let native = &config.environments[1];
+        assert_eq!(
+            native.build_src_filter,
+            vec![
+                "-<*>",
+                "+<main.cpp>",
+                "+<matrix.cpp>",
+                "+<state.cpp>",
+                "+<control.cpp>",
+                "+<effects.cpp>",
+                "+<audio.cpp>",
+                "+<web_ui.cpp>",
+                "+<matter_ep.cpp>",
+            ]
+        );
+        assert!(native.test.build_src);
+        assert_eq!(
+            native.lib_ignore,
+            vec![
+                "LEDStrip",
+                "NeoPixelBus",
+                "LEDPWM",
+                "Connectivity",
+                "AudioInput"
+            ]
+        );
+        assert!(!native.is_hardware_target);
+        assert!(!native.has_filesystem_image);
+        assert!(
+            native.extra_options.is_empty(),
+            "{:?}",
+            native.extra_options
+        );
+    }
+
+    #[test]
+    fn test_led_panel_option_model() {
+        let config = parse_platformio_ini(&app_ini("led-panel")).unwrap();
+        let names: Vec<&str> = config
+            .environments
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "esp32",
+                "esp8266",
+                "native",
+                "native-progressive-h",
+                "native-serpentine-v"
+            ]
+        );
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+
+        for env in &config.environments {
+            assert_eq!(env.monitor.speed, Some(115200), "{}", env.name);
+            assert!(env.lib_extra_dirs.contains(&"../../shared/lib".to_string()));
+            assert_eq!(
+                env.board_build,
+                BoardBuildOptions::default(),
+                "{}",
+                env.name
+            );
+            assert!(
+                env.extra_options.is_empty(),
+                "{}: {:?}",
+                env.name,
+                env.extra_options
+            );
+            // No filesystem configured and no data/ directory
+            assert!(!env.has_filesystem_image, "{}", env.name);
+        }
+
+        let esp8266 = &config.environments[1];
+        assert_eq!(esp8266.board, Some("nodemcuv2".to_string()));
+        assert_eq!(
+            esp8266.lib_ignore,
+            vec!["LEDPWM", "Connectivity", "AudioInput"]
+        );
+        assert!(esp8266.can_upload);
+
+        let native = &config.environments[2];
+        assert_eq!(native.build_src_filter, vec!["-<*>", "+<matrix.cpp>"]);
+        assert_eq!(native.lib_deps, vec!["throwtheswitch/Unity@^2.6.0"]);
+
+        // Test options come through `extends = env:native`
+        let progressive = &config.environments[3];
+        assert_eq!(progressive.extends, Some("native".to_string()));
+        assert!(progressive.test.build_src);
+        assert_eq!(progressive.build_src_filter, native.build_src_filter);
+        assert!(progressive
+            .defines
+            .iter()
+            .any(|d| d.name == "WIRING_PATTERN"
+                && d.value.as_deref() == Some("1")
+                && d.origin_env == "native-progressive-h"));
+    }
+
+    #[test]
+    fn test_upload_monitor_and_board_build_options() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:esp32]
+platform = espressif32
+board = esp32dev
+framework = arduino
+upload_speed = 921600
+upload_protocol = esptool
+monitor_filters = esp32_exception_decoder, time
+board_build.f_flash = 80000000L
+board_build.arduino.memory_type = qio_qspi
+custom_panel_name = booth
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let esp32 = &config.environments[0];
+        assert_eq!(esp32.upload.speed, Some(921600));
+        assert_eq!(esp32.upload.protocol, Some("esptool".to_string()));
+        assert_eq!(
+            esp32.monitor.filters,
+            vec!["esp32_exception_decoder", "time"]
+        );
+        assert_eq!(esp32.monitor_speed, None);
+        assert_eq!(esp32.board_build.f_flash, Some("80000000L".to_string()));
+        assert_eq!(
+            esp32.board_build.other.get("arduino.memory_type"),
+            Some(&"qio_qspi".to_string())
+        );
+        assert_eq!(
+            esp32.extra_options.get("custom_panel_name"),
+            Some(&"booth".to_string())
+        );
+    }
+
+    #[test]
+    fn test_defines_track_origin_through_extends() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env]
+build_flags = -DBASE_FLAG
+
+[env:native]
+platform = native
+build_flags =
+    -std=c++17
+    -DNATIVE_BUILD
+
+[env:native-progressive-h]
+extends = env:native
+build_flags =
+    ${env:native.build_flags}
+    -DWIRING_PATTERN=1
+
+[env:native-inherited]
+extends = env:native
+
+[env:base-only]
+platform = native
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let find = |name: &str| config.environments.iter().find(|e| e.name == name).unwrap();
+
+        let progressive = find("native-progressive-h");
+        assert_eq!(
+            progressive.build_flags,
+            vec!["-std=c++17", "-DNATIVE_BUILD", "-DWIRING_PATTERN=1"]
+        );
+        assert_eq!(
+            progressive.defines,
+            vec![
+                BuildDefine {
+                    name: "NATIVE_BUILD".to_string(),
+                    value: None,
+                    origin_env: "native".to_string(),
+                },
+                BuildDefine {
+                    name: "WIRING_PATTERN".to_string(),
+                    value: Some("1".to_string()),
+                    origin_env: "native-progressive-h".to_string(),
+                },
+            ]
+        );
+
+        // Without its own build_flags the child uses the parent's verbatim
+        let inherited = find("native-inherited");
+        assert_eq!(inherited.build_flags, vec!["-std=c++17", "-DNATIVE_BUILD"]);
+        assert_eq!(inherited.defines[0].origin_env, "native");
+
+        let base_only = find("base-only");
+        assert_eq!(base_only.defines[0].name, "BASE_FLAG");
+        assert_eq!(base_only.defines[0].origin_env, "env");
+    }
+
+    #[test]
+    fn test_effective_flags_tokenize_like_shell() {
+        let lines = vec![
+            (
+                r#"-D PANEL_WIDTH=48 -DWIFI_SSID=\"booth\" -DLABEL='two words'"#.to_string(),
+                "env:esp32".to_string(),
+            ),
+            (
+                "-DPANEL_WIDTH=64 -UDEBUG".to_string(),
+                "env:esp32".to_string(),
+            ),
+            ("-DDEBUG -UDEBUG".to_string(), "common".to_string()),
+        ];
+
+        let (flags, defines) = effective_build_flags(&lines);
+        assert_eq!(
+            flags,
+            vec![
+                "-DPANEL_WIDTH=48",
+                r#"-DWIFI_SSID="booth""#,
+                "-DLABEL=two words",
+                "-DPANEL_WIDTH=64",
+                "-UDEBUG",
+                "-DDEBUG",
+                "-UDEBUG",
+            ]
+        );
+
+        let names: Vec<&str> = defines.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["WIFI_SSID", "LABEL", "PANEL_WIDTH"]);
+        assert_eq!(defines[2].value, Some("64".to_string()));
+        assert_eq!(defines[0].origin_env, "esp32");
+    }
+
+    #[test]
+    fn test_compare_profile_defines() {
+        let temp = tempdir().unwrap();
+        let ini_path = temp.path().join("platformio.ini");
+        fs::write(
+            &ini_path,
+            r#"
+[env:native]
+platform = native
+build_flags =
+    -DNATIVE_BUILD
+    -DWIRING_PATTERN=1
+    -DCORE_DEBUG_LEVEL=3
+"#,
+        )
+        .unwrap();
+
+        let config = parse_platformio_ini(&ini_path).unwrap();
+        let overrides: HashMap<String, String> = [
+            ("WIRING_PATTERN", "2"),
+            ("CORE_DEBUG_LEVEL", "3"),
+            ("PANEL_WIDTH", "48"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let comparison = compare_profile_defines(&config.environments[0], &overrides);
+        let statuses: Vec<(&str, DefineStatus)> = comparison
+            .iter()
+            .map(|c| (c.name.as_str(), c.status.clone()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("NATIVE_BUILD", DefineStatus::Inherited),
+                ("WIRING_PATTERN", DefineStatus::Overridden),
+                ("CORE_DEBUG_LEVEL", DefineStatus::Unchanged),
+                ("PANEL_WIDTH", DefineStatus::Added),
+            ]
+        );
+        assert_eq!(comparison[1].profile_value, Some("2".to_string()));
+        assert_eq!(
+            comparison[1].inherited.as_ref().unwrap().value,
+            Some("1".to_string())
+        );
+    }
 }
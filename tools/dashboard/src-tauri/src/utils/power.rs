@@ -0,0 +1,450 @@
+use crate::utils::config_schema::{self, EnumValue, ResolvedConfig};
+use crate::utils::pio_parser::DiscoveredEnvironment;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// LED strip families the dashboard can budget for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StripKind {
+    /// SK6812 RGBW, 5V, ~80 mA per pixel at full white.
+    Sk6812Rgbw,
+    /// WS2815B RGB, 12V, ~30 mA per pixel at full white.
+    Ws2815bRgb,
+    /// WS2812B RGB, 5V, ~60 mA per pixel at full white (~20 mA per channel x 3).
+    Ws2812bRgb,
+}
+
+impl StripKind {
+    pub fn voltage(&self) -> f64 {
+        match self {
+            StripKind::Ws2815bRgb => 12.0,
+            StripKind::Sk6812Rgbw | StripKind::Ws2812bRgb => 5.0,
+        }
+    }
+
+    pub fn ma_per_pixel(&self) -> f64 {
+        match self {
+            StripKind::Sk6812Rgbw => 80.0,
+            StripKind::Ws2815bRgb => 30.0,
+            StripKind::Ws2812bRgb => 60.0,
+        }
+    }
+
+    /// Maps a `STRIP_TYPE` value from config.h. Named constants such as
+    /// `STRIP_TYPE_WS2812_RGB` win, then bare numbers are looked up in the app's own
+    /// `STRIP_TYPE` options; the rest follow the `StripType` enum in LEDStrip.
+    pub fn from_define(expression: &str, value: Option<i64>, named: &[EnumValue]) -> Option<Self> {
+        if let Some(kind) = Self::from_name(expression) {
+            return Some(kind);
+        }
+        let value = value?;
+        let label = named
+            .iter()
+            .find(|option| option.value.trim().parse::<i64>() == Ok(value))
+            .map(|option| option.label.as_str());
+        if let Some(kind) = label.and_then(Self::from_name) {
+            return Some(kind);
+        }
+        match value {
+            0 => Some(StripKind::Sk6812Rgbw),
+            1 => Some(StripKind::Ws2815bRgb),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        let upper = name.to_uppercase();
+        if upper.contains("SK6812") {
+            Some(StripKind::Sk6812Rgbw)
+        } else if upper.contains("WS2815") {
+            Some(StripKind::Ws2815bRgb)
+        } else if upper.contains("WS2812") {
+            Some(StripKind::Ws2812bRgb)
+        } else {
+            None
+        }
+    }
+}
+
+/// User-supplied wiring details for a power budget.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerOptions {
+    /// Brightness cap passed to `setBrightness()`, 0-255.
+    pub brightness_cap: u8,
+    /// Gauge of the feed wire from the PSU to the strip.
+    pub wire_awg: u8,
+    /// One-way length of the feed wire in meters.
+    pub run_length_m: f64,
+    /// Pixel density, used to work out the strip length. Defaults to 60/m.
+    #[serde(default)]
+    pub leds_per_meter: Option<f64>,
+    /// Overrides the strip type read from config.h.
+    #[serde(default)]
+    pub strip_type: Option<StripKind>,
+    /// Rating of the PSU actually in use, if known.
+    #[serde(default)]
+    pub psu_amps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireCheck {
+    pub awg: u8,
+    pub ohms_per_meter: f64,
+    pub ampacity: f64,
+    /// Round-trip drop through + and - at the capped worst-case current.
+    pub voltage_drop: f64,
+    pub drop_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerReport {
+    pub strip_type: StripKind,
+    pub num_pixels: u32,
+    pub voltage: f64,
+    /// Full white at 100% brightness.
+    pub max_amps: f64,
+    /// Full white at the brightness cap.
+    pub worst_case_amps: f64,
+    /// Average pattern load at the brightness cap.
+    pub typical_amps: f64,
+    pub worst_case_watts: f64,
+    /// Smallest standard PSU rating with 20% headroom over the worst case.
+    pub psu_amps: f64,
+    pub psu_watts: f64,
+    /// Smallest standard fuse at or above 125% of the worst case.
+    pub fuse_amps: f64,
+    pub wire: WireCheck,
+    pub strip_length_m: f64,
+    /// Recommended feed positions along the strip, in meters from the head.
+    pub injection_points_m: Vec<f64>,
+    pub warnings: Vec<String>,
+}
+
+/// Typical animated patterns average about a third of full white.
+const TYPICAL_LOAD_FACTOR: f64 = 1.0 / 3.0;
+const PSU_HEADROOM: f64 = 1.2;
+const FUSE_FACTOR: f64 = 1.25;
+/// Target maximum voltage drop as a fraction of the supply.
+const MAX_DROP_FRACTION: f64 = 0.05;
+const DEFAULT_LEDS_PER_METER: f64 = 60.0;
+/// Current rating of the JST-SM connectors pre-installed on most strips.
+const JST_SM_AMPS: f64 = 3.0;
+
+const PSU_SIZES_AMPS: &[f64] = &[
+    1.0, 2.0, 2.5, 3.0, 4.0, 5.0, 6.0, 8.0, 10.0, 12.0, 15.0, 20.0, 25.0, 30.0, 40.0, 50.0, 60.0,
+];
+const FUSE_SIZES_AMPS: &[f64] = &[
+    1.0, 2.0, 3.0, 4.0, 5.0, 7.5, 10.0, 15.0, 20.0, 25.0, 30.0, 35.0, 40.0,
+];
+
+/// (AWG, ohm/m, chassis wiring ampacity), from docs/power-and-wiring.md.
+const AWG_TABLE: &[(u8, f64, f64)] = &[
+    (14, 0.0083, 15.0),
+    (16, 0.0132, 10.0),
+    (18, 0.0210, 7.0),
+    (20, 0.0333, 5.0),
+    (22, 0.0530, 3.0),
+    (24, 0.0842, 2.0),
+];
+
+/// Reads the pixel count (`NUM_PIXELS` or `NUM_LEDS`) and strip type from a resolved config.
+/// `strip_types` are the `STRIP_TYPE` options from the app's config schema.
+pub fn config_inputs(
+    config: &ResolvedConfig,
+    strip_types: &[EnumValue],
+) -> Result<(u32, Option<StripKind>), String> {
+    let find = |name: &str| config.values.iter().find(|d| d.name == name);
+
+    let num_pixels = find("NUM_PIXELS")
+        .or_else(|| find("NUM_LEDS"))
+        .and_then(|d| d.value)
+        .ok_or("Config does not resolve NUM_PIXELS or NUM_LEDS to a number")?;
+    let num_pixels =
+        u32::try_from(num_pixels).map_err(|_| format!("Invalid pixel count: {}", num_pixels))?;
+
+    let strip_type = find("STRIP_TYPE")
+        .and_then(|d| StripKind::from_define(&d.expression, d.value, strip_types));
+    Ok((num_pixels, strip_type))
+}
+
+/// Resolves an app's config for an environment and `-D` overrides, then computes its
+/// power budget. `options.strip_type` takes precedence over `STRIP_TYPE` in config.h.
+pub fn app_power_budget(
+    app_path: &Path,
+    env: Option<&DiscoveredEnvironment>,
+    defines: &HashMap<String, String>,
+    options: &PowerOptions,
+) -> Result<PowerReport, String> {
+    let mut macros = env
+        .map(config_schema::environment_macros)
+        .unwrap_or_default();
+    macros.extend(defines.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    let config = config_schema::resolve_config_values(app_path, &macros)?;
+    let (num_pixels, config_strip) = config_inputs(&config, &strip_type_options(app_path))?;
+    let strip_type = options
+        .strip_type
+        .or(config_strip)
+        .ok_or("Could not determine the strip type from STRIP_TYPE; choose one explicitly")?;
+    calculate_power(strip_type, num_pixels, options)
+}
+
+/// The named `STRIP_TYPE` values config.h offers, e.g. `STRIP_TYPE_WS2812_RGB` = 1.
+fn strip_type_options(app_path: &Path) -> Vec<EnumValue> {
+    let Ok(schema) = config_schema::parse_config_schema(app_path) else {
+        return Vec::new();
+    };
+    schema
+        .defines
+        .into_iter()
+        .chain(schema.platform_conditional.into_values().flatten())
+        .find(|define| define.name == "STRIP_TYPE")
+        .and_then(|define| define.enum_values)
+        .unwrap_or_default()
+}
+
+/// Computes the power budget for a strip of `num_pixels`.
+pub fn calculate_power(
+    strip_type: StripKind,
+    num_pixels: u32,
+    options: &PowerOptions,
+) -> Result<PowerReport, String> {
+    let (_, ohms_per_meter, ampacity) = AWG_TABLE
+        .iter()
+        .copied()
+        .find(|(awg, _, _)| *awg == options.wire_awg)
+        .ok_or_else(|| {
+            format!(
+                "Unsupported wire gauge {} AWG (expected 14-24 in even steps)",
+                options.wire_awg
+            )
+        })?;
+    if !(options.run_length_m >= 0.0 && options.run_length_m.is_finite()) {
+        return Err("Run length must be a non-negative number of meters".to_string());
+    }
+    let leds_per_meter = options.leds_per_meter.unwrap_or(DEFAULT_LEDS_PER_METER);
+    if leds_per_meter <= 0.0 || !leds_per_meter.is_finite() {
+        return Err("LEDs per meter must be positive".to_string());
+    }
+
+    let voltage = strip_type.voltage();
+    let max_amps = num_pixels as f64 * strip_type.ma_per_pixel() / 1000.0;
+    let worst_case_amps = max_amps * options.brightness_cap as f64 / 255.0;
+    let typical_amps = worst_case_amps * TYPICAL_LOAD_FACTOR;
+    let psu_amps = next_size(PSU_SIZES_AMPS, worst_case_amps * PSU_HEADROOM);
+    let fuse_amps = next_size(FUSE_SIZES_AMPS, worst_case_amps * FUSE_FACTOR);
+
+    let voltage_drop = worst_case_amps * 2.0 * options.run_length_m * ohms_per_meter;
+    let wire = WireCheck {
+        awg: options.wire_awg,
+        ohms_per_meter,
+        ampacity,
+        voltage_drop,
+        drop_percent: voltage_drop / voltage * 100.0,
+    };
+    let strip_length_m = num_pixels as f64 / leds_per_meter;
+
+    let mut warnings = Vec::new();
+    if voltage_drop > voltage * MAX_DROP_FRACTION {
+        warnings.push(format!(
+            "Feed wire drops {:.2} V ({:.1}%) over {} m of {} AWG at {:.1} A, above the 5% target. \
+             Use thicker wire or a shorter run.",
+            voltage_drop, wire.drop_percent, options.run_length_m, options.wire_awg, worst_case_amps
+        ));
+    }
+    if worst_case_amps > ampacity {
+        warnings.push(format!(
+            "{:.1} A exceeds the {} A rating of {} AWG wire.",
+            worst_case_amps, ampacity, options.wire_awg
+        ));
+    } else if fuse_amps > ampacity {
+        warnings.push(format!(
+            "A {} A fuse is needed for {:.1} A but {} AWG wire is only rated for {} A. \
+             Use thicker wire.",
+            fuse_amps, worst_case_amps, options.wire_awg, ampacity
+        ));
+    }
+    if worst_case_amps > JST_SM_AMPS {
+        warnings.push(format!(
+            "{:.1} A exceeds the 3 A rating of JST-SM strip connectors. \
+             Inject power through separate wires and screw terminals.",
+            worst_case_amps
+        ));
+    }
+    if let Some(actual) = options.psu_amps {
+        if actual < worst_case_amps * PSU_HEADROOM {
+            warnings.push(format!(
+                "The {} A PSU is below the recommended {} A ({:.1} A worst case plus 20% headroom).",
+                actual, psu_amps, worst_case_amps
+            ));
+        }
+    }
+
+    Ok(PowerReport {
+        strip_type,
+        num_pixels,
+        voltage,
+        max_amps,
+        worst_case_amps,
+        typical_amps,
+        worst_case_watts: worst_case_amps * voltage,
+        psu_amps,
+        psu_watts: psu_amps * voltage,
+        fuse_amps,
+        wire,
+        strip_length_m,
+        injection_points_m: injection_points(strip_length_m),
+        warnings,
+    })
+}
+
+/// Smallest standard size at or above `amps`, or `amps` rounded up past the table.
+fn next_size(sizes: &[f64], amps: f64) -> f64 {
+    sizes
+        .iter()
+        .copied()
+        .find(|size| *size >= amps)
+        .unwrap_or_else(|| amps.ceil())
+}
+
+/// Feed positions following the injection table in docs/power-and-wiring.md.
+pub fn injection_points(length_m: f64) -> Vec<f64> {
+    let spacing = match length_m {
+        l if l <= 1.0 => return vec![0.0],
+        l if l <= 2.0 => return vec![0.0, length_m],
+        l if l <= 5.0 => return vec![0.0, length_m / 2.0, length_m],
+        l if l <= 10.0 => 2.5,
+        _ => 1.5,
+    };
+
+    let segments = (length_m / spacing).ceil();
+    (0..=segments as usize)
+        .map(|i| (i as f64 * length_m / segments * 100.0).round() / 100.0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config_schema::ResolvedDefine;
+
+    fn options(brightness_cap: u8, wire_awg: u8, run_length_m: f64) -> PowerOptions {
+        PowerOptions {
+            brightness_cap,
+            wire_awg,
+            run_length_m,
+            leds_per_meter: None,
+            strip_type: None,
+            psu_amps: None,
+        }
+    }
+
+    #[test]
+    fn test_budget_matches_docs_table() {
+        let cases = [
+            (StripKind::Sk6812Rgbw, 30, 2.4, 3.0),
+            (StripKind::Sk6812Rgbw, 60, 4.8, 6.0),
+            (StripKind::Sk6812Rgbw, 150, 12.0, 15.0),
+            (StripKind::Ws2815bRgb, 60, 1.8, 2.5),
+            (StripKind::Ws2815bRgb, 150, 4.5, 6.0),
+            (StripKind::Ws2815bRgb, 300, 9.0, 12.0),
+        ];
+        for (strip, pixels, max_amps, psu_amps) in cases {
+            let report = calculate_power(strip, pixels, &options(255, 14, 1.0)).unwrap();
+            assert!(
+                (report.max_amps - max_amps).abs() < 1e-9,
+                "{:?} {}",
+                strip,
+                pixels
+            );
+            assert_eq!(report.psu_amps, psu_amps, "{:?} {}", strip, pixels);
+        }
+    }
+
+    #[test]
+    fn test_brightness_cap_and_wire() {
+        let report = calculate_power(StripKind::Sk6812Rgbw, 60, &options(128, 18, 2.0)).unwrap();
+        assert!((report.worst_case_amps - 4.8 * 128.0 / 255.0).abs() < 1e-9);
+        assert!((report.typical_amps - report.worst_case_amps / 3.0).abs() < 1e-9);
+        assert_eq!(report.fuse_amps, 4.0);
+        // 2.41 A * 2 * 2 m * 0.021 ohm/m
+        assert!((report.wire.voltage_drop - 0.2024).abs() < 1e-3);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        let report = calculate_power(StripKind::Sk6812Rgbw, 150, &options(255, 22, 3.0)).unwrap();
+        assert!(report.warnings.iter().any(|w| w.contains("5% target")));
+        assert!(report.warnings.iter().any(|w| w.contains("22 AWG")));
+        assert!(report.warnings.iter().any(|w| w.contains("JST-SM")));
+
+        assert!(calculate_power(StripKind::Sk6812Rgbw, 60, &options(255, 12, 1.0)).is_err());
+    }
+
+    #[test]
+    fn test_injection_points() {
+        assert_eq!(injection_points(0.5), vec![0.0]);
+        assert_eq!(injection_points(2.0), vec![0.0, 2.0]);
+        assert_eq!(injection_points(4.0), vec![0.0, 2.0, 4.0]);
+        assert_eq!(injection_points(7.5), vec![0.0, 2.5, 5.0, 7.5]);
+        assert_eq!(injection_points(12.0).len(), 9);
+    }
+
+    #[test]
+    fn test_config_inputs() {
+        let define = |name: &str, expression: &str, value: Option<i64>| ResolvedDefine {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            value,
+            overridable: true,
+            overridden: false,
+        };
+        let config = ResolvedConfig {
+            values: vec![
+                define("NUM_PIXELS", "(PANEL_WIDTH * PANEL_HEIGHT)", Some(256)),
+                define("STRIP_TYPE", "STRIP_TYPE_WS2812_RGB", Some(1)),
+            ],
+            errors: Vec::new(),
+        };
+        assert_eq!(
+            config_inputs(&config, &[]).unwrap(),
+            (256, Some(StripKind::Ws2812bRgb))
+        );
+
+        assert_eq!(
+            StripKind::from_define("1", Some(1), &[]),
+            Some(StripKind::Ws2815bRgb)
+        );
+        assert!(config_inputs(
+            &ResolvedConfig {
+                values: Vec::new(),
+                errors: Vec::new()
+            },
+            &[]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_numeric_strip_type_uses_app_constants() {
+        let apps = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../apps");
+
+        // dj-booth numbers its own constants: 1 is STRIP_TYPE_WS2812_RGB, not a WS2815B
+        let dj_booth = strip_type_options(&apps.join("dj-booth"));
+        assert_eq!(
+            StripKind::from_define("1", Some(1), &dj_booth),
+            Some(StripKind::Ws2812bRgb)
+        );
+        assert_eq!(
+            StripKind::from_define("0", Some(0), &dj_booth),
+            Some(StripKind::Sk6812Rgbw)
+        );
+
+        // led-panel follows LEDStrip's numbering
+        let led_panel = strip_type_options(&apps.join("led-panel"));
+        assert_eq!(
+            StripKind::from_define("1", Some(1), &led_panel),
+            Some(StripKind::Ws2815bRgb)
+        );
+    }
+}
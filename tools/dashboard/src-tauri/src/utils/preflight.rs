@@ -0,0 +1,432 @@
+use crate::utils::config_schema;
+use crate::utils::pin_database;
+use crate::utils::pin_validator::{self, Severity};
+use crate::utils::pio_parser::DiscoveredEnvironment;
+use crate::utils::{pio_path, secrets};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Everything the checks may look at before a build or upload is spawned.
+pub struct PreflightContext<'a> {
+    pub monorepo_path: &'a Path,
+    pub app_path: &'a Path,
+    pub env: &'a DiscoveredEnvironment,
+    /// `-D` overrides from the dashboard's build flags.
+    pub defines: HashMap<String, String>,
+    /// Secret headers will be generated from the encrypted store for this run.
+    pub secrets_unlocked: bool,
+    /// The selected upload port, for uploads.
+    pub upload_port: Option<UploadPortStatus>,
+}
+
+/// What the dashboard knows about the port an upload will use.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadPortStatus {
+    pub path: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    /// The port is held open by a serial monitor connection.
+    pub monitor_open: bool,
+}
+
+/// Findings of one check. An `Err` means the check itself could not run and is reported
+/// as a warning rather than blocking the build.
+pub type CheckResult = Result<Vec<(Severity, String)>, String>;
+
+/// A named check run by [`run_preflight`].
+pub struct PreflightCheck {
+    pub id: &'static str,
+    pub run: fn(&PreflightContext) -> CheckResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightFinding {
+    pub check: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightReport {
+    /// False when any finding is an error.
+    pub passed: bool,
+    pub findings: Vec<PreflightFinding>,
+}
+
+impl PreflightReport {
+    pub fn errors(&self) -> impl Iterator<Item = &PreflightFinding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+    }
+}
+
+/// Checks run before every build and upload, in order.
+pub const DEFAULT_CHECKS: &[PreflightCheck] = &[
+    PreflightCheck {
+        id: "platformio",
+        run: check_platformio,
+    },
+    PreflightCheck {
+        id: "define_values",
+        run: check_define_values,
+    },
+    PreflightCheck {
+        id: "pins",
+        run: check_pins,
+    },
+    PreflightCheck {
+        id: "secrets",
+        run: check_secrets,
+    },
+    PreflightCheck {
+        id: "port_lock",
+        run: check_port_lock,
+    },
+    PreflightCheck {
+        id: "usb_board",
+        run: check_usb_board,
+    },
+];
+
+/// Checks run before a filesystem upload. The data image doesn't depend on config.h or
+/// secret headers, and the upload closes any monitor on its port itself.
+pub const FILESYSTEM_UPLOAD_CHECKS: &[PreflightCheck] = &[
+    PreflightCheck {
+        id: "platformio",
+        run: check_platformio,
+    },
+    PreflightCheck {
+        id: "usb_board",
+        run: check_usb_board,
+    },
+];
+
+/// Runs `checks` in order and collects their findings into one report.
+pub fn run_preflight(ctx: &PreflightContext, checks: &[PreflightCheck]) -> PreflightReport {
+    let mut findings = Vec::new();
+    for check in checks {
+        let results = (check.run)(ctx).unwrap_or_else(|e| {
+            vec![(
+                Severity::Warning,
+                format!("Check '{}' could not run: {}", check.id, e),
+            )]
+        });
+        findings.extend(
+            results
+                .into_iter()
+                .filter(|(severity, _)| *severity != Severity::Ok)
+                .map(|(severity, message)| PreflightFinding {
+                    check: check.id.to_string(),
+                    severity,
+                    message,
+                }),
+        );
+    }
+
+    PreflightReport {
+        passed: !findings.iter().any(|f| f.severity == Severity::Error),
+        findings,
+    }
+}
+
+fn check_platformio(ctx: &PreflightContext) -> CheckResult {
+    Ok(pio_path::resolve_pio_path(ctx.monorepo_path)
+        .err()
+        .map(|e| (Severity::Error, e))
+        .into_iter()
+        .collect())
+}
+
+fn check_define_values(ctx: &PreflightContext) -> CheckResult {
+    let schema = config_schema::parse_config_schema(ctx.app_path)?;
+    let type_errors = config_schema::validate_define_types(&schema, &ctx.defines);
+    let mistyped: HashSet<String> = type_errors.iter().map(|v| v.name.clone()).collect();
+
+    // A mistyped value would also fail every range guard, so only its type error is kept
+    let range_errors = config_schema::validate_define_values(&schema, &ctx.defines)
+        .into_iter()
+        .filter(|violation| !mistyped.contains(&violation.name));
+
+    Ok(type_errors
+        .into_iter()
+        .chain(range_errors)
+        .map(|violation| {
+            (
+                Severity::Error,
+                format!(
+                    "{}={}: {}",
+                    violation.name, violation.value, violation.message
+                ),
+            )
+        })
+        .collect())
+}
+
+fn check_pins(ctx: &PreflightContext) -> CheckResult {
+    let Some(profile) =
+        pin_database::environment_profile(&ctx.env.platform, ctx.env.board.as_deref())
+    else {
+        return Ok(Vec::new());
+    };
+    let schema = config_schema::parse_config_schema(ctx.app_path)?;
+    let pins = pin_validator::schema_pins(&schema, Some(&ctx.env.name), &ctx.defines, &profile);
+    let report = pin_validator::validate_pin_map(
+        &pins,
+        &profile,
+        pin_validator::app_uses_wifi(ctx.app_path),
+    );
+
+    Ok(report
+        .issues
+        .into_iter()
+        .map(|issue| (issue.severity, issue.message))
+        .collect())
+}
+
+fn check_secrets(ctx: &PreflightContext) -> CheckResult {
+    // Native test builds never include the secret headers
+    if ctx.secrets_unlocked || !ctx.env.is_hardware_target {
+        return Ok(Vec::new());
+    }
+
+    Ok(secrets::find_secret_templates(ctx.app_path)?
+        .into_iter()
+        .filter(|template| !ctx.app_path.join(&template.header).exists())
+        .map(|template| {
+            (
+                Severity::Error,
+                format!(
+                    "{} is missing. Copy {} to {} and fill it in, or unlock the secrets store.",
+                    template.header, template.template, template.header
+                ),
+            )
+        })
+        .collect())
+}
+
+fn check_port_lock(ctx: &PreflightContext) -> CheckResult {
+    Ok(ctx
+        .upload_port
+        .iter()
+        .filter(|port| port.monitor_open)
+        .map(|port| {
+            (
+                Severity::Error,
+                format!(
+                    "{} is open in the serial monitor. Close the monitor before uploading.",
+                    port.path
+                ),
+            )
+        })
+        .collect())
+}
+
+/// USB ids that identify a board's platform. USB-UART bridges (CP210x, CH340, FTDI) are
+/// used across platforms and are not listed. A `None` product matches the whole vendor.
+const USB_BOARD_IDS: &[(u16, Option<u16>, &str, &str)] = &[
+    (0x303a, None, "espressif32", "Espressif native USB"),
+    (0x2341, Some(0x0070), "espressif32", "Arduino Nano ESP32"),
+    (0x2341, Some(0x0001), "atmelavr", "Arduino Uno"),
+    (0x2341, Some(0x0043), "atmelavr", "Arduino Uno"),
+    (0x2a03, Some(0x0043), "atmelavr", "Arduino Uno"),
+    (0x2341, Some(0x0010), "atmelavr", "Arduino Mega 2560"),
+    (0x2341, Some(0x0042), "atmelavr", "Arduino Mega 2560"),
+    (0x2a03, Some(0x0042), "atmelavr", "Arduino Mega 2560"),
+];
+
+fn check_usb_board(ctx: &PreflightContext) -> CheckResult {
+    let Some(port) = &ctx.upload_port else {
+        return Ok(Vec::new());
+    };
+    let (Some(vid), Some(pid)) = (port.vid, port.pid) else {
+        return Ok(Vec::new());
+    };
+    let Some((_, _, platform, description)) = USB_BOARD_IDS
+        .iter()
+        .find(|(v, p, _, _)| *v == vid && p.is_none_or(|p| p == pid))
+    else {
+        return Ok(Vec::new());
+    };
+
+    if ctx.env.platform.to_lowercase().contains(platform) {
+        return Ok(Vec::new());
+    }
+    Ok(vec![(
+        Severity::Error,
+        format!(
+            "{} looks like a {} board ({:04x}:{:04x}), but environment '{}' targets {}.",
+            port.path, description, vid, pid, ctx.env.name, ctx.env.platform
+        ),
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pio_parser;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn environment(app_path: &Path, platform: &str, board: &str) -> DiscoveredEnvironment {
+        fs::write(
+            app_path.join("platformio.ini"),
+            format!(
+                "[env:main]\nplatform = {}\nboard = {}\nframework = arduino\n",
+                platform, board
+            ),
+        )
+        .unwrap();
+        pio_parser::parse_platformio_ini(&app_path.join("platformio.ini"))
+            .unwrap()
+            .environments
+            .remove(0)
+    }
+
+    fn context<'a>(
+        root: &'a Path,
+        env: &'a DiscoveredEnvironment,
+        upload_port: Option<UploadPortStatus>,
+    ) -> PreflightContext<'a> {
+        PreflightContext {
+            monorepo_path: root,
+            app_path: root,
+            env,
+            defines: HashMap::new(),
+            secrets_unlocked: false,
+            upload_port,
+        }
+    }
+
+    fn messages(ctx: &PreflightContext, check: &PreflightCheck) -> Vec<String> {
+        run_preflight(ctx, std::slice::from_ref(check))
+            .findings
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    fn check(id: &str) -> &'static PreflightCheck {
+        DEFAULT_CHECKS.iter().find(|check| check.id == id).unwrap()
+    }
+
+    #[test]
+    fn test_define_and_pin_checks() {
+        let temp = tempdir().unwrap();
+        let env = environment(temp.path(), "espressif32", "esp32dev");
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        fs::write(
+            temp.path().join("src/config.h"),
+            "#ifndef NUM_ROWS\n#define NUM_ROWS 8\n#endif\n\
+             #if NUM_ROWS < 1\n#error \"NUM_ROWS must be at least 1\"\n#endif\n\
+             #ifndef PIN_LED_DATA\n#define PIN_LED_DATA 16\n#endif\n",
+        )
+        .unwrap();
+
+        let mut ctx = context(temp.path(), &env, None);
+        assert!(messages(&ctx, check("define_values")).is_empty());
+        assert!(messages(&ctx, check("pins")).is_empty());
+
+        ctx.defines.insert("NUM_ROWS".to_string(), "0".to_string());
+        ctx.defines
+            .insert("PIN_LED_DATA".to_string(), "6".to_string());
+        assert_eq!(
+            messages(&ctx, check("define_values")),
+            vec!["NUM_ROWS=0: NUM_ROWS must be at least 1"]
+        );
+        let report = run_preflight(&ctx, DEFAULT_CHECKS);
+        assert!(!report.passed);
+        assert!(report
+            .errors()
+            .any(|f| f.check == "pins" && f.message.contains("PIN_LED_DATA")));
+
+        ctx.defines
+            .insert("NUM_ROWS".to_string(), "many".to_string());
+        assert_eq!(
+            messages(&ctx, check("define_values")),
+            vec!["NUM_ROWS=many: NUM_ROWS must be an integer"]
+        );
+    }
+
+    #[test]
+    fn test_secrets_and_platformio_checks() {
+        let temp = tempdir().unwrap();
+        let env = environment(temp.path(), "espressif32", "esp32dev");
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        fs::write(
+            temp.path().join("src/secrets.h.example"),
+            "#define WIFI_SSID \"your-ssid\"\n",
+        )
+        .unwrap();
+
+        let mut ctx = context(temp.path(), &env, None);
+        let missing = messages(&ctx, check("secrets"));
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].starts_with("src/secrets.h is missing"));
+
+        ctx.secrets_unlocked = true;
+        assert!(messages(&ctx, check("secrets")).is_empty());
+        ctx.secrets_unlocked = false;
+        fs::write(
+            temp.path().join("src/secrets.h"),
+            "#define WIFI_SSID \"home\"\n",
+        )
+        .unwrap();
+        assert!(messages(&ctx, check("secrets")).is_empty());
+
+        fs::remove_file(temp.path().join("src/secrets.h")).unwrap();
+        let native = environment(temp.path(), "native", "native");
+        let ctx = context(temp.path(), &native, None);
+        assert!(messages(&ctx, check("secrets")).is_empty());
+
+        let pio = run_preflight(&ctx, std::slice::from_ref(check("platformio")));
+        assert_eq!(pio.findings[0].severity, Severity::Error);
+        assert!(pio.findings[0].message.contains("PlatformIO not found"));
+    }
+
+    #[test]
+    fn test_port_checks() {
+        let temp = tempdir().unwrap();
+        let avr = environment(temp.path(), "atmelavr", "uno");
+        let port = |vid: u16, pid: u16, monitor_open: bool| UploadPortStatus {
+            path: "/dev/ttyACM0".to_string(),
+            vid: Some(vid),
+            pid: Some(pid),
+            monitor_open,
+        };
+
+        let ctx = context(temp.path(), &avr, Some(port(0x2341, 0x0043, false)));
+        assert!(messages(&ctx, check("usb_board")).is_empty());
+        assert!(messages(&ctx, check("port_lock")).is_empty());
+
+        let ctx = context(temp.path(), &avr, Some(port(0x303a, 0x1001, true)));
+        let usb = messages(&ctx, check("usb_board"));
+        assert_eq!(usb.len(), 1);
+        assert!(usb[0].contains("Espressif native USB board (303a:1001)"));
+        assert_eq!(messages(&ctx, check("port_lock")).len(), 1);
+
+        // CP210x bridges are used by every platform
+        let ctx = context(temp.path(), &avr, Some(port(0x10c4, 0xea60, false)));
+        assert!(messages(&ctx, check("usb_board")).is_empty());
+    }
+
+    #[test]
+    fn test_failing_check_does_not_block() {
+        let temp = tempdir().unwrap();
+        let env = environment(temp.path(), "native", "native");
+        let ctx = context(temp.path(), &env, None);
+        let broken = PreflightCheck {
+            id: "broken",
+            run: |_| Err("no data".to_string()),
+        };
+
+        let report = run_preflight(&ctx, &[broken]);
+        assert!(report.passed);
+        assert_eq!(report.findings[0].severity, Severity::Warning);
+        assert_eq!(
+            report.findings[0].message,
+            "Check 'broken' could not run: no data"
+        );
+    }
+}
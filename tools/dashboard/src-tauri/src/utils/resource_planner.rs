@@ -0,0 +1,466 @@
+use crate::utils::config_schema;
+use crate::utils::pin_database::{self, PeripheralLimits};
+use crate::utils::pio_parser::DiscoveredEnvironment;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeripheralKind {
+    RmtTx,
+    I2s,
+    LedcChannel,
+    LedcTimer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub kind: PeripheralKind,
+    pub used: u32,
+    /// Channels the chip provides; `None` when it has no such peripheral.
+    pub available: Option<u32>,
+    /// Declarations that claim the resource, e.g. `strip (src/main.cpp)`.
+    pub consumers: Vec<String>,
+    pub oversubscribed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourcePlan {
+    pub environment: String,
+    /// Chip the limits come from; `None` for native or unknown platforms.
+    pub chip: Option<String>,
+    pub usage: Vec<ResourceUsage>,
+    pub warnings: Vec<String>,
+}
+
+/// Peripheral claims found in an app's active source lines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceCounts {
+    /// One RMT TX channel per `LEDStrip` instance.
+    pub strips: Vec<String>,
+    /// `AudioInput` instances; each claims I2S port 0.
+    pub audio_inputs: Vec<String>,
+    /// `LEDPWM` instances; each drives four LEDC channels (R, G, B, W).
+    pub pwm_outputs: Vec<String>,
+    /// Distinct frequency/resolution arguments across `LEDPWM` instances.
+    pub pwm_timer_configs: Vec<String>,
+}
+
+/// LEDC channels each `LEDPWM` attaches (R, G, B, W).
+const LEDC_CHANNELS_PER_PWM: u32 = 4;
+
+/// I2S ports `AudioInput` can use. It always installs its driver on `I2S_NUM_0`, so a
+/// second instance collides even on chips with two ports.
+const AUDIO_INPUT_I2S_PORTS: u32 = 1;
+
+const SOURCE_EXTENSIONS: &[&str] = &["c", "cpp", "h", "hpp", "ino"];
+
+static STRIP_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\bLEDStrip\s*<[^;>]*>\s+(\w+)\s*(?:\[\s*(\w+)\s*\])?\s*[({;=]")
+        .expect("strip regex")
+});
+static PWM_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\bLEDPWM\s+(\w+)\s*(?:\[\s*(\w+)\s*\])?\s*([({;=])(.*)$").expect("pwm regex")
+});
+static AUDIO_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\bAudioInput\s+(\w+)\s*(?:\[\s*(\w+)\s*\])?\s*[({;=]").expect("audio regex")
+});
+
+/// Counts `LEDStrip`, `LEDPWM` and `AudioInput` instances in the branches of each source
+/// file that the given macros select.
+pub fn count_resources(
+    sources: &[(String, String)],
+    macros: &HashMap<String, String>,
+) -> ResourceCounts {
+    let mut counts = ResourceCounts::default();
+    let array_len = |len: Option<regex::Match>| -> usize {
+        len.and_then(|m| {
+            let text = m.as_str();
+            text.parse::<usize>()
+                .ok()
+                .or_else(|| macros.get(text).and_then(|v| v.trim().parse().ok()))
+        })
+        .unwrap_or(1)
+    };
+
+    for (file, content) in sources {
+        for line in config_schema::active_source_lines(content, macros) {
+            if let Some(caps) = STRIP_RE.captures(&line) {
+                for _ in 0..array_len(caps.get(2)) {
+                    counts.strips.push(format!("{} ({})", &caps[1], file));
+                }
+            }
+            if let Some(caps) = AUDIO_RE.captures(&line) {
+                for _ in 0..array_len(caps.get(2)) {
+                    counts.audio_inputs.push(format!("{} ({})", &caps[1], file));
+                }
+            }
+            if let Some(caps) = PWM_RE.captures(&line) {
+                let timer_config = match &caps[3] {
+                    "(" | "{" => timer_arguments(&caps[4]),
+                    _ => String::new(),
+                };
+                for _ in 0..array_len(caps.get(2)) {
+                    counts.pwm_outputs.push(format!("{} ({})", &caps[1], file));
+                }
+                if !counts.pwm_timer_configs.contains(&timer_config) {
+                    counts.pwm_timer_configs.push(timer_config);
+                }
+            }
+        }
+    }
+
+    counts
+}
+
+/// Returns the constructor arguments after the pins, which select the LEDC timer.
+fn timer_arguments(args: &str) -> String {
+    let mut depth = 0;
+    let mut parts = vec![String::new()];
+    for c in args.chars() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' if depth == 0 => break,
+            ')' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(c);
+        }
+    }
+    parts
+        .iter()
+        .skip(1)
+        .map(|part| part.trim())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Compares resource counts with a chip's limits.
+pub fn check_limits(
+    environment: &str,
+    chip: Option<&str>,
+    limits: &PeripheralLimits,
+    counts: &ResourceCounts,
+) -> ResourcePlan {
+    let ledc_channels: Vec<String> = counts
+        .pwm_outputs
+        .iter()
+        .flat_map(|pwm| std::iter::repeat_n(pwm.clone(), LEDC_CHANNELS_PER_PWM as usize))
+        .collect();
+    let pwm_timers = if counts.pwm_outputs.is_empty() {
+        Vec::new()
+    } else {
+        counts.pwm_timer_configs.clone()
+    };
+    let chip_name = chip.unwrap_or("this target");
+
+    let resources = [
+        (PeripheralKind::RmtTx, &counts.strips, limits.rmt_tx),
+        (PeripheralKind::I2s, &counts.audio_inputs, limits.i2s),
+        (
+            PeripheralKind::LedcChannel,
+            &ledc_channels,
+            limits.ledc_channels,
+        ),
+        (PeripheralKind::LedcTimer, &pwm_timers, limits.ledc_timers),
+    ];
+
+    let mut usage = Vec::new();
+    let mut warnings = Vec::new();
+    for (kind, consumers, available) in resources {
+        let used = consumers.len() as u32;
+        let claimable = match kind {
+            PeripheralKind::I2s => available.map(|ports| ports.min(AUDIO_INPUT_I2S_PORTS)),
+            _ => available,
+        };
+        let oversubscribed = match claimable {
+            Some(claimable) => used > claimable,
+            // Without the peripheral, strips bit-bang and PWM falls back to analogWrite;
+            // only audio input has no fallback.
+            None => kind == PeripheralKind::I2s && used > 0 && chip.is_some(),
+        };
+
+        if oversubscribed {
+            let available = available.unwrap_or(0);
+            warnings.push(match kind {
+                PeripheralKind::RmtTx => format!(
+                    "{} LED strips need {} RMT TX channels but {} has {}. \
+                     Move the extra strips to an I2S or SPI NeoPixelBus method.",
+                    used, used, chip_name, available
+                ),
+                PeripheralKind::I2s if available > 0 => format!(
+                    "{} audio inputs all claim I2S0 (AudioInput always uses I2S_NUM_0), \
+                     so only one can run on {}.",
+                    used, chip_name
+                ),
+                PeripheralKind::I2s => format!(
+                    "{} audio inputs need an I2S port but {} has none.",
+                    used, chip_name
+                ),
+                PeripheralKind::LedcChannel => format!(
+                    "{} PWM outputs need {} LEDC channels but {} has {}.",
+                    counts.pwm_outputs.len(),
+                    used,
+                    chip_name,
+                    available
+                ),
+                PeripheralKind::LedcTimer => format!(
+                    "PWM outputs use {} frequency/resolution combinations but {} has {} LEDC timers.",
+                    used, chip_name, available
+                ),
+            });
+        }
+
+        usage.push(ResourceUsage {
+            kind,
+            used,
+            available,
+            consumers: consumers.clone(),
+            oversubscribed,
+        });
+    }
+
+    ResourcePlan {
+        environment: environment.to_string(),
+        chip: chip.map(str::to_string),
+        usage,
+        warnings,
+    }
+}
+
+/// Plans peripheral usage for an environment: the environment's platform macros, build
+/// flag defines and `overrides` select the active branches of config.h and the app's
+/// sources, and the environment's board supplies the limits.
+pub fn plan_resources(
+    app_path: &Path,
+    env: &DiscoveredEnvironment,
+    overrides: &HashMap<String, String>,
+) -> Result<ResourcePlan, String> {
+    let mut macros = config_schema::environment_macros(env);
+    macros.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    if app_path.join("src/config.h").exists() {
+        let resolved = config_schema::resolve_config_values(app_path, &macros)?;
+        for define in resolved.values {
+            let value = define
+                .value
+                .map(|v| v.to_string())
+                .unwrap_or(define.expression);
+            macros.insert(define.name, value);
+        }
+    }
+
+    let mut sources = Vec::new();
+    collect_sources(app_path, &app_path.join("src"), &mut sources)?;
+    let counts = count_resources(&sources, &macros);
+
+    let profile = pin_database::environment_profile(&env.platform, env.board.as_deref());
+    let limits = profile
+        .as_ref()
+        .map(|p| p.chip.peripherals)
+        .unwrap_or_default();
+    Ok(check_limits(
+        &env.name,
+        profile.as_ref().map(|p| p.chip.name.as_str()),
+        &limits,
+        &counts,
+    ))
+}
+
+/// Reads every C/C++ source under `dir` recursively, labelled relative to `app_path`.
+pub(crate) fn collect_sources(
+    app_path: &Path,
+    dir: &Path,
+    sources: &mut Vec<(String, String)>,
+) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+    let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            collect_sources(app_path, &path, sources)?;
+            continue;
+        }
+        let is_source = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext));
+        if !is_source {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let label = path
+            .strip_prefix(app_path)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        sources.push((label, content));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pio_parser;
+    use tempfile::tempdir;
+
+    fn macros(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn esp32_limits() -> PeripheralLimits {
+        pin_database::chip_profile("esp32", None)
+            .unwrap()
+            .chip
+            .peripherals
+    }
+
+    #[test]
+    fn test_count_resources_follows_branches() {
+        let main = r#"
+#if AUDIO_ENABLED
+AudioInput s_audioInput;
+#endif
+#if STRIP_TYPE == 0
+LEDStrip<StripType::SK6812_RGBW> strip(PIN_LED_DATA, NUM_PIXELS);
+#else
+LEDStrip<StripType::WS2815B_RGB> strip(PIN_LED_DATA, NUM_PIXELS);
+#endif
+LEDStrip<StripType::SK6812_RGBW> rings[NUM_RINGS];
+LEDPWM accent({16, 17, 18, 19});
+LEDPWM cabinet({21, 22, 23, 25}, 5000, 8);
+void show(LEDStrip<StripType::SK6812_RGBW>& target);
+"#;
+        let sources = vec![("src/main.cpp".to_string(), main.to_string())];
+
+        let counts = count_resources(
+            &sources,
+            &macros(&[
+                ("AUDIO_ENABLED", "1"),
+                ("STRIP_TYPE", "0"),
+                ("NUM_RINGS", "3"),
+            ]),
+        );
+        assert_eq!(counts.strips.len(), 4);
+        assert_eq!(counts.strips[0], "strip (src/main.cpp)");
+        assert_eq!(counts.audio_inputs, vec!["s_audioInput (src/main.cpp)"]);
+        assert_eq!(counts.pwm_outputs.len(), 2);
+        assert_eq!(counts.pwm_timer_configs, vec!["", "5000, 8"]);
+
+        let counts = count_resources(&sources, &macros(&[("AUDIO_ENABLED", "0")]));
+        assert!(counts.audio_inputs.is_empty());
+        assert_eq!(counts.strips.len(), 2);
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let counts = ResourceCounts {
+            strips: (0..9).map(|i| format!("strip{}", i)).collect(),
+            audio_inputs: vec!["mic".to_string()],
+            pwm_outputs: vec!["accent".to_string()],
+            pwm_timer_configs: vec![String::new()],
+        };
+
+        let plan = check_limits("esp32", Some("ESP32"), &esp32_limits(), &counts);
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("9 LED strips"));
+        let rmt = &plan.usage[0];
+        assert_eq!(
+            (rmt.kind, rmt.used, rmt.available),
+            (PeripheralKind::RmtTx, 9, Some(8))
+        );
+        assert!(rmt.oversubscribed);
+        assert_eq!(plan.usage[2].used, 4);
+
+        let c3 = pin_database::chip_profile("esp32c3", None).unwrap();
+        let plan = check_limits("c3", Some("ESP32-C3"), &c3.chip.peripherals, &counts);
+        assert!(plan.warnings[0].contains("ESP32-C3 has 2"));
+
+        // Bit-banged strips on AVR never run out, but audio input has nowhere to go
+        let uno = pin_database::board_profile("uno").unwrap();
+        let plan = check_limits("uno", Some("ATmega328P"), &uno.chip.peripherals, &counts);
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("I2S"));
+
+        let plan = check_limits("native", None, &PeripheralLimits::default(), &counts);
+        assert!(plan.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_audio_inputs_share_i2s0() {
+        let counts = ResourceCounts {
+            audio_inputs: vec!["mic".to_string(), "line_in".to_string()],
+            ..ResourceCounts::default()
+        };
+
+        // The ESP32 has two I2S ports, but AudioInput only ever uses port 0
+        let plan = check_limits("esp32", Some("ESP32"), &esp32_limits(), &counts);
+        let i2s = &plan.usage[1];
+        assert_eq!(
+            (i2s.kind, i2s.used, i2s.available),
+            (PeripheralKind::I2s, 2, Some(2))
+        );
+        assert!(i2s.oversubscribed);
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("I2S0"));
+
+        let single = ResourceCounts {
+            audio_inputs: vec!["mic".to_string()],
+            ..ResourceCounts::default()
+        };
+        let plan = check_limits("esp32", Some("ESP32"), &esp32_limits(), &single);
+        assert!(!plan.usage[1].oversubscribed);
+        assert!(plan.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_plan_resources_uses_config_defaults() {
+        let temp = tempdir().unwrap();
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        fs::write(
+            temp.path().join("src/config.h"),
+            "#ifndef AUDIO_ENABLED\n#ifdef ESP32\n#define AUDIO_ENABLED 1\n#else\n#define AUDIO_ENABLED 0\n#endif\n#endif\n",
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join("src/main.cpp"),
+            "#include \"config.h\"\n#if AUDIO_ENABLED\nAudioInput mic;\n#endif\nLEDStrip<StripType::SK6812_RGBW> strip(4, 60);\n",
+        )
+        .unwrap();
+
+        fs::write(
+            temp.path().join("platformio.ini"),
+            "[env:esp32]\nplatform = espressif32\nboard = esp32dev\n",
+        )
+        .unwrap();
+        let config = pio_parser::parse_platformio_ini(&temp.path().join("platformio.ini")).unwrap();
+        let env = &config.environments[0];
+        let plan = plan_resources(temp.path(), env, &HashMap::new()).unwrap();
+        assert_eq!(plan.chip.as_deref(), Some("ESP32"));
+        assert_eq!(plan.usage[0].used, 1);
+        assert_eq!(plan.usage[1].used, 1);
+
+        let overrides = macros(&[("AUDIO_ENABLED", "0")]);
+        let plan = plan_resources(temp.path(), env, &overrides).unwrap();
+        assert_eq!(plan.usage[1].used, 0);
+    }
+}
@@ -0,0 +1,528 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Secret values for one app, keyed by header path (e.g. `src/secrets.h`) then define name.
+pub type AppSecrets = BTreeMap<String, BTreeMap<String, String>>;
+
+/// A `*.h.example` template and the defines it asks for.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SecretTemplate {
+    /// Header that gets generated, relative to the app directory.
+    pub header: String,
+    /// Template file, relative to the app directory.
+    pub template: String,
+    pub fields: Vec<SecretField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SecretField {
+    pub name: String,
+    /// Example value from the template, shown as a placeholder.
+    pub placeholder: String,
+    /// Whether the template writes the value as a C string literal.
+    pub quoted: bool,
+}
+
+/// On-disk format of the encrypted store. The plaintext is the JSON form of
+/// `BTreeMap<app name, AppSecrets>`.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedStore {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+const STORE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TEMPLATE_SUFFIX: &str = ".example";
+
+/// First line of every generated header, so stale copies can be recognised and replaced
+/// while hand-written headers are left alone.
+const GENERATED_MARKER: &str = "// Generated by rgbw-dashboard from";
+
+/// Directories searched for templates, relative to the app directory.
+const TEMPLATE_DIRS: &[&str] = &["", "src", "include"];
+
+static DEFINE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*#define\s+(\w+)\s+(.*?)\s*$").expect("define regex"));
+
+/// Finds `*.h.example` templates in the app root, `src/` and `include/`.
+pub fn find_secret_templates(app_path: &Path) -> Result<Vec<SecretTemplate>, String> {
+    let mut templates = Vec::new();
+
+    for dir in TEMPLATE_DIRS {
+        let dir_path = app_path.join(dir);
+        let entries = match fs::read_dir(&dir_path) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter(|entry| entry.path().is_file())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".h.example"))
+            .collect();
+        names.sort();
+
+        for name in names {
+            let content = fs::read_to_string(dir_path.join(&name))
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            let relative = |file: &str| {
+                if dir.is_empty() {
+                    file.to_string()
+                } else {
+                    format!("{}/{}", dir, file)
+                }
+            };
+            templates.push(SecretTemplate {
+                header: relative(name.trim_end_matches(TEMPLATE_SUFFIX)),
+                template: relative(&name),
+                fields: parse_template_fields(&content),
+            });
+        }
+    }
+
+    Ok(templates)
+}
+
+fn parse_template_fields(content: &str) -> Vec<SecretField> {
+    content
+        .lines()
+        .filter_map(|line| DEFINE_RE.captures(line))
+        .map(|caps| {
+            let raw = caps[2].to_string();
+            let quoted = raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"');
+            SecretField {
+                name: caps[1].to_string(),
+                placeholder: if quoted {
+                    raw[1..raw.len() - 1].to_string()
+                } else {
+                    raw
+                },
+                quoted,
+            }
+        })
+        .collect()
+}
+
+/// Fills a template with stored values. Every define in the template must have a value.
+pub fn render_secret_header(
+    template: &SecretTemplate,
+    template_content: &str,
+    values: &BTreeMap<String, String>,
+) -> Result<String, String> {
+    let missing: Vec<&str> = template
+        .fields
+        .iter()
+        .filter(|field| !values.contains_key(&field.name))
+        .map(|field| field.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Missing secrets for {}: {}",
+            template.header,
+            missing.join(", ")
+        ));
+    }
+
+    let mut lines = vec![format!(
+        "{} {}; removed after the build.",
+        GENERATED_MARKER, template.template
+    )];
+    for line in template_content.lines() {
+        let caps = match DEFINE_RE.captures(line) {
+            Some(caps) => caps,
+            None => {
+                lines.push(line.to_string());
+                continue;
+            }
+        };
+        let name = &caps[1];
+        let field = template.fields.iter().find(|f| f.name == name);
+        let value = &values[name];
+        if value.contains(['\n', '\r', '\0']) {
+            return Err(format!("Secret {} contains a line break", name));
+        }
+
+        let literal = match field {
+            Some(field) if !field.quoted => {
+                if !value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '+'))
+                {
+                    return Err(format!(
+                        "Secret {} must be a plain number or identifier",
+                        name
+                    ));
+                }
+                value.clone()
+            }
+            _ => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        };
+        lines.push(format!("#define {} {}", name, literal));
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Headers written for a build. Dropping the guard deletes them again.
+#[derive(Debug, Default)]
+pub struct GeneratedSecrets {
+    paths: Vec<PathBuf>,
+}
+
+impl GeneratedSecrets {
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl Drop for GeneratedSecrets {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            if let Err(e) = fs::remove_file(path) {
+                warn!(
+                    "Failed to remove generated secrets {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Writes every template's header from the stored values.
+///
+/// A header that already exists and was not generated by the dashboard is treated as
+/// hand-maintained and left untouched.
+pub fn write_secret_headers(
+    app_path: &Path,
+    secrets: &AppSecrets,
+) -> Result<GeneratedSecrets, String> {
+    let mut generated = GeneratedSecrets::default();
+
+    for template in find_secret_templates(app_path)? {
+        let header_path = app_path.join(&template.header);
+        if header_path.exists() && !is_generated_header(&header_path) {
+            info!(header = %template.header, "Keeping hand-written secrets header");
+            continue;
+        }
+
+        let template_content = fs::read_to_string(app_path.join(&template.template))
+            .map_err(|e| format!("Failed to read {}: {}", template.template, e))?;
+        let values = secrets.get(&template.header).cloned().unwrap_or_default();
+        let rendered = render_secret_header(&template, &template_content, &values)?;
+
+        write_private(&header_path, &rendered)
+            .map_err(|e| format!("Failed to write {}: {}", template.header, e))?;
+        generated.paths.push(header_path);
+    }
+
+    Ok(generated)
+}
+
+/// Deletes generated headers a previous build left behind, e.g. because the dashboard
+/// was killed mid-build before `GeneratedSecrets` could drop. Hand-written headers are kept.
+pub fn remove_stale_headers(app_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut removed = Vec::new();
+    for template in find_secret_templates(app_path)? {
+        let header_path = app_path.join(&template.header);
+        if is_generated_header(&header_path) {
+            fs::remove_file(&header_path)
+                .map_err(|e| format!("Failed to remove {}: {}", template.header, e))?;
+            removed.push(header_path);
+        }
+    }
+    Ok(removed)
+}
+
+/// Writes a file that only the current user can read.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+
+    // The mode above only applies when the file is created
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())
+}
+
+fn is_generated_header(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .map(|content| content.starts_with(GENERATED_MARKER))
+        .unwrap_or(false)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase cannot be empty".to_string());
+    }
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive key: {}", e))?;
+    Ok(key)
+}
+
+/// Decrypts the store. A missing store file is treated as empty.
+pub fn load_store(path: &Path, passphrase: &str) -> Result<BTreeMap<String, AppSecrets>, String> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read secrets: {}", e))?;
+    let store: EncryptedStore =
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse secrets: {}", e))?;
+    if store.version != STORE_VERSION {
+        return Err(format!(
+            "Unsupported secrets store version {}",
+            store.version
+        ));
+    }
+
+    let decode = |field: &str, value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|e| format!("Corrupted secrets {}: {}", field, e))
+    };
+    let salt = decode("salt", &store.salt)?;
+    let nonce = decode("nonce", &store.nonce)?;
+    let ciphertext = decode("ciphertext", &store.ciphertext)?;
+    if nonce.len() != NONCE_LEN {
+        return Err("Corrupted secrets nonce".to_string());
+    }
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Incorrect passphrase or corrupted secrets store".to_string())?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Failed to parse secrets: {}", e))
+}
+
+/// Encrypts and writes the store with a fresh salt and nonce.
+pub fn save_store(
+    path: &Path,
+    passphrase: &str,
+    secrets: &BTreeMap<String, AppSecrets>,
+) -> Result<(), String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+    let plaintext =
+        serde_json::to_vec(secrets).map_err(|e| format!("Failed to serialize: {}", e))?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+        .map_err(|_| "Failed to encrypt secrets".to_string())?;
+
+    let store = EncryptedStore {
+        version: STORE_VERSION,
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    let json =
+        serde_json::to_string_pretty(&store).map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_private(path, &json).map_err(|e| format!("Failed to write secrets: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const SECRETS_EXAMPLE: &str = r#"#pragma once
+
+// Copy this file to secrets.h and fill in the real values for local builds.
+
+#define WIFI_SSID "your-wifi-ssid"
+#define WIFI_PASSWORD "your-wifi-password"
+#define MATTER_PAIRING_CODE "12345678"
+"#;
+
+    fn app_with_template() -> tempfile::TempDir {
+        let temp = tempdir().unwrap();
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        fs::write(temp.path().join("src/secrets.h.example"), SECRETS_EXAMPLE).unwrap();
+        temp
+    }
+
+    fn wifi_values() -> BTreeMap<String, String> {
+        [
+            ("WIFI_SSID", "booth"),
+            ("WIFI_PASSWORD", r#"p"ss\word"#),
+            ("MATTER_PAIRING_CODE", "20202021"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_find_templates() {
+        let temp = app_with_template();
+        let templates = find_secret_templates(temp.path()).unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].header, "src/secrets.h");
+        assert_eq!(templates[0].template, "src/secrets.h.example");
+
+        let names: Vec<&str> = templates[0]
+            .fields
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["WIFI_SSID", "WIFI_PASSWORD", "MATTER_PAIRING_CODE"]
+        );
+        assert_eq!(templates[0].fields[0].placeholder, "your-wifi-ssid");
+        assert!(templates[0].fields[0].quoted);
+    }
+
+    #[test]
+    fn test_render_escapes_values() {
+        let temp = app_with_template();
+        let template = &find_secret_templates(temp.path()).unwrap()[0];
+        let header = render_secret_header(template, SECRETS_EXAMPLE, &wifi_values()).unwrap();
+
+        assert!(header.starts_with(GENERATED_MARKER));
+        assert!(header.contains("#pragma once\n"));
+        assert!(header.contains("#define WIFI_SSID \"booth\"\n"));
+        assert!(header.contains(r#"#define WIFI_PASSWORD "p\"ss\\word""#));
+    }
+
+    #[test]
+    fn test_render_requires_all_values() {
+        let temp = app_with_template();
+        let template = &find_secret_templates(temp.path()).unwrap()[0];
+        let mut values = wifi_values();
+        values.remove("WIFI_PASSWORD");
+
+        let err = render_secret_header(template, SECRETS_EXAMPLE, &values).unwrap_err();
+        assert!(err.contains("WIFI_PASSWORD"));
+    }
+
+    #[test]
+    fn test_generated_headers_removed_on_drop() {
+        let temp = app_with_template();
+        let header_path = temp.path().join("src/secrets.h");
+        let secrets: AppSecrets = [("src/secrets.h".to_string(), wifi_values())]
+            .into_iter()
+            .collect();
+
+        {
+            let generated = write_secret_headers(temp.path(), &secrets).unwrap();
+            assert_eq!(generated.paths().to_vec(), vec![header_path.clone()]);
+            assert!(header_path.exists());
+        }
+        assert!(!header_path.exists());
+    }
+
+    #[test]
+    fn test_stale_generated_headers_removed() {
+        let temp = app_with_template();
+        let header_path = temp.path().join("src/secrets.h");
+        let secrets: AppSecrets = [("src/secrets.h".to_string(), wifi_values())]
+            .into_iter()
+            .collect();
+
+        // Simulate a build that never got to drop its guard
+        std::mem::forget(write_secret_headers(temp.path(), &secrets).unwrap());
+        assert!(header_path.exists());
+
+        assert_eq!(
+            remove_stale_headers(temp.path()).unwrap(),
+            vec![header_path.clone()]
+        );
+        assert!(!header_path.exists());
+        assert!(remove_stale_headers(temp.path()).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = app_with_template();
+        let header_path = temp.path().join("src/secrets.h");
+        let store_path = temp.path().join("secrets.json");
+        // A leftover header with default permissions is tightened when rewritten
+        fs::write(&header_path, format!("{} old\n", GENERATED_MARKER)).unwrap();
+        let secrets: AppSecrets = [("src/secrets.h".to_string(), wifi_values())]
+            .into_iter()
+            .collect();
+
+        let _generated = write_secret_headers(temp.path(), &secrets).unwrap();
+        save_store(&store_path, "correct horse", &BTreeMap::new()).unwrap();
+
+        for path in [&header_path, &store_path] {
+            let mode = fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_hand_written_header_kept() {
+        let temp = app_with_template();
+        let header_path = temp.path().join("src/secrets.h");
+        fs::write(&header_path, "#define WIFI_SSID \"mine\"\n").unwrap();
+
+        let generated = write_secret_headers(temp.path(), &AppSecrets::new()).unwrap();
+        assert!(generated.paths().is_empty());
+        drop(generated);
+        assert!(remove_stale_headers(temp.path()).unwrap().is_empty());
+        assert!(header_path.exists());
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("secrets.json");
+        let mut store = BTreeMap::new();
+        store.insert(
+            "dj-booth".to_string(),
+            [("src/secrets.h".to_string(), wifi_values())]
+                .into_iter()
+                .collect::<AppSecrets>(),
+        );
+
+        save_store(&path, "correct horse", &store).unwrap();
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("booth"));
+
+        assert_eq!(load_store(&path, "correct horse").unwrap(), store);
+        assert!(load_store(&path, "wrong").is_err());
+        assert!(load_store(&temp.path().join("missing.json"), "x")
+            .unwrap()
+            .is_empty());
+    }
+}
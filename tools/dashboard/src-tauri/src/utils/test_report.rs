@@ -0,0 +1,502 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+    Errored,
+}
+
+impl TestStatus {
+    /// Maps PlatformIO's status names (`PASSED`, `WARNED`, ...) and Unity's (`PASS`, `IGNORE`, ...).
+    fn parse(status: &str) -> Self {
+        match status.to_ascii_uppercase().as_str() {
+            "PASSED" | "PASS" | "WARNED" => TestStatus::Passed,
+            "SKIPPED" | "IGNORE" | "IGNORED" => TestStatus::Skipped,
+            "FAILED" | "FAIL" => TestStatus::Failed,
+            _ => TestStatus::Errored,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub status: TestStatus,
+    pub duration_ms: Option<u64>,
+    /// Assertion message for failed and skipped cases.
+    pub message: Option<String>,
+    /// Relative to the app directory, e.g. `test/test_matrix/test_main.cpp`.
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestSuite {
+    /// Test directory, e.g. `test_matrix`.
+    pub name: String,
+    pub environment: String,
+    pub status: TestStatus,
+    pub duration_ms: Option<u64>,
+    pub cases: Vec<TestCase>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TestReport {
+    pub success: bool,
+    pub duration_ms: Option<u64>,
+    pub suites: Vec<TestSuite>,
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub errored: usize,
+}
+
+impl TestReport {
+    fn new(success: bool, duration_ms: Option<u64>, suites: Vec<TestSuite>) -> Self {
+        let mut report = TestReport {
+            success,
+            duration_ms,
+            ..Default::default()
+        };
+        for case in suites.iter().flat_map(|suite| &suite.cases) {
+            report.total += 1;
+            match case.status {
+                TestStatus::Passed => report.passed += 1,
+                TestStatus::Failed => report.failed += 1,
+                TestStatus::Skipped => report.skipped += 1,
+                TestStatus::Errored => report.errored += 1,
+            }
+        }
+        report.suites = suites;
+        report
+    }
+}
+
+fn seconds_to_ms(seconds: Option<f64>) -> Option<u64> {
+    seconds
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(|s| (s * 1000.0).round() as u64)
+}
+
+// `pio test --json-output-path` format
+#[derive(Deserialize)]
+struct PioJsonReport {
+    duration: Option<f64>,
+    #[serde(default)]
+    test_suites: Vec<PioJsonSuite>,
+}
+
+#[derive(Deserialize)]
+struct PioJsonSuite {
+    env_name: String,
+    test_name: Option<String>,
+    test_dir: Option<String>,
+    status: String,
+    duration: Option<f64>,
+    #[serde(default)]
+    test_cases: Vec<PioJsonCase>,
+}
+
+#[derive(Deserialize)]
+struct PioJsonCase {
+    name: String,
+    status: String,
+    message: Option<String>,
+    duration: Option<f64>,
+    exception: Option<String>,
+    source: Option<PioJsonSource>,
+}
+
+#[derive(Deserialize)]
+struct PioJsonSource {
+    file: String,
+    line: Option<u32>,
+}
+
+/// Parses the report `pio test --json-output-path` writes.
+pub fn parse_json_report(content: &str, success: bool) -> Result<TestReport, String> {
+    let json: PioJsonReport =
+        serde_json::from_str(content).map_err(|e| format!("Failed to parse test report: {}", e))?;
+
+    let suites = json
+        .test_suites
+        .into_iter()
+        .map(|suite| TestSuite {
+            name: suite
+                .test_name
+                .or(suite.test_dir)
+                .unwrap_or_else(|| "*".to_string()),
+            environment: suite.env_name,
+            status: TestStatus::parse(&suite.status),
+            duration_ms: seconds_to_ms(suite.duration),
+            cases: suite
+                .test_cases
+                .into_iter()
+                .map(|case| TestCase {
+                    name: case.name,
+                    status: TestStatus::parse(&case.status),
+                    duration_ms: seconds_to_ms(case.duration),
+                    message: case.message.or(case.exception).filter(|m| !m.is_empty()),
+                    line: case.source.as_ref().and_then(|source| source.line),
+                    file: case.source.map(|source| source.file),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(TestReport::new(
+        success,
+        seconds_to_ms(json.duration),
+        suites,
+    ))
+}
+
+/// Builds a report from `pio test` console output, for when no JSON report was written.
+#[derive(Debug, Default)]
+pub struct TestOutputParser {
+    suites: Vec<TestSuite>,
+}
+
+impl TestOutputParser {
+    pub fn push_line(&mut self, line: &str) {
+        // `Processing test_matrix in native environment`
+        static SUITE_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^Processing (\S+) in (\S+) environment").expect("test suite regex")
+        });
+        // `test/test_matrix/test_main.cpp:45: test_xy_to_index: Expected 1 Was 2 [FAILED]`
+        static PIO_CASE_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^(?P<file>[^:\s][^:]*):(?P<line>\d+): (?P<name>\w+)(?:: (?P<msg>.*?))?\s+\[(?P<status>PASSED|FAILED|SKIPPED|ERRORED)\]$",
+            )
+            .expect("test case regex")
+        });
+        // Raw Unity: `test/test_matrix/test_main.cpp:45:test_xy_to_index:FAIL: Expected 1 Was 2`
+        static UNITY_CASE_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^(?P<file>[^:\s][^:]*):(?P<line>\d+):(?P<name>\w+):(?P<status>PASS|FAIL|IGNORE)(?::\s*(?P<msg>.*))?$",
+            )
+            .expect("unity case regex")
+        });
+        // Summary table: `native  test_matrix  PASSED  00:00:01.234`
+        static SUMMARY_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(
+                r"^(?P<env>\S+)\s+(?P<suite>\S+)\s+(?P<status>PASSED|FAILED|SKIPPED|ERRORED)\s+(?P<h>\d+):(?P<m>\d+):(?P<s>\d+(?:\.\d+)?)$",
+            )
+            .expect("test summary regex")
+        });
+
+        let line = line.trim();
+        if let Some(caps) = SUITE_RE.captures(line) {
+            self.suite(&caps[1], &caps[2]);
+        } else if let Some(caps) = PIO_CASE_RE
+            .captures(line)
+            .or_else(|| UNITY_CASE_RE.captures(line))
+        {
+            let file = caps["file"].to_string();
+            let case = TestCase {
+                name: caps["name"].to_string(),
+                status: TestStatus::parse(&caps["status"]),
+                duration_ms: None,
+                message: caps
+                    .name("msg")
+                    .map(|m| m.as_str().trim().to_string())
+                    .filter(|m| !m.is_empty()),
+                line: caps["line"].parse().ok(),
+                file: Some(file.clone()),
+            };
+            if self.suites.is_empty() {
+                // No header: take the suite from `test/<suite>/...`
+                let name = file.split('/').nth(1).unwrap_or("*").to_string();
+                self.suite(&name, "");
+            }
+            if let Some(suite) = self.suites.last_mut() {
+                suite.cases.push(case);
+            }
+        } else if let Some(caps) = SUMMARY_RE.captures(line) {
+            let seconds = caps["h"].parse::<f64>().unwrap_or(0.0) * 3600.0
+                + caps["m"].parse::<f64>().unwrap_or(0.0) * 60.0
+                + caps["s"].parse::<f64>().unwrap_or(0.0);
+            let suite = self.suite(&caps["suite"], &caps["env"]);
+            suite.status = TestStatus::parse(&caps["status"]);
+            suite.duration_ms = seconds_to_ms(Some(seconds));
+        }
+    }
+
+    /// The suite with this name and environment, created if it hasn't been seen yet.
+    fn suite(&mut self, name: &str, environment: &str) -> &mut TestSuite {
+        let index = match self.suites.iter().position(|s| {
+            s.name == name && (s.environment == environment || s.environment.is_empty())
+        }) {
+            Some(index) => index,
+            None => {
+                self.suites.push(TestSuite {
+                    name: name.to_string(),
+                    environment: environment.to_string(),
+                    status: TestStatus::Passed,
+                    duration_ms: None,
+                    cases: Vec::new(),
+                });
+                self.suites.len() - 1
+            }
+        };
+        let suite = &mut self.suites[index];
+        if suite.environment.is_empty() {
+            suite.environment = environment.to_string();
+        }
+        suite
+    }
+
+    pub fn report(&self, success: bool) -> TestReport {
+        let mut suites = self.suites.clone();
+        for suite in &mut suites {
+            let failed = suite
+                .cases
+                .iter()
+                .any(|c| matches!(c.status, TestStatus::Failed | TestStatus::Errored));
+            if failed && suite.status == TestStatus::Passed {
+                suite.status = TestStatus::Failed;
+            }
+        }
+        let duration_ms = suites
+            .iter()
+            .map(|s| s.duration_ms)
+            .sum::<Option<u64>>()
+            .filter(|_| !suites.is_empty());
+        TestReport::new(success, duration_ms, suites)
+    }
+}
+
+/// Prefers the JSON report at `json_path`, falling back to the parsed console output.
+pub fn load_report(json_path: &Path, output: &TestOutputParser, success: bool) -> TestReport {
+    fs::read_to_string(json_path)
+        .ok()
+        .and_then(|content| parse_json_report(&content, success).ok())
+        .unwrap_or_else(|| output.report(success))
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn xml_seconds(ms: Option<u64>) -> String {
+    format!("{:.3}", ms.unwrap_or(0) as f64 / 1000.0)
+}
+
+/// Renders the report as JUnit XML, one `<testsuite>` per suite and environment.
+pub fn to_junit_xml(report: &TestReport) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">",
+        report.total,
+        report.failed,
+        report.errored,
+        report.skipped,
+        xml_seconds(report.duration_ms)
+    );
+
+    for suite in &report.suites {
+        let count = |status: TestStatus| suite.cases.iter().filter(|c| c.status == status).count();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">",
+            xml_escape(&format!("{}:{}", suite.environment, suite.name)),
+            suite.cases.len(),
+            count(TestStatus::Failed),
+            count(TestStatus::Errored),
+            count(TestStatus::Skipped),
+            xml_seconds(suite.duration_ms)
+        );
+        for case in &suite.cases {
+            let mut attributes = format!(
+                "name=\"{}\" classname=\"{}\" time=\"{}\"",
+                xml_escape(&case.name),
+                xml_escape(&format!("{}.{}", suite.environment, suite.name)),
+                xml_seconds(case.duration_ms)
+            );
+            if let Some(file) = &case.file {
+                let _ = write!(attributes, " file=\"{}\"", xml_escape(file));
+            }
+            if let Some(line) = case.line {
+                let _ = write!(attributes, " line=\"{}\"", line);
+            }
+
+            let message = xml_escape(case.message.as_deref().unwrap_or(""));
+            match case.status {
+                TestStatus::Passed => {
+                    let _ = writeln!(xml, "    <testcase {} />", attributes);
+                }
+                TestStatus::Skipped => {
+                    let _ = writeln!(
+                        xml,
+                        "    <testcase {}>\n      <skipped message=\"{}\" />\n    </testcase>",
+                        attributes, message
+                    );
+                }
+                TestStatus::Failed | TestStatus::Errored => {
+                    let element = if case.status == TestStatus::Failed {
+                        "failure"
+                    } else {
+                        "error"
+                    };
+                    let _ = writeln!(
+                        xml,
+                        "    <testcase {}>\n      <{} message=\"{}\">{}</{}>\n    </testcase>",
+                        attributes, element, message, message, element
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_console_output() {
+        let output = "\
+Processing test_matrix in native environment
+--------------------------------------------------------------------------------
+Building...
+Testing...
+test/test_matrix/test_main.cpp:45: test_xy_to_index [PASSED]
+test/test_matrix/test_main.cpp:52: test_serpentine_rows: Expected 25 Was 24 [FAILED]
+test/test_matrix/test_main.cpp:60: test_rotation: Not implemented [SKIPPED]
+Processing test_state in native environment
+test/test_state/test_main.cpp:12:test_default_state:PASS
+================================ SUMMARY ================================
+Environment    Test         Status    Duration
+-------------  -----------  --------  ------------
+native         test_matrix  FAILED    00:00:01.250
+native         test_state   PASSED    00:00:00.500
+";
+        let mut parser = TestOutputParser::default();
+        for line in output.lines() {
+            parser.push_line(line);
+        }
+        let report = parser.report(false);
+
+        assert_eq!(report.suites.len(), 2);
+        assert_eq!(
+            (report.total, report.passed, report.failed, report.skipped),
+            (4, 2, 1, 1)
+        );
+        assert_eq!(report.duration_ms, Some(1750));
+
+        let matrix = &report.suites[0];
+        assert_eq!(matrix.environment, "native");
+        assert_eq!(matrix.status, TestStatus::Failed);
+        assert_eq!(matrix.duration_ms, Some(1250));
+        let failed = &matrix.cases[1];
+        assert_eq!(failed.message.as_deref(), Some("Expected 25 Was 24"));
+        assert_eq!(
+            failed.file.as_deref(),
+            Some("test/test_matrix/test_main.cpp")
+        );
+        assert_eq!(failed.line, Some(52));
+    }
+
+    #[test]
+    fn test_parses_json_report() {
+        let json = r#"{
+            "version": "1.0",
+            "project_dir": "/repo/apps/led-panel",
+            "duration": 2.5,
+            "testcase_nums": 2, "error_nums": 0, "failure_nums": 1, "skipped_nums": 0,
+            "test_suites": [{
+                "env_name": "native",
+                "test_name": "test_rgbw",
+                "test_dir": "/repo/apps/led-panel/test/test_rgbw",
+                "status": "FAILED",
+                "duration": 1.2,
+                "timestamp": null,
+                "testcase_nums": 2, "error_nums": 0, "failure_nums": 1, "skipped_nums": 0,
+                "test_cases": [
+                    {"name": "test_white_extraction", "status": "PASSED", "message": null, "stdout": "", "duration": 0.01, "exception": null, "source": {"file": "test/test_rgbw/test_main.cpp", "line": 20}},
+                    {"name": "test_gamma", "status": "FAILED", "message": "Expected 128 Was 127", "stdout": "", "duration": null, "exception": null, "source": {"file": "test/test_rgbw/test_main.cpp", "line": 31}}
+                ]
+            }]
+        }"#;
+        let report = parse_json_report(json, false).unwrap();
+        assert_eq!(report.duration_ms, Some(2500));
+        assert_eq!((report.total, report.failed), (2, 1));
+        let suite = &report.suites[0];
+        assert_eq!(suite.name, "test_rgbw");
+        assert_eq!(suite.cases[0].duration_ms, Some(10));
+        assert_eq!(suite.cases[1].line, Some(31));
+        assert_eq!(
+            suite.cases[1].message.as_deref(),
+            Some("Expected 128 Was 127")
+        );
+    }
+
+    #[test]
+    fn test_junit_xml_escapes_and_counts() {
+        let report = TestReport::new(
+            false,
+            Some(1000),
+            vec![TestSuite {
+                name: "test_control".to_string(),
+                environment: "native".to_string(),
+                status: TestStatus::Failed,
+                duration_ms: Some(1000),
+                cases: vec![
+                    TestCase {
+                        name: "test_ok".to_string(),
+                        status: TestStatus::Passed,
+                        duration_ms: None,
+                        message: None,
+                        file: None,
+                        line: None,
+                    },
+                    TestCase {
+                        name: "test_queue".to_string(),
+                        status: TestStatus::Failed,
+                        duration_ms: Some(5),
+                        message: Some("Expected <1> & got \"2\"".to_string()),
+                        file: Some("test/test_control/test_main.cpp".to_string()),
+                        line: Some(40),
+                    },
+                ],
+            }],
+        );
+
+        let xml = to_junit_xml(&report);
+        assert!(xml.contains(
+            "<testsuites tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\" time=\"1.000\">"
+        ));
+        assert!(xml.contains("<testsuite name=\"native:test_control\""));
+        assert!(xml.contains(
+            "<testcase name=\"test_ok\" classname=\"native.test_control\" time=\"0.000\" />"
+        ));
+        assert!(xml.contains("line=\"40\""));
+        assert!(xml.contains("<failure message=\"Expected &lt;1&gt; &amp; got &quot;2&quot;\">"));
+    }
+}
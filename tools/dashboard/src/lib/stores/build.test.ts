@@ -2,6 +2,9 @@ import { describe, it, expect, beforeEach } from 'vitest';
 import { get } from 'svelte/store';
 import {
 	buildState,
+	jobStates,
+	initialBuildState,
+	selectJob,
 	handleBuildEvent,
 	addBuildLine,
 	clearBuildLog,
@@ -12,18 +15,26 @@ import {
 	runTests
 } from './build';
 import { invoke } from '../../test/mocks/tauri';
+import type { TestReport } from '$lib/types/config';
+
+function testReport(success: boolean): TestReport {
+	return {
+		success,
+		duration_ms: 1200,
+		suites: [],
+		total: 1,
+		passed: success ? 1 : 0,
+		failed: success ? 0 : 1,
+		skipped: 0,
+		errored: 0
+	};
+}
 
 describe('build store', () => {
 	beforeEach(() => {
 		// Reset to initial state
-		buildState.set({
-			isBuilding: false,
-			appName: null,
-			environment: null,
-			success: null,
-			durationMs: null,
-			lines: []
-		});
+		buildState.set(initialBuildState());
+		jobStates.set({});
 	});
 
 	describe('handleBuildEvent', () => {
@@ -83,6 +94,71 @@ describe('build store', () => {
 			const state = get(buildState);
 			expect(state.lines).toEqual([]);
 		});
+
+		it('records warnings and diagnostics', () => {
+			handleBuildEvent({ type: 'warning', message: 'I2S0 is claimed twice' });
+			handleBuildEvent({
+				type: 'diagnostic',
+				severity: 'error',
+				source: 'linker',
+				file: 'shared/lib/AudioInput/src/AudioInput.cpp',
+				line: null,
+				column: null,
+				message: "undefined reference to `i2s_driver_install'"
+			});
+
+			const state = get(buildState);
+			expect(state.warnings).toEqual(['I2S0 is claimed twice']);
+			expect(state.lines).toEqual(['WARNING: I2S0 is claimed twice']);
+			expect(state.diagnostics[0].file).toBe('shared/lib/AudioInput/src/AudioInput.cpp');
+		});
+
+		it('keeps reports without the event tag', () => {
+			handleBuildEvent({
+				type: 'filesystem_report',
+				filesystem: 'littlefs',
+				file_count: 1,
+				data_bytes: 17,
+				image_bytes: null,
+				partition: 'storage',
+				partition_size: 4096,
+				last_uploaded_at: null,
+				changes: [{ path: 'index.html', kind: 'added' }]
+			});
+
+			const report = get(buildState).filesystemReport;
+			expect(report?.partition).toBe('storage');
+			expect(report).not.toHaveProperty('type');
+		});
+	});
+
+	describe('job events', () => {
+		it('keeps state per job', () => {
+			handleBuildEvent({ job_id: 'a', type: 'started', app_name: 'led-panel' });
+			handleBuildEvent({ job_id: 'b', type: 'started', app_name: 'dj-booth' });
+			handleBuildEvent({ job_id: 'a', type: 'output', line: 'Compiling led-panel' });
+			handleBuildEvent({ job_id: 'b', type: 'output', line: 'Compiling dj-booth' });
+			handleBuildEvent({ job_id: 'a', type: 'complete', success: true, duration_ms: 10 });
+
+			const jobs = get(jobStates);
+			expect(jobs.a.lines).toEqual(['Compiling led-panel']);
+			expect(jobs.a.success).toBe(true);
+			expect(jobs.b.lines).toEqual(['Compiling dj-booth']);
+			expect(jobs.b.isBuilding).toBe(true);
+
+			// The most recently started job is shown
+			expect(get(buildState).jobId).toBe('b');
+			selectJob('a');
+			expect(get(buildState).appName).toBe('led-panel');
+		});
+
+		it('marks queued jobs', () => {
+			handleBuildEvent({ job_id: 'a', type: 'queued', app_name: 'led-panel' });
+
+			const job = get(jobStates).a;
+			expect(job.isQueued).toBe(true);
+			expect(job.isBuilding).toBe(false);
+		});
 	});
 
 	describe('addBuildLine', () => {
@@ -168,11 +244,28 @@ describe('build store', () => {
 			expect(invoke).toHaveBeenCalledWith('run_build', {
 				appName: 'led-panel',
 				environment: 'esp32',
-				buildFlags: ['-DFOO=1']
+				buildFlags: ['-DFOO=1'],
+				options: null
 			});
 			expect(result).toBe(true);
 		});
 
+		it('passes run options', async () => {
+			invoke.mockResolvedValueOnce(true);
+
+			await runBuild('led-panel', 'esp32', [], {
+				secrets_passphrase: 'hunter2',
+				ignore_preflight_errors: true
+			});
+
+			expect(invoke).toHaveBeenCalledWith('run_build', {
+				appName: 'led-panel',
+				environment: 'esp32',
+				buildFlags: [],
+				options: { secrets_passphrase: 'hunter2', ignore_preflight_errors: true }
+			});
+		});
+
 		it('handles errors gracefully', async () => {
 			invoke.mockRejectedValueOnce(new Error('Build timeout'));
 
@@ -196,7 +289,8 @@ describe('build store', () => {
 				appName: 'led-panel',
 				environment: 'esp32',
 				buildFlags: [],
-				uploadPort: '/dev/ttyUSB0'
+				uploadPort: '/dev/ttyUSB0',
+				options: null
 			});
 			expect(result).toBe(true);
 		});
@@ -210,14 +304,15 @@ describe('build store', () => {
 				appName: 'led-panel',
 				environment: 'esp32',
 				buildFlags: [],
-				uploadPort: null
+				uploadPort: null,
+				options: null
 			});
 		});
 	});
 
 	describe('runTests', () => {
 		it('invokes run_tests', async () => {
-			invoke.mockResolvedValueOnce(true);
+			invoke.mockResolvedValueOnce(testReport(true));
 
 			const result = await runTests('led-panel', 'native');
 
@@ -228,6 +323,14 @@ describe('build store', () => {
 			expect(result).toBe(true);
 		});
 
+		it('returns false when tests fail', async () => {
+			invoke.mockResolvedValueOnce(testReport(false));
+
+			const result = await runTests('led-panel', 'native');
+
+			expect(result).toBe(false);
+		});
+
 		it('handles errors gracefully', async () => {
 			invoke.mockRejectedValueOnce(new Error('Test timeout'));
 
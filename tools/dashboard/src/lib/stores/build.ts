@@ -1,38 +1,78 @@
-import { writable } from 'svelte/store';
-import type { BuildEvent } from '$lib/types/config';
+import { get, writable } from 'svelte/store';
+import type {
+  BuildEvent,
+  Diagnostic,
+  DiagnosticSummary,
+  FilesystemReport,
+  FirmwareSizeReport,
+  RunOptions,
+  TestReport
+} from '$lib/types/config';
 import { invokeWithTimeout } from '$lib/utils/invoke';
 
 // Store mutations are funneled through exported action functions to centralize side effects.
 const MAX_LOG_LINES = 5000;
+const MAX_FINISHED_JOBS = 20;
 const BUILD_TIMEOUT_MS = 10 * 60 * 1000;
 const CLEAN_TIMEOUT_MS = 5 * 60 * 1000;
 
 export interface BuildState {
+  jobId: string | null;
   isBuilding: boolean;
+  isQueued: boolean;
   appName: string | null;
   environment: string | null;
   success: boolean | null;
   durationMs: number | null;
   lines: string[];
+  warnings: string[];
+  diagnostics: Diagnostic[];
+  diagnosticSummary: DiagnosticSummary | null;
+  sizeReport: FirmwareSizeReport | null;
+  filesystemReport: FilesystemReport | null;
+  testReport: TestReport | null;
 }
 
-export const buildState = writable<BuildState>({
-  isBuilding: false,
-  appName: null,
-  environment: null,
-  success: null,
-  durationMs: null,
-  lines: []
-});
+export function initialBuildState(jobId: string | null = null): BuildState {
+  return {
+    jobId,
+    isBuilding: false,
+    isQueued: false,
+    appName: null,
+    environment: null,
+    success: null,
+    durationMs: null,
+    lines: [],
+    warnings: [],
+    diagnostics: [],
+    diagnosticSummary: null,
+    sizeReport: null,
+    filesystemReport: null,
+    testReport: null
+  };
+}
+
+// The job shown in the output panel: the one started most recently, unless another was selected
+export const buildState = writable<BuildState>(initialBuildState());
+
+// Every job the dashboard has seen events for, keyed by job ID
+export const jobStates = writable<Record<string, BuildState>>({});
 
 // Actions
 export function resetBuildState(): void {
   buildState.update((state) => ({
     ...state,
     isBuilding: false,
+    isQueued: false,
     success: null,
     durationMs: null,
-    lines: []
+    lines: [],
+    warnings: [],
+    diagnostics: [],
+    diagnosticSummary: null,
+    sizeReport: null,
+    filesystemReport: null,
+    testReport: null
   }));
 }
 
@@ -51,51 +91,128 @@ export function clearBuildStatus(): void {
   }));
 }
 
+function withLine(state: BuildState, line: string): BuildState {
+  const lines = [...state.lines, line];
+  // Keep only last MAX_LOG_LINES
+  if (lines.length > MAX_LOG_LINES) {
+    return { ...state, lines: lines.slice(-MAX_LOG_LINES) };
+  }
+  return { ...state, lines };
+}
+
 export function addBuildLine(line: string): void {
-  buildState.update((state) => {
-    const newLines = [...state.lines, line];
-    // Keep only last MAX_LOG_LINES
-    if (newLines.length > MAX_LOG_LINES) {
-      return { ...state, lines: newLines.slice(-MAX_LOG_LINES) };
-    }
-    return { ...state, lines: newLines };
-  });
+  buildState.update((state) => withLine(state, line));
 }
 
-export function handleBuildEvent(event: BuildEvent): void {
+/** Applies one build event to a job's state. */
+export function applyBuildEvent(state: BuildState, event: BuildEvent): BuildState {
   switch (event.type) {
+    case 'queued':
+      return {
+        ...initialBuildState(state.jobId),
+        isQueued: true,
+        appName: event.app_name ?? null,
+        environment: event.environment ?? null,
+        lines: [`Waiting for another job on ${event.app_name ?? 'this app'} to finish...`]
+      };
+
     case 'started':
-      buildState.update((state) => ({
-        ...state,
+      return {
+        ...initialBuildState(state.jobId),
         isBuilding: true,
         appName: event.app_name ?? null,
-        environment: event.environment ?? null,
-        success: null,
-        durationMs: null,
-        lines: []
-      }));
-      break;
+        environment: event.environment ?? null
+      };
 
     case 'output':
-      if (event.line) {
-        addBuildLine(event.line);
-      }
-      break;
+      return event.line ? withLine(state, event.line) : state;
 
     case 'error':
-      if (event.message) {
-        addBuildLine(`ERROR: ${event.message}`);
+      return event.message ? withLine(state, `ERROR: ${event.message}`) : state;
+
+    case 'warning':
+      if (!event.message) {
+        return state;
       }
-      break;
+      return {
+        ...withLine(state, `WARNING: ${event.message}`),
+        warnings: [...state.warnings, event.message]
+      };
+
+    case 'diagnostic': {
+      const { type: _type, job_id: _jobId, ...diagnostic } = event;
+      return { ...state, diagnostics: [...state.diagnostics, diagnostic] };
+    }
+
+    case 'size_report': {
+      const { type: _type, job_id: _jobId, ...report } = event;
+      return { ...state, sizeReport: report };
+    }
+
+    case 'filesystem_report': {
+      const { type: _type, job_id: _jobId, ...report } = event;
+      return { ...state, filesystemReport: report };
+    }
+
+    case 'test_report': {
+      const { type: _type, job_id: _jobId, ...report } = event;
+      return { ...state, testReport: report };
+    }
 
     case 'complete':
-      buildState.update((state) => ({
+      return {
         ...state,
         isBuilding: false,
+        isQueued: false,
         success: event.success ?? false,
-        durationMs: event.duration_ms ?? null
-      }));
-      break;
+        durationMs: event.duration_ms ?? null,
+        diagnosticSummary: event.diagnostics ?? state.diagnosticSummary
+      };
+  }
+  return state;
+}
+
+/** Drops the oldest finished jobs beyond MAX_FINISHED_JOBS. */
+function pruneFinishedJobs(states: Record<string, BuildState>): Record<string, BuildState> {
+  const finished = Object.keys(states).filter(
+    (id) => !states[id].isBuilding && !states[id].isQueued
+  );
+  const excess = finished.slice(0, Math.max(0, finished.length - MAX_FINISHED_JOBS));
+  if (excess.length === 0) {
+    return states;
+  }
+  const kept = { ...states };
+  for (const id of excess) {
+    delete kept[id];
+  }
+  return kept;
+}
+
+export function handleBuildEvent(event: BuildEvent): void {
+  // Events without a job ID come from a single anonymous job
+  if (!event.job_id) {
+    buildState.update((state) => applyBuildEvent(state, event));
+    return;
+  }
+
+  const jobId = event.job_id;
+  let next = initialBuildState(jobId);
+  jobStates.update((states) => {
+    next = applyBuildEvent(states[jobId] ?? initialBuildState(jobId), event);
+    return pruneFinishedJobs({ ...states, [jobId]: next });
+  });
+
+  const shown = get(buildState);
+  if (shown.jobId === jobId || event.type === 'started') {
+    buildState.set(next);
+  }
+}
+
+/** Shows a job's state in the output panel. */
+export function selectJob(jobId: string): void {
+  const state = get(jobStates)[jobId];
+  if (state) {
+    buildState.set(state);
   }
 }
 
@@ -104,7 +221,8 @@ export function handleBuildEvent(event: BuildEvent): void {
 export async function runBuild(
   appName: string,
   environment: string,
-  buildFlags: string[]
+  buildFlags: string[],
+  options?: RunOptions
 ): Promise<boolean> {
   try {
     const success = await invokeWithTimeout<boolean>(
@@ -112,7 +230,8 @@ export async function runBuild(
       {
         appName,
         environment,
-        buildFlags
+        buildFlags,
+        options: options ?? null
       },
       BUILD_TIMEOUT_MS
     );
@@ -128,7 +247,8 @@ export async function runUpload(
   appName: string,
   environment: string,
   buildFlags: string[],
-  uploadPort?: string
+  uploadPort?: string,
+  options?: RunOptions
 ): Promise<boolean> {
   try {
     const success = await invokeWithTimeout<boolean>(
@@ -137,7 +257,8 @@ export async function runUpload(
         appName,
         environment,
         buildFlags,
-        uploadPort: uploadPort ?? null
+        uploadPort: uploadPort ?? null,
+        options: options ?? null
       },
       BUILD_TIMEOUT_MS
     );
@@ -151,7 +272,7 @@ export async function runUpload(
 
 export async function runTests(appName: string, environment: string): Promise<boolean> {
   try {
-    const success = await invokeWithTimeout<boolean>(
+    const report = await invokeWithTimeout<TestReport>(
       'run_tests',
       {
         appName,
@@ -159,7 +280,7 @@ export async function runTests(appName: string, environment: string): Promise<bo
       },
       BUILD_TIMEOUT_MS
     );
-    return success;
+    return report.success;
   } catch (error) {
     addBuildLine(`Tests failed: ${error}`);
     buildState.update((state) => ({ ...state, isBuilding: false, success: false }));
@@ -29,6 +29,13 @@ export interface EnumValue {
 
 export type ValueType = 'integer' | 'float' | 'boolean' | 'string' | 'enum' | 'pin';
 
+// Integer range from an `#if ... #error` guard in the header
+export interface ValueConstraint {
+  min: number | null;
+  max: number | null;
+  message: string;
+}
+
 export interface ConfigDefine {
   name: string;
   default_value: string;
@@ -36,6 +43,15 @@ export interface ConfigDefine {
   enum_values: EnumValue[] | null;
   platform: string | null;
   description: string | null;
+  section: string | null;
+  constraints: ValueConstraint[];
+  environments: string[];
+  header: string;              // Relative to the app directory
+  group: string | null;        // Display group from the dashboard manifest
+  min: number | null;
+  max: number | null;
+  step: number | null;
+  unit: string | null;
 }
 
 // Enhanced metadata for UI display (merged with ConfigDefine at runtime)
@@ -53,8 +69,8 @@ export interface FieldMeta {
   unit?: string;             // Display unit (e.g., "ms", "px")
 }
 
-// ConfigDefine with enhanced metadata merged in
-export interface EnhancedDefine extends ConfigDefine {
+// ConfigDefine with enhanced metadata merged in (manifest group/min/max/unit are resolved)
+export interface EnhancedDefine extends Omit<ConfigDefine, 'group' | 'min' | 'max' | 'unit'> {
   label: string;
   group: string;
   min?: number;
@@ -67,10 +83,25 @@ export interface EnhancedDefine extends ConfigDefine {
   unit?: string;
 }
 
+export interface DisplayGroup {
+  name: string;
+  defines: string[];
+}
+
 export interface AppConfigSchema {
   has_config: boolean;
   defines: ConfigDefine[];
   platform_conditional: Record<string, ConfigDefine[]>;
+  headers: string[];           // Scanned headers in include order
+  groups: DisplayGroup[];
+}
+
+export interface IniDiagnostic {
+  kind: 'unresolved_reference' | 'interpolation_cycle';
+  section: string;
+  option: string;
+  reference: string;
+  message: string;
 }
 
 export interface AppInfo {
@@ -78,6 +109,7 @@ export interface AppInfo {
   path: string;
   has_config: boolean;
   environments: DiscoveredEnvironment[];
+  ini_diagnostics: IniDiagnostic[];
   config_schema: AppConfigSchema;
 }
 
@@ -104,14 +136,114 @@ export interface PinValidation {
 
 export type PinPurpose = 'output' | 'input' | 'adc' | 'i2c' | 'spi' | 'i2s' | 'pwm';
 
-export interface BuildEvent {
-  type: 'output' | 'error' | 'complete' | 'started';
-  line?: string;
-  message?: string;
-  success?: boolean;
-  duration_ms?: number;
-  app_name?: string;
-  environment?: string;
+export interface Diagnostic {
+  severity: 'error' | 'warning' | 'note';
+  source: 'compiler' | 'linker' | 'platformio';
+  file: string | null;
+  line: number | null;
+  column: number | null;
+  message: string;
+}
+
+export interface DiagnosticSummary {
+  errors: number;
+  warnings: number;
+  diagnostics: Diagnostic[];
+}
+
+export interface MemoryUsage {
+  used: number;
+  total: number;
+  percent: number;
+}
+
+export interface FirmwareSizeReport {
+  app_name: string;
+  environment: string;
+  recorded_at: number;
+  ram: MemoryUsage | null;
+  flash: MemoryUsage | null;
+  sections: { name: string; size: number }[];
+  top_symbols: { name: string; size: number; kind: string }[];
+  top_objects: { name: string; size: number }[];
+  app_partition_size: number | null;
+  delta: {
+    previous_recorded_at: number;
+    ram_bytes: number | null;
+    flash_bytes: number | null;
+    sections: { name: string; before: number; after: number }[];
+  } | null;
+}
+
+export interface FilesystemReport {
+  filesystem: string;
+  file_count: number;
+  data_bytes: number;
+  image_bytes: number | null;
+  partition: string | null;
+  partition_size: number | null;
+  last_uploaded_at: number | null;
+  changes: { path: string; kind: 'added' | 'removed' | 'changed' }[];
+}
+
+// Sent as "build-event", tagged with the job it belongs to
+export type BuildEvent = { job_id?: string } & (
+  | { type: 'started' | 'queued'; app_name?: string; environment?: string }
+  | { type: 'output'; line?: string }
+  | { type: 'error' | 'warning'; message?: string }
+  | { type: 'complete'; success?: boolean; duration_ms?: number; diagnostics?: DiagnosticSummary }
+  | ({ type: 'diagnostic' } & Diagnostic)
+  | ({ type: 'size_report' } & FirmwareSizeReport)
+  | ({ type: 'filesystem_report' } & FilesystemReport)
+  | ({ type: 'test_report' } & TestReport)
+);
+
+export interface PowerOptions {
+  brightness_cap: number;
+  wire_awg: number;
+  run_length_m: number;
+  leds_per_meter?: number;
+  strip_type?: 'sk6812_rgbw' | 'ws2815b_rgb' | 'ws2812b_rgb';
+  psu_amps?: number;
+}
+
+// Optional extras for run_build and run_upload
+export interface RunOptions {
+  secrets_passphrase?: string;
+  power_check?: PowerOptions;
+  ignore_preflight_errors?: boolean;
+  profile_name?: string;
+}
+
+export type TestStatus = 'passed' | 'failed' | 'skipped' | 'errored';
+
+export interface TestCase {
+  name: string;
+  status: TestStatus;
+  duration_ms: number | null;
+  message: string | null;
+  file: string | null;
+  line: number | null;
+}
+
+export interface TestSuite {
+  name: string;
+  environment: string;
+  status: TestStatus;
+  duration_ms: number | null;
+  cases: TestCase[];
+}
+
+// Result of run_tests; also sent as a test_report build event
+export interface TestReport {
+  success: boolean;
+  duration_ms: number | null;
+  suites: TestSuite[];
+  total: number;
+  passed: number;
+  failed: number;
+  skipped: number;
+  errored: number;
 }
 
 export interface SerialEvent {
@@ -10,6 +10,15 @@ const makeDefine = (overrides: Partial<ConfigDefine> = {}): ConfigDefine => ({
 	enum_values: null,
 	platform: null,
 	description: null,
+	section: null,
+	constraints: [],
+	environments: [],
+	header: 'src/config.h',
+	group: null,
+	min: null,
+	max: null,
+	step: null,
+	unit: null,
 	...overrides
 });
 
@@ -44,6 +53,17 @@ describe('enhanceDefine', () => {
 		expect(enhanced.max).toBe(500);
 	});
 
+	it('falls back to manifest group and limits for fields without metadata', () => {
+		const define = makeDefine({ name: 'AUDIO_GAIN', group: 'Audio', min: 0, max: 8, unit: 'x' });
+		const enhanced = enhanceDefine(define);
+
+		expect(enhanced.group).toBe('Audio');
+		expect(enhanced.advanced).toBe(false);
+		expect(enhanced.min).toBe(0);
+		expect(enhanced.max).toBe(8);
+		expect(enhanced.unit).toBe('x');
+	});
+
 	it('converts enumDescriptions to enum_values when backend has none', () => {
 		const define = makeDefine({ name: 'STRIP_TYPE', value_type: 'integer' });
 		const enhanced = enhanceDefine(define);
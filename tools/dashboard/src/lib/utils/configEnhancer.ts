@@ -9,8 +9,8 @@ import { FIELD_METADATA, toHumanLabel, FIELD_GROUPS } from '$lib/config/fieldMet
 export function enhanceDefine(define: ConfigDefine): EnhancedDefine {
   const meta = FIELD_METADATA[define.name];
 
-  // Determine group (defaults to Advanced if no metadata)
-  const group = meta?.group ?? FIELD_GROUPS.ADVANCED;
+  // Determine group (static metadata, then the app's manifest, then Advanced)
+  const group = meta?.group ?? define.group ?? FIELD_GROUPS.ADVANCED;
 
   // Fields in Advanced group should default to advanced: true
   // This ensures unknown fields are hidden by the toggle
@@ -39,14 +39,14 @@ export function enhanceDefine(define: ConfigDefine): EnhancedDefine {
     label: meta?.label ?? toHumanLabel(define.name),
     description: meta?.description ?? define.description ?? null,
     group,
-    min: meta?.min,
-    max: meta?.max,
+    min: meta?.min ?? define.min ?? undefined,
+    max: meta?.max ?? define.max ?? undefined,
     advanced,
     helpUrl: meta?.helpUrl,
     enumDescriptions: meta?.enumDescriptions,
     readOnly: meta?.readOnly ?? false,
     derivedFrom: meta?.derivedFrom,
-    unit: meta?.unit
+    unit: meta?.unit ?? define.unit ?? undefined
   };
 }
 
@@ -54,7 +54,16 @@ export const mockConfigDefine: ConfigDefine = {
 	value_type: 'integer',
 	enum_values: null,
 	platform: null,
-	description: 'Number of LEDs in the horizontal direction'
+	description: 'Number of LEDs in the horizontal direction',
+	section: null,
+	constraints: [],
+	environments: [],
+	header: 'src/config.h',
+	group: null,
+	min: null,
+	max: null,
+	step: null,
+	unit: null
 };
 
 export const mockEnumDefine: ConfigDefine = {
@@ -66,7 +75,16 @@ export const mockEnumDefine: ConfigDefine = {
 		{ value: '1', label: 'WS2815B RGB' }
 	],
 	platform: null,
-	description: 'LED strip type'
+	description: 'LED strip type',
+	section: null,
+	constraints: [],
+	environments: [],
+	header: 'src/config.h',
+	group: null,
+	min: null,
+	max: null,
+	step: null,
+	unit: null
 };
 
 export const mockBoolDefine: ConfigDefine = {
@@ -75,7 +93,16 @@ export const mockBoolDefine: ConfigDefine = {
 	value_type: 'boolean',
 	enum_values: null,
 	platform: 'ESP32',
-	description: 'Enable WiFi connectivity'
+	description: 'Enable WiFi connectivity',
+	section: null,
+	constraints: [],
+	environments: [],
+	header: 'src/config.h',
+	group: null,
+	min: null,
+	max: null,
+	step: null,
+	unit: null
 };
 
 export const mockPinDefine: ConfigDefine = {
@@ -84,7 +111,16 @@ export const mockPinDefine: ConfigDefine = {
 	value_type: 'pin',
 	enum_values: null,
 	platform: null,
-	description: 'Data pin for LED strip'
+	description: 'Data pin for LED strip',
+	section: null,
+	constraints: [],
+	environments: [],
+	header: 'src/config.h',
+	group: null,
+	min: null,
+	max: null,
+	step: null,
+	unit: null
 };
 
 // Config schema
@@ -93,7 +129,9 @@ export const mockConfigSchema: AppConfigSchema = {
 	defines: [mockConfigDefine, mockEnumDefine, mockBoolDefine],
 	platform_conditional: {
 		ESP32: [mockPinDefine]
-	}
+	},
+	headers: ['src/config.h'],
+	groups: []
 };
 
 // App info
@@ -102,6 +140,7 @@ export const mockAppInfo: AppInfo = {
 	path: '/home/user/projects/rgbw-lighting/apps/led-panel',
 	has_config: true,
 	environments: [mockEsp32Env, mockEsp8266Env, mockNativeEnv],
+	ini_diagnostics: [],
 	config_schema: mockConfigSchema
 };
 